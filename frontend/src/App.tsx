@@ -1,4 +1,4 @@
-import {useCallback, useState} from 'react'
+import {useCallback, useRef, useState} from 'react'
 import Input from './Input'
 import * as wasm from 'sfl_wasm_lib'
 import './App.css'
@@ -20,12 +20,20 @@ function App() {
   const [selectedRcToStringHistory, setSelectedRcToStringHistory] = useState<string[]>([]);
   const [settingsIsVisible, setSettingsIsVisible] = useState(false);
   const [multiple, setMultiple] = useState(false);
+  // The state redexes are picked from. The others in the history only keep main
+  const liveAst = useRef<wasm.RawASTInfo | null>(null);
 
   const generateRCs = (ast: wasm.RawASTInfo, _multiple: boolean) => {
+    liveAst.current = ast;
     try {
       const rcs = _multiple ? wasm.get_all_redexes(ast) : wasm.get_one_redex(ast);
 
       if (wasm.get_rcs_len(rcs) === 0) {
+        // Either main is in normal form, or the reduction has hit its step or size limit
+        const exceeded = wasm.step_budget_exceeded(ast);
+        if (exceeded) {
+          setErrorString(exceeded);
+        }
         setRcs([]);
         return;
       }
@@ -91,7 +99,11 @@ function App() {
   const resetTo = useCallback((n: number) => {
     setAstHistory((prevAstHistory) => {
       const new_slice = prevAstHistory.slice(0, n);
-      generateRCs(new_slice[new_slice.length - 1], multiple);
+      const last = new_slice[new_slice.length - 1];
+      if (liveAst.current !== null && liveAst.current !== last) {
+        wasm.restore(liveAst.current, last);
+      }
+      generateRCs(last, multiple);
       return new_slice
     });
   }, [generateRCs, multiple])
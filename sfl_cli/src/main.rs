@@ -1,18 +1,186 @@
-use sfl_lib::{self as lib, typecheck};
+use sfl_lib::{
+    self as lib, explore_reductions_with, lint_declared_types, reduce_within_budget,
+    strategy_from_name, typecheck, typecheck_hm, Budget, EvaluationConfig, EvaluationStrategy,
+    NormalOrder, Outcome, RCPair, ReductionGraph, ReductionRules, TypeChecker, DEFAULT_GRAPH_BOUND,
+    DEFAULT_RUN_STEP_LIMIT, DEFAULT_SIZE_BUDGET, DEFAULT_STEP_LIMIT,
+};
 use std::{env, fs};
 
 static HORIZONTAL_SEPARATOR: &str =
     "______________________________________________________________";
 
+enum GraphFormat {
+    Text,
+    Dot,
+    Json,
+}
+
+struct Args {
+    file_path: String,
+    checker: TypeChecker,
+    /// Typecheck main after every step, stopping at the first that changes its type
+    check_preservation: bool,
+    /// Which redex to contract at each step
+    strategy: Box<dyn EvaluationStrategy>,
+    /// The most nodes main can grow to before the reduction is stopped as never terminating
+    max_size: usize,
+    /// The most steps to take before the reduction, or the interpreter, is stopped
+    max_steps: usize,
+    /// Print only the value of main, from the interpreter rather than the stepper
+    run: bool,
+    /// Print only the steps, as a JSON trace
+    json_trace: bool,
+    /// Print the graph of every way main reduces instead of stepping, in this format
+    graph: Option<GraphFormat>,
+    /// The most terms to put in the graph
+    max_terms: usize,
+    /// Whether to reduce under lambdas and eta-reduce, for stepping and graphs
+    rules: ReductionRules,
+    /// Labels whose calls are evaluated in one step, along with any the file's pragmas name
+    opaque: Vec<String>,
+    /// Make every label the prelude defines opaque
+    opaque_prelude: bool,
+}
+
+// sfl [--typecheck bidirectional|w|j] [--check-preservation]
+//     [--strategy normal|need|applicative|innermost-rightmost|random[:seed]]
+//     [--max-size <nodes>] [--max-steps <steps>] [--trace text|json] [--under-lambdas] [--eta]
+//     [--opaque <label>[,<label>...]] [--opaque-prelude] <file>
+// sfl run [--typecheck bidirectional|w|j] [--max-steps <steps>] <file>
+// sfl graph [--typecheck bidirectional|w|j] [--max-terms <terms>] [--format text|dot|json]
+//     [--under-lambdas] [--eta] [--opaque <label>[,<label>...]] [--opaque-prelude] <file>
+fn parse_args(argv: &[String]) -> Result<Args, String> {
+    let mut file_path = None;
+    let mut checker = TypeChecker::Bidirectional;
+    let mut check_preservation = false;
+    let mut strategy: Box<dyn EvaluationStrategy> = Box::new(NormalOrder);
+    let mut max_size = DEFAULT_SIZE_BUDGET;
+    let mut max_steps = None;
+    let mut json_trace = false;
+    let mut graph_format = GraphFormat::Text;
+    let mut max_terms = DEFAULT_GRAPH_BOUND;
+    let mut rules = ReductionRules::default();
+    let mut opaque = vec![];
+    let mut opaque_prelude = false;
+    let run = argv.get(1).is_some_and(|arg| arg == "run");
+    let graph = argv.get(1).is_some_and(|arg| arg == "graph");
+    let stepping = !run && !graph;
+
+    let mut args = argv.iter().skip(if stepping { 1 } else { 2 });
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--typecheck" => {
+                checker = args
+                    .next()
+                    .ok_or("--typecheck needs a typechecker")?
+                    .parse()?
+            }
+            "--check-preservation" if stepping => check_preservation = true,
+            "--strategy" if stepping => {
+                strategy = strategy_from_name(args.next().ok_or("--strategy needs a strategy")?)?
+            }
+            "--max-size" if stepping => {
+                max_size = args
+                    .next()
+                    .ok_or("--max-size needs a number of nodes")?
+                    .parse()
+                    .map_err(|_| "--max-size needs a number of nodes")?
+            }
+            "--max-steps" if !graph => {
+                max_steps = Some(
+                    args.next()
+                        .ok_or("--max-steps needs a number of steps")?
+                        .parse()
+                        .map_err(|_| "--max-steps needs a number of steps")?,
+                )
+            }
+            "--trace" if stepping => {
+                json_trace = match args.next().map(|format| format.as_str()) {
+                    Some("text") => false,
+                    Some("json") => true,
+                    _ => return Err("--trace needs a format, text or json".to_string()),
+                }
+            }
+            "--max-terms" if graph => {
+                max_terms = args
+                    .next()
+                    .ok_or("--max-terms needs a number of terms")?
+                    .parse()
+                    .map_err(|_| "--max-terms needs a number of terms")?
+            }
+            "--format" if graph => {
+                graph_format = match args.next().map(|format| format.as_str()) {
+                    Some("text") => GraphFormat::Text,
+                    Some("dot") => GraphFormat::Dot,
+                    Some("json") => GraphFormat::Json,
+                    _ => return Err("--format needs a format, text, dot or json".to_string()),
+                }
+            }
+            "--under-lambdas" if !run => rules.under_lambdas = true,
+            "--eta" if !run => rules.eta = true,
+            "--opaque" if !run => opaque.extend(
+                args.next()
+                    .ok_or("--opaque needs a list of labels")?
+                    .split(',')
+                    .filter(|label| !label.is_empty())
+                    .map(|label| label.to_string()),
+            ),
+            "--opaque-prelude" if !run => opaque_prelude = true,
+            _ if file_path.is_none() && !arg.starts_with("--") => file_path = Some(arg.clone()),
+            _ => return Err("Incorrect args".to_string()),
+        }
+    }
+
+    // Thunks are shared on the assumption that they are closed, which they aren't under lambdas
+    if rules.under_lambdas && strategy.shares_arguments() {
+        return Err(format!(
+            "--under-lambdas can't be used with --strategy {}",
+            strategy.name()
+        ));
+    }
+
+    Ok(Args {
+        file_path: file_path.ok_or("Incorrect args")?,
+        checker,
+        check_preservation,
+        strategy,
+        max_size,
+        // The interpreter's steps are much smaller than the stepper's
+        max_steps: max_steps.unwrap_or(if run {
+            DEFAULT_RUN_STEP_LIMIT
+        } else {
+            DEFAULT_STEP_LIMIT
+        }),
+        run,
+        json_trace,
+        graph: graph.then_some(graph_format),
+        max_terms,
+        rules,
+        opaque,
+        opaque_prelude,
+    })
+}
+
 fn main() {
     let argv: Vec<String> = env::args().collect();
-
-    let file_path = if argv.len() == 2 {
-        argv[1].clone()
-    } else {
-        eprintln!("Incorrect args");
+    let Args {
+        file_path,
+        checker,
+        check_preservation,
+        mut strategy,
+        max_size,
+        max_steps,
+        run,
+        json_trace,
+        graph,
+        max_terms,
+        rules,
+        opaque,
+        opaque_prelude,
+    } = parse_args(&argv).unwrap_or_else(|e| {
+        eprintln!("{}", e);
         std::process::exit(1);
-    };
+    });
 
     let file_string = if fs::metadata(&file_path).is_ok() {
         fs::read_to_string(&file_path).expect("Failed to read file")
@@ -21,7 +189,7 @@ fn main() {
         std::process::exit(1);
     };
 
-    let pr = lib::Parser::from_string(file_string).parse_module(true);
+    let pr = lib::Parser::from_string(file_string.clone()).parse_module(true);
     if let Err(e) = &pr {
         eprintln!("{:?}", e);
         std::process::exit(1);
@@ -31,25 +199,73 @@ fn main() {
     let mut lt = pr.lt;
     let tm = pr.tm;
 
+    let mut opaque = opaque;
+    if opaque_prelude {
+        opaque.extend(ast.get_prelude_labels(ast.root));
+    }
+    for label in opaque {
+        lt.set_opaque(&label).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+    }
+
+    // Running, graphs and JSON traces only print their result
+    let quiet = run || json_trace || graph.is_some();
+
     // Typecheck
     let module = ast.root;
-    println!(
-        "INPUT:\n\n{}\n{}",
-        ast.to_string_sugar(ast.root, true),
-        HORIZONTAL_SEPARATOR
-    );
-    typecheck(&mut ast, module, &mut lt, &tm).unwrap_or_else(|e| {
-        eprintln!("{:?}", e);
+    let lints = lint_declared_types(&ast, module, &lt, &tm);
+    if !quiet {
+        println!(
+            "INPUT:\n\n{}\n{}",
+            ast.to_string_sugar(ast.root, true),
+            HORIZONTAL_SEPARATOR
+        );
+    }
+    let result = match checker {
+        TypeChecker::Bidirectional => typecheck(&mut ast, module, &mut lt, &tm),
+        TypeChecker::HindleyMilner(algorithm) => {
+            let (result, traces) = typecheck_hm(&mut ast, module, &mut lt, &tm, algorithm);
+
+            // Only the unifications for labels in the file, not the prelude
+            if !quiet {
+                println!("Unifications:");
+                for (label, trace) in traces {
+                    if trace.iter().all(|u| ast.is_from_prelude(u.node)) {
+                        continue;
+                    }
+                    println!("{}:", label);
+                    for unification in trace {
+                        println!("  {}", unification);
+                    }
+                }
+                println!("{}", HORIZONTAL_SEPARATOR);
+            }
+            result
+        }
+    };
+    result.unwrap_or_else(|e| {
+        eprint!("{}", e.render(&file_string));
         std::process::exit(1)
     });
+    for lint in lints {
+        if quiet {
+            eprint!("{}", lint.render(&file_string));
+        } else {
+            print!("{}", lint.render(&file_string));
+        }
+    }
 
-    println!(
-        "Typed: \n{}\n{}\n",
-        ast.to_string_sugar(ast.root, true),
-        HORIZONTAL_SEPARATOR
-    );
+    if !quiet {
+        println!(
+            "Typed: \n{}\n{}\n",
+            ast.to_string_sugar(ast.root, true),
+            HORIZONTAL_SEPARATOR
+        );
+    }
 
-    let mut main_expr = ast.get_assign_exp(match ast.get_main(ast.root) {
+    let main_expr = ast.get_assign_exp(match ast.get_main(ast.root) {
         Some(v) => v,
         None => {
             eprintln!("Main not found");
@@ -57,40 +273,113 @@ fn main() {
         }
     });
 
-    let mut rcs = lib::find_single_redex_contraction_pair(&ast, Some(ast.root), main_expr, &lt);
+    if run {
+        match lib::evaluate_within(&ast, ast.root, main_expr, &lt, max_steps) {
+            Some(value) => println!("{}", value.to_string_sugar(value.root, false)),
+            None => {
+                let outcome = Outcome::BudgetExceeded {
+                    budget: Budget::Steps,
+                    limit: max_steps,
+                };
+                eprintln!("{}", outcome);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    println!("{}", ast.to_string_sugar(main_expr, false));
-    let mut i = 0;
+    if let Some(format) = graph {
+        let graph = explore_reductions_with(&ast, &lt, max_terms, rules);
+        match format {
+            GraphFormat::Text => print_graph(&graph),
+            GraphFormat::Dot => print!("{}", graph.to_dot()),
+            GraphFormat::Json => print!("{}", graph.to_json()),
+        }
+        return;
+    }
+
+    // Call-by-need binds each thunk once with a let, rather than printing it at every use
+    let shares = strategy.shares_arguments();
+    let show = move |ast: &lib::AST, expr: usize| {
+        if shares {
+            ast.to_string_shared(expr)
+        } else {
+            ast.to_string_sugar(expr, false)
+        }
+    };
 
-    while let Some(rc) = rcs {
+    // Print each step as it is taken, with main as it was before it
+    let mut i = 0;
+    let print_step = move |ast: &lib::AST, main_expr: usize, rc: &RCPair| {
+        if i > 0 {
+            println!("\n{}", show(ast, main_expr));
+        }
         let s1 = ast.to_string_sugar(rc.from, false);
         let s2 = rc.to.to_string_sugar(rc.to.root, false);
-        println!("{i}: Next: {} => {}", s1, s2);
-
-        // io::stdout().flush().unwrap();
-        //
-        // let mut input = String::new();
-        // io::stdin()
-        //     .read_line(&mut input)
-        //     .expect("Failed to read line");
-        // input = input.trim().to_string();
-
-        main_expr = ast.get_assign_exp(match ast.get_main(ast.root) {
-            Some(v) => v,
-            None => {
-                panic!("Main not found, should have been caught by parser");
-            }
-        });
-
+        let shared = ast.get_shared(main_expr);
+        match shared.iter().position(|(thunk, _)| *thunk == rc.from) {
+            Some(k) if shares => println!(
+                "{i}: Next: #{} = {} => {} (evaluated once for its {} uses)",
+                k + 1,
+                s1,
+                s2,
+                shared[k].1
+            ),
+            _ => println!("{i}: Next: {} => {}", s1, s2),
+        }
         i += 1;
+    };
 
-        ast.do_rc_subst(main_expr, &rc);
+    // The reduction stops if it loops, keeps growing or runs too long, rather than stepping
+    // forever
+    let config = EvaluationConfig {
+        max_steps,
+        max_nodes: max_size,
+        cancel: None,
+        rules,
+        check_preservation: check_preservation.then_some(&tm),
+        before_step: (!json_trace).then(|| Box::new(print_step) as lib::BeforeStep),
+    };
+    if !json_trace {
+        println!("{}", show(&ast, main_expr));
+    }
+    let reduction = reduce_within_budget(&mut ast, &lt, &mut *strategy, config);
 
-        if i == 12 {
-            print!("");
+    if json_trace {
+        print!("{}", reduction.to_json());
+    } else if let Some(step) = reduction.steps.last() {
+        println!("\n{}", step.term);
+    }
+    if reduction.outcome != Outcome::NormalForm {
+        if !json_trace {
+            eprintln!("\n{}", reduction.outcome);
         }
+        std::process::exit(1);
+    }
+}
 
-        rcs = lib::find_single_redex_contraction_pair(&ast, Some(ast.root), main_expr, &lt);
-        println!("\n{}", ast.to_string_sugar(main_expr, false));
+/// How many terms the graph has, and how main gets to each normal form
+fn print_graph(graph: &ReductionGraph) {
+    println!("{} terms, {} steps", graph.terms.len(), graph.steps.len());
+    if !graph.complete {
+        println!("Stopped at the bound, so there may be more terms and normal forms");
+    }
+
+    let normal_forms = graph.normal_forms();
+    match normal_forms.len() {
+        0 => println!("No normal form found"),
+        1 => println!("Every reduction found reaches the same normal form"),
+        n => println!("{} different normal forms found", n),
+    }
+    for term in normal_forms {
+        println!("\n{}", graph.terms[term].term);
+        println!(
+            "  Shortest reduction: {} steps",
+            graph.shortest_path(term).len() - 1
+        );
+        match graph.longest_path(term) {
+            Some(path) => println!("  Longest reduction: {} steps", path.len() - 1),
+            None => println!("  Longest reduction: unbounded, as it can go round a cycle"),
+        }
     }
 }
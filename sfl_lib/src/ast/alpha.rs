@@ -0,0 +1,206 @@
+use super::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// Terms that only differ in the names of their bound variables are alpha-equivalent. Hashing a
+// bound variable by how many binders out it was bound, rather than by its name, gives them the
+// same hash.
+
+impl AST {
+    /// A hash of expr that is the same for alpha-equivalent expressions. Shared nodes are hashed
+    /// at each of their uses, as they are printed
+    pub fn alpha_hash(&self, expr: usize) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash_expr(expr, &mut vec![], &mut hasher);
+        hasher.finish()
+    }
+
+    /// The number of nodes in expr, counting shared nodes at each of their uses
+    pub fn term_size(&self, expr: usize) -> usize {
+        1 + self
+            .get_children(expr)
+            .iter()
+            .map(|c| self.term_size(*c))
+            .sum::<usize>()
+    }
+
+    /// Whether a and b are alpha-equivalent: the same up to the names of their bound
+    /// variables. Equivalent expressions have the same alpha_hash
+    pub fn alpha_eq(&self, a: usize, b: usize) -> bool {
+        self.alpha_eq_in(a, b, &mut vec![], &mut vec![])
+    }
+
+    fn alpha_eq_in(
+        &self,
+        a: usize,
+        b: usize,
+        binders_a: &mut Vec<String>,
+        binders_b: &mut Vec<String>,
+    ) -> bool {
+        let (na, nb) = (self.get(a), self.get(b));
+        if na.t != nb.t {
+            return false;
+        }
+        match na.t {
+            ASTNodeType::Identifier => {
+                let (name_a, name_b) = (na.get_value(), nb.get_value());
+                match (
+                    binders_a.iter().rev().position(|binder| *binder == name_a),
+                    binders_b.iter().rev().position(|binder| *binder == name_b),
+                ) {
+                    (Some(i), Some(j)) => i == j,
+                    (None, None) => name_a == name_b,
+                    _ => false,
+                }
+            }
+            ASTNodeType::Literal | ASTNodeType::Error => na.get_value() == nb.get_value(),
+            ASTNodeType::Application | ASTNodeType::Pair => {
+                self.alpha_eq_in(na.children[0], nb.children[0], binders_a, binders_b)
+                    && self.alpha_eq_in(na.children[1], nb.children[1], binders_a, binders_b)
+            }
+            ASTNodeType::Abstraction => {
+                let (bound_a, bound_b) = (binders_a.len(), binders_b.len());
+                let eq = self.pattern_alpha_eq(
+                    self.get_abstr_var(a),
+                    self.get_abstr_var(b),
+                    binders_a,
+                    binders_b,
+                ) && self.alpha_eq_in(
+                    self.get_abstr_expr(a),
+                    self.get_abstr_expr(b),
+                    binders_a,
+                    binders_b,
+                );
+                binders_a.truncate(bound_a);
+                binders_b.truncate(bound_b);
+                eq
+            }
+            ASTNodeType::Match => {
+                let (cases_a, cases_b) = (self.get_match_cases(a), self.get_match_cases(b));
+                cases_a.len() == cases_b.len()
+                    && self.alpha_eq_in(
+                        self.get_match_unpack_pattern(a),
+                        self.get_match_unpack_pattern(b),
+                        binders_a,
+                        binders_b,
+                    )
+                    && zip(cases_a, cases_b).all(|((pa, ea), (pb, eb))| {
+                        let (bound_a, bound_b) = (binders_a.len(), binders_b.len());
+                        let eq = self.pattern_alpha_eq(pa, pb, binders_a, binders_b)
+                            && self.alpha_eq_in(ea, eb, binders_a, binders_b);
+                        binders_a.truncate(bound_a);
+                        binders_b.truncate(bound_b);
+                        eq
+                    })
+            }
+            ASTNodeType::Annotation => {
+                na.type_assignment.as_ref().unwrap().to_string()
+                    == nb.type_assignment.as_ref().unwrap().to_string()
+                    && self.alpha_eq_in(
+                        self.get_annotated_expr(a),
+                        self.get_annotated_expr(b),
+                        binders_a,
+                        binders_b,
+                    )
+            }
+            _ => panic!("Cannot compare non expr"),
+        }
+    }
+
+    /// Whether two patterns have the same shape, binding their variables in the order they
+    /// appear, as hash_pattern does
+    fn pattern_alpha_eq(
+        &self,
+        a: usize,
+        b: usize,
+        binders_a: &mut Vec<String>,
+        binders_b: &mut Vec<String>,
+    ) -> bool {
+        let (na, nb) = (self.get(a), self.get(b));
+        if na.t != nb.t {
+            return false;
+        }
+        match na.t {
+            ASTNodeType::Identifier => {
+                let (name_a, name_b) = (na.get_value(), nb.get_value());
+                let is_var = |name: &String| name.starts_with(|c: char| c.is_lowercase());
+                match (is_var(&name_a), is_var(&name_b)) {
+                    (true, true) => {
+                        binders_a.push(name_a);
+                        binders_b.push(name_b);
+                        true
+                    }
+                    (false, false) => name_a == name_b,
+                    _ => false,
+                }
+            }
+            ASTNodeType::Literal => na.get_value() == nb.get_value(),
+            ASTNodeType::Application | ASTNodeType::Pair => {
+                self.pattern_alpha_eq(na.children[0], nb.children[0], binders_a, binders_b)
+                    && self.pattern_alpha_eq(na.children[1], nb.children[1], binders_a, binders_b)
+            }
+            _ => panic!("Cannot compare non pattern"),
+        }
+    }
+
+    fn hash_expr(&self, expr: usize, binders: &mut Vec<String>, h: &mut DefaultHasher) {
+        let n = self.get(expr);
+        n.t.hash(h);
+        match n.t {
+            ASTNodeType::Identifier => {
+                let name = n.get_value();
+                match binders.iter().rev().position(|binder| *binder == name) {
+                    Some(index) => index.hash(h),
+                    None => name.hash(h),
+                }
+            }
+            ASTNodeType::Literal | ASTNodeType::Error => n.get_value().hash(h),
+            ASTNodeType::Application | ASTNodeType::Pair => {
+                self.hash_expr(n.children[0], binders, h);
+                self.hash_expr(n.children[1], binders, h);
+            }
+            ASTNodeType::Abstraction => {
+                let bound = binders.len();
+                self.hash_pattern(self.get_abstr_var(expr), binders, h);
+                self.hash_expr(self.get_abstr_expr(expr), binders, h);
+                binders.truncate(bound);
+            }
+            ASTNodeType::Match => {
+                self.hash_expr(self.get_match_unpack_pattern(expr), binders, h);
+                for (pattern, case_expr) in self.get_match_cases(expr) {
+                    let bound = binders.len();
+                    self.hash_pattern(pattern, binders, h);
+                    self.hash_expr(case_expr, binders, h);
+                    binders.truncate(bound);
+                }
+            }
+            ASTNodeType::Annotation => {
+                n.type_assignment.as_ref().unwrap().to_string().hash(h);
+                self.hash_expr(self.get_annotated_expr(expr), binders, h);
+            }
+            _ => panic!("Cannot hash non expr"),
+        }
+    }
+
+    /// Hash a pattern, binding the variables in it in the order they appear
+    fn hash_pattern(&self, pattern: usize, binders: &mut Vec<String>, h: &mut DefaultHasher) {
+        let n = self.get(pattern);
+        n.t.hash(h);
+        match n.t {
+            ASTNodeType::Identifier => {
+                let name = n.get_value();
+                if name.starts_with(|c: char| c.is_lowercase()) {
+                    binders.push(name);
+                } else {
+                    name.hash(h);
+                }
+            }
+            ASTNodeType::Literal => n.get_value().hash(h),
+            ASTNodeType::Application | ASTNodeType::Pair => {
+                self.hash_pattern(n.children[0], binders, h);
+                self.hash_pattern(n.children[1], binders, h);
+            }
+            _ => panic!("Cannot hash non pattern"),
+        }
+    }
+}
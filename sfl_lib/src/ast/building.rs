@@ -14,8 +14,13 @@ impl AST {
     }
 
     pub fn add(&mut self, n: ASTNode) -> usize {
+        let id = self.vec.len();
+        for &c in &n.children {
+            self.parents[c].push(id);
+        }
         self.vec.push(n);
-        self.vec.len() - 1
+        self.parents.push(vec![]);
+        id
     }
 
     pub fn single_node(n: ASTNode) -> Self {
@@ -27,16 +32,20 @@ impl AST {
     }
 
     pub fn clone_node(&self, n: usize) -> AST {
-        let node = self.get(n);
-        let mut ast = AST::single_node(node.clone());
-        for i in 0..node.children.len() {
-            let index = ast.append_root(&self.clone_node(node.children[i]));
-            ast.vec[ast.root].children[i] = index;
-        }
-
+        let mut ast = AST::new();
+        ast.root = ast.append_copy(self, n);
         ast
     }
 
+    // Unlike append, this keeps every field of the nodes, such as the types of identifiers
+    fn append_copy(&mut self, other: &AST, node: usize) -> usize {
+        let mut n = other.get(node).clone();
+        for c in &mut n.children {
+            *c = self.append_copy(other, *c);
+        }
+        self.add(n)
+    }
+
     pub fn add_id(&mut self, tk: Token, line: usize, col: usize) -> usize {
         self.add(ASTNode::new_id(tk, line, col))
     }
@@ -57,6 +66,10 @@ impl AST {
         self.add(ASTNode::new_lit(tk, line, col))
     }
 
+    pub fn add_error(&mut self, msg: String, line: usize, col: usize) -> usize {
+        self.add(ASTNode::new_error(msg, line, col))
+    }
+
     pub fn add_app(
         &mut self,
         f: usize,
@@ -76,6 +89,10 @@ impl AST {
         self.add(ASTNode::new_abstraction(id, exp, line, col))
     }
 
+    pub fn add_annotation(&mut self, expr: usize, t: Type, line: usize, col: usize) -> usize {
+        self.add(ASTNode::new_annotation(expr, t, line, col))
+    }
+
     pub fn set_assignment_type(&mut self, assignment: usize, type_: Type) {
         self.vec[assignment].type_assignment = Some(type_);
     }
@@ -102,5 +119,6 @@ impl AST {
     pub fn add_to_module(&mut self, module: usize, assign: usize) {
         assert_eq!(self.vec[module].t, ASTNodeType::Module);
         self.vec[module].children.push(assign);
+        self.parents[assign].push(module);
     }
 }
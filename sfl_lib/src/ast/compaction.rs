@@ -0,0 +1,63 @@
+use super::*;
+
+// Substitution adds the contractum as new nodes and leaves the redex behind, unreachable. Over a
+// long reduction most of the AST is these, so they are removed by compacting it between steps.
+
+impl AST {
+    /// The number of nodes in the AST, including those that can no longer be reached from root
+    pub fn node_count(&self) -> usize {
+        self.vec.len()
+    }
+
+    /// Remove the nodes that can't be reached from root. The nodes that are kept stay in the
+    /// same order, so those from the prelude stay first, and shared nodes stay shared. Nodes are
+    /// renumbered, so any node other than root found before compacting must be found again
+    pub fn compact(&mut self) {
+        if self.vec.is_empty() {
+            return;
+        }
+
+        let mut live = vec![false; self.vec.len()];
+        let mut stack = vec![self.root];
+        while let Some(node) = stack.pop() {
+            if !live[node] {
+                live[node] = true;
+                stack.extend(&self.vec[node].children);
+            }
+        }
+
+        let mut new_ids = vec![usize::MAX; self.vec.len()];
+        let mut vec = vec![];
+        for (node, n) in std::mem::take(&mut self.vec).into_iter().enumerate() {
+            if live[node] {
+                new_ids[node] = vec.len();
+                vec.push(n);
+            }
+        }
+
+        self.parents = vec![vec![]; vec.len()];
+        for (node, n) in vec.iter_mut().enumerate() {
+            for c in &mut n.children {
+                *c = new_ids[*c];
+                self.parents[*c].push(node);
+            }
+        }
+
+        self.prelude_len = live[..self.prelude_len].iter().filter(|l| **l).count();
+        self.root = new_ids[self.root];
+        self.vec = vec;
+        self.compacted_len = self.vec.len();
+    }
+
+    /// Compact the AST if it has at least doubled in size since it was last compacted, so that
+    /// calling this after every step keeps its size proportional to the live term, at a cost
+    /// proportional to the nodes added. Gives whether it compacted, renumbering the nodes
+    pub fn collect_garbage(&mut self) -> bool {
+        if self.vec.len() >= 2 * self.compacted_len {
+            self.compact();
+            true
+        } else {
+            false
+        }
+    }
+}
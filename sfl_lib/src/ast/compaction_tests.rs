@@ -0,0 +1,76 @@
+use crate::find_single_redex_contraction_pair;
+use crate::test_utils::parse_and_typecheck;
+
+/// main after each step, and the most nodes the AST had
+fn reduce(program: &str, collect_garbage: bool) -> (Vec<String>, usize) {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let root = ast.root;
+    let mut main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+    let mut steps = vec![];
+    let mut most_nodes = ast.node_count();
+    while let Some(rc) = find_single_redex_contraction_pair(&ast, Some(ast.root), main_expr, &lt) {
+        ast.do_rc_subst(main_expr, &rc);
+        if collect_garbage {
+            ast.collect_garbage();
+        }
+        main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+        steps.push(ast.to_string_sugar(main_expr, false));
+        most_nodes = most_nodes.max(ast.node_count());
+    }
+    (steps, most_nodes)
+}
+
+#[test]
+fn garbage_is_collected_during_a_long_reduction() {
+    let program = "main :: Int\nmain = sum (range 1 20)";
+    let (steps, most_nodes) = reduce(program, true);
+    let (uncollected_steps, uncollected_nodes) = reduce(program, false);
+
+    assert_eq!(steps, uncollected_steps);
+    assert_eq!(steps.last().unwrap(), "190");
+    assert!(
+        most_nodes * 4 < uncollected_nodes,
+        "{} nodes, {} without collecting garbage",
+        most_nodes,
+        uncollected_nodes
+    );
+}
+
+#[test]
+fn compaction_keeps_sharing_and_the_prelude() {
+    let program = "fac :: Int -> Int\nfac n = if (n == 0) 1 (n * fac (n - 1))\nmain :: Int\nmain = (\\x. x + x) (fac 2)";
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let rc = find_single_redex_contraction_pair(&ast, Some(ast.root), main_expr, &lt).unwrap();
+    ast.do_rc_subst_shared(main_expr, &rc);
+    let before = ast.node_count();
+
+    ast.compact();
+    let main = ast.get_main(ast.root).unwrap();
+    let main_expr = ast.get_assign_exp(main);
+    assert!(ast.node_count() < before);
+    assert_eq!(
+        ast.to_string_shared(main_expr),
+        "let #1 = fac 2\nin #1 + #1"
+    );
+    assert!(!ast.is_from_prelude(main));
+    assert!(ast.is_from_prelude(ast.get_assign_to(ast.root, "map".to_string()).unwrap()));
+}
+
+#[test]
+fn replacing_a_shared_node_replaces_every_use() {
+    let program = "main :: Int\nmain = (\\x. x * x) (1 + 2)";
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let rc = find_single_redex_contraction_pair(&ast, Some(ast.root), main_expr, &lt).unwrap();
+    ast.do_rc_subst_shared(main_expr, &rc);
+    ast.compact();
+
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let thunk = ast.get_shared(main_expr)[0].0;
+    let rc = find_single_redex_contraction_pair(&ast, Some(ast.root), main_expr, &lt).unwrap();
+    assert_eq!(rc.from, thunk);
+    ast.do_rc_subst_shared(main_expr, &rc);
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    assert_eq!(ast.to_string_sugar(main_expr, false), "3 * 3");
+}
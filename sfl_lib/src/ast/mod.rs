@@ -1,12 +1,19 @@
+mod alpha;
 mod building;
+mod compaction;
 mod node;
 mod output;
+mod sharing;
 mod transform;
 
+#[cfg(test)]
+mod compaction_tests;
 #[cfg(test)]
 mod diff_tests;
+#[cfg(test)]
+mod sharing_tests;
 
-use crate::{find_redexes::RCPair, Token, Type};
+use crate::{find_redexes::RCPair, parsing::TokenType, Token, Type};
 pub use node::*;
 pub use output::{ASTDiff, ASTDiffElem};
 use std::collections::HashSet;
@@ -16,17 +23,50 @@ use std::{collections::HashMap, fmt::Debug, vec};
 #[derive(Clone)]
 pub struct AST {
     vec: Vec<ASTNode>,
+    /// The nodes each node is a child of, once for each time it is a child, so a node can be
+    /// replaced without searching the whole AST for its uses
+    parents: Vec<Vec<usize>>,
     pub root: usize,
+    /// The number of nodes at the start of vec that came from the prelude
+    prelude_len: usize,
+    /// The number of nodes after the last compaction
+    compacted_len: usize,
 }
 
 impl AST {
     pub fn new() -> Self {
         Self {
             vec: vec![],
+            parents: vec![],
             root: 0,
+            prelude_len: 0,
+            compacted_len: 0,
         }
     }
 
+    /// Mark every node currently in the AST as coming from the prelude
+    pub fn mark_prelude(&mut self) {
+        self.prelude_len = self.vec.len();
+    }
+
+    /// Whether a node came from the prelude, so its position is in the prelude's source rather
+    /// than the module's
+    pub fn is_from_prelude(&self, node: usize) -> bool {
+        node < self.prelude_len
+    }
+
+    /// The labels the prelude defines in module
+    pub fn get_prelude_labels(&self, module: usize) -> Vec<String> {
+        let mut labels: Vec<String> = self
+            .get_assigns_map(module)
+            .into_iter()
+            .filter(|(_, assign)| self.is_from_prelude(*assign))
+            .map(|(name, _)| name)
+            .collect();
+        labels.sort();
+        labels
+    }
+
     pub fn rc_to_str(&self, rc: &RCPair) -> String {
         self.to_string_sugar(rc.from, false) + " -> " + &rc.to.to_string_sugar(rc.to.root, false)
     }
@@ -44,6 +84,11 @@ impl AST {
         &self.vec[i]
     }
 
+    /// The direct subexpressions of a node, from left to right
+    pub fn get_children(&self, i: usize) -> &[usize] {
+        &self.vec[i].children
+    }
+
     pub fn get_first(&self, p: usize) -> usize {
         assert_eq!(self.get(p).t, ASTNodeType::Pair);
         self.get(p).children[0]
@@ -64,6 +109,11 @@ impl AST {
         self.vec[abst].children[1]
     }
 
+    pub fn get_annotated_expr(&self, anno: usize) -> usize {
+        assert_eq!(self.vec[anno].t, ASTNodeType::Annotation);
+        self.vec[anno].children[0]
+    }
+
     pub fn get_func(&self, app: usize) -> usize {
         assert_eq!(self.vec[app].t, ASTNodeType::Application);
         self.vec[app].children[0]
@@ -79,6 +129,11 @@ impl AST {
         self.vec[assign].children[1]
     }
 
+    pub fn get_assignee_id(&self, assign: usize) -> usize {
+        assert_eq!(self.vec[assign].t, ASTNodeType::Assignment);
+        self.vec[assign].children[0]
+    }
+
     pub fn get_assignee(&self, assign: usize) -> String {
         assert_eq!(self.vec[assign].t, ASTNodeType::Assignment);
         self.get(self.vec[assign].children[0]).get_value().clone()
@@ -179,12 +234,65 @@ impl AST {
         }
     }
 
+    /// The source span of an expression, from the start of its first token to just after its
+    /// last. Only identifiers and literals know where their tokens are, so brackets are not included
+    pub fn get_span(&self, expr: usize) -> ((usize, usize), (usize, usize)) {
+        let n = self.get(expr);
+        match n.t {
+            ASTNodeType::Identifier | ASTNodeType::Literal => {
+                let mut len = n.get_value().chars().count();
+                if let Some(Token {
+                    tt: TokenType::CharLit,
+                    ..
+                }) = n.info
+                {
+                    len += 2;
+                }
+                ((n.line, n.col), (n.line, n.col + len))
+            }
+            _ => n
+                .children
+                .iter()
+                .map(|c| self.get_span(*c))
+                .reduce(|(s1, e1), (s2, e2)| (s1.min(s2), e1.max(e2)))
+                .unwrap_or(((n.line, n.col), (n.line, n.col))),
+        }
+    }
+
     pub fn get_app_head(&self, expr: usize) -> usize {
         match self.get(expr).t {
             ASTNodeType::Application => self.get_app_head(self.get_func(expr)),
             _ => expr,
         }
     }
+
+    /// The indices into children to follow from expr down to node, the first way it is reached
+    /// from the left if it is shared, or None if it isn't in expr
+    pub fn get_path(&self, expr: usize, node: usize) -> Option<Vec<usize>> {
+        // A shared node that has been searched once doesn't hold node the second time either, so
+        // each node is searched at most once. The stack holds each node on the way down with the
+        // index of the next of its children to try
+        let mut visited = HashSet::from([expr]);
+        let mut stack = vec![(expr, 0)];
+        while let Some(&(at, next)) = stack.last() {
+            if at == node {
+                stack.pop();
+                return Some(stack.into_iter().map(|(_, next)| next - 1).collect());
+            }
+            match self.get_children(at).get(next) {
+                Some(&child) => {
+                    stack.last_mut().unwrap().1 += 1;
+                    if visited.insert(child) {
+                        stack.push((child, 0));
+                    }
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+        None
+    }
 }
 
 impl Debug for AST {
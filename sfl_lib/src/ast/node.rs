@@ -2,7 +2,7 @@ use crate::parsing::{Token, TokenType};
 use crate::{Primitive, Type};
 use std::fmt::Debug;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ASTNodeType {
     Identifier,
     Literal,
@@ -12,6 +12,10 @@ pub enum ASTNodeType {
     Abstraction,
     Module,
     Match,
+    /// A runtime error, such as division by zero. Evaluation halts once one is produced
+    Error,
+    /// An expression with a type annotation, (e :: T). The type is the node's type_assignment
+    Annotation,
 }
 
 #[derive(Clone)]
@@ -55,16 +59,28 @@ impl ASTNode {
         }
     }
 
-    /// Get the string value of the identifier or literal
+    /// Get the string value of the identifier or literal, or the message of an error
     #[inline(always)]
     pub fn get_value(&self) -> String {
-        assert!(self.t == ASTNodeType::Identifier || self.t == ASTNodeType::Literal);
+        assert!(
+            self.t == ASTNodeType::Identifier
+                || self.t == ASTNodeType::Literal
+                || self.t == ASTNodeType::Error
+        );
         match &self.info {
             Some(tk) => tk.value.clone(),
             None => panic!("Cannot get value of node {:?}", self),
         }
     }
 
+    /// get_value, without copying it
+    pub(crate) fn value_str(&self) -> &str {
+        match &self.info {
+            Some(tk) => &tk.value,
+            None => panic!("Cannot get value of node {:?}", self),
+        }
+    }
+
     pub fn is_uppercase(&self) -> bool {
         if self.t == ASTNodeType::Identifier {
             return self.get_value().chars().nth(0).unwrap().is_uppercase();
@@ -100,6 +116,23 @@ impl ASTNode {
         }
     }
 
+    pub(super) fn new_error(msg: String, line: usize, col: usize) -> Self {
+        ASTNode {
+            t: ASTNodeType::Error,
+            info: Some(Token {
+                tt: TokenType::StringLit,
+                value: msg,
+            }),
+            children: vec![],
+            line,
+            col,
+            type_assignment: None,
+            wait_for_args: false,
+            fancy_assign_abst_syntax: false,
+            dollar_app: false,
+        }
+    }
+
     pub(super) fn new_pair(a: usize, b: usize, line: usize, col: usize) -> Self {
         ASTNode {
             t: ASTNodeType::Pair,
@@ -190,6 +223,20 @@ impl ASTNode {
         }
     }
 
+    pub(super) fn new_annotation(expr: usize, t: Type, line: usize, col: usize) -> Self {
+        ASTNode {
+            t: ASTNodeType::Annotation,
+            info: None,
+            children: vec![expr],
+            line,
+            col,
+            type_assignment: Some(t),
+            wait_for_args: false,
+            fancy_assign_abst_syntax: false,
+            dollar_app: false,
+        }
+    }
+
     pub(super) fn wait_for_args(&mut self) {
         self.wait_for_args = true;
     }
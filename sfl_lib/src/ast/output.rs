@@ -114,6 +114,38 @@ impl ASTDiff {
 }
 
 impl AST {
+    /// A single application, without following its argument
+    fn application_to_string_sugar(&self, node: usize, show_assigned_types: bool) -> String {
+        let n = self.get(node);
+        let func = self.get_func(node);
+        let arg = self.get_arg(node);
+        let func_str = self.to_string_sugar(func, show_assigned_types);
+        let arg_str = self.to_string_sugar(arg, show_assigned_types);
+
+        // If the func is an abstraction, wrap it in parens
+        let func_str = match self.get(func).t {
+            ASTNodeType::Abstraction => format!("({})", func_str),
+            _ => func_str,
+        };
+        // If the argument is an application, wrap it in parens
+        let arg_str = match self.get(arg).t {
+            ASTNodeType::Application | ASTNodeType::Abstraction => format!("({})", arg_str),
+            _ => arg_str,
+        };
+
+        if let Some(tk) = &self.get(func).info {
+            if tk.is_infix_id() {
+                return format!("{} {}", arg_str, func_str);
+            }
+        }
+
+        if n.dollar_app {
+            format!("{} $ {}", func_str, arg_str)
+        } else {
+            format!("{} {}", func_str, arg_str)
+        }
+    }
+
     pub fn to_string_sugar(&self, node: usize, show_assigned_types: bool) -> String {
         let n = self.get(node);
         match n.t {
@@ -124,41 +156,59 @@ impl AST {
             ASTNodeType::Literal => {
                 format!("{}", n.get_value())
             }
+            ASTNodeType::Error => format!("<error: {}>", n.get_value()),
             ASTNodeType::Application => {
-                let func = self.get_func(node);
-                let arg = self.get_arg(node);
-                let func_str = self.to_string_sugar(func, show_assigned_types);
-                let arg_str = self.to_string_sugar(arg, show_assigned_types);
-
-                // If the func is an abstraction, wrap it in parens
-                let func_str = match self.get(func).t {
-                    ASTNodeType::Abstraction => format!("({})", func_str),
-                    _ => func_str,
-                };
-                // If the argument is an application, wrap it in parens
-                let arg_str = match self.get(arg).t {
-                    ASTNodeType::Application | ASTNodeType::Abstraction => format!("({})", arg_str),
-                    _ => arg_str,
-                };
+                // Lists nest in the last argument, so a chain of applications in argument
+                // position is followed in a loop rather than by recursion, which long lists
+                // would overflow the stack with
+                let mut prefix = String::new();
+                let mut brackets = 0;
+                let mut app = node;
+                while self.get(app).t == ASTNodeType::Application {
+                    let func = self.get_func(app);
+                    let arg = self.get_arg(app);
+                    let infix = self
+                        .get(func)
+                        .info
+                        .as_ref()
+                        .is_some_and(|tk| tk.is_infix_id());
+                    if infix || self.get(arg).t != ASTNodeType::Application {
+                        break;
+                    }
 
-                if let Some(tk) = &self.get(func).info {
-                    if tk.is_infix_id() {
-                        return format!("{} {}", arg_str, func_str);
+                    let func_str = self.to_string_sugar(func, show_assigned_types);
+                    if self.get(func).t == ASTNodeType::Abstraction {
+                        prefix += &format!("({})", func_str);
+                    } else {
+                        prefix += &func_str;
                     }
+                    prefix += if self.get(app).dollar_app {
+                        " $ ("
+                    } else {
+                        " ("
+                    };
+                    brackets += 1;
+                    app = arg;
                 }
 
-                if n.dollar_app {
-                    format!("{} $ {}", func_str, arg_str)
+                let last = if self.get(app).t == ASTNodeType::Application {
+                    self.application_to_string_sugar(app, show_assigned_types)
                 } else {
-                    format!("{} {}", func_str, arg_str)
-                }
+                    self.to_string_sugar(app, show_assigned_types)
+                };
+                prefix + &last + &")".repeat(brackets)
             }
             ASTNodeType::Match => {
                 let mut s = "match ".to_string();
                 let unpack_pattern = self.get_match_unpack_pattern(node);
-                s.push('(');
-                s.push_str(&self.to_string_sugar(unpack_pattern, false));
-                s.push(')');
+                // Annotations bring their own brackets
+                if self.get(unpack_pattern).t == ASTNodeType::Annotation {
+                    s.push_str(&self.to_string_sugar(unpack_pattern, false));
+                } else {
+                    s.push('(');
+                    s.push_str(&self.to_string_sugar(unpack_pattern, false));
+                    s.push(')');
+                }
                 s.push(' ');
                 s.push('{');
                 s.push('\n');
@@ -228,9 +278,20 @@ impl AST {
                 res
             }
             ASTNodeType::Pair => {
-                let a = self.to_string_sugar(self.get_first(node), show_assigned_types);
+                let first = self.get_first(node);
+                let a = self.to_string_sugar(first, show_assigned_types);
                 let b = self.to_string_sugar(self.get_second(node), show_assigned_types);
-                format!("({}, {})", a, b)
+
+                // An abstraction's body would take the rest of the pair
+                if self.get(first).t == ASTNodeType::Abstraction {
+                    format!("(({}), {})", a, b)
+                } else {
+                    format!("({}, {})", a, b)
+                }
+            }
+            ASTNodeType::Annotation => {
+                let expr = self.to_string_sugar(self.get_annotated_expr(node), show_assigned_types);
+                format!("({} :: {})", expr, n.type_assignment.as_ref().unwrap())
             }
         }
     }
@@ -253,9 +314,25 @@ impl AST {
         match (&n1.t, &n2.t) {
             (ASTNodeType::Pair, ASTNodeType::Pair) => {
                 diff.const_str("(");
-                let diff1 = AST::diff(old, new, old.get_first(expr1), new.get_first(expr2));
+                let mut diff1 = AST::diff(old, new, old.get_first(expr1), new.get_first(expr2));
+                let old_first_needs_brackets =
+                    old.get(old.get_first(expr1)).t == ASTNodeType::Abstraction;
+                let new_first_needs_brackets =
+                    new.get(new.get_first(expr2)).t == ASTNodeType::Abstraction;
+                match (old_first_needs_brackets, new_first_needs_brackets) {
+                    (true, true) => diff1.bracket(false),
+                    (true, false) => {
+                        diff1.prepend(ASTDiffElem::Different("(".to_string(), "".to_string()));
+                        diff1.diff(")".to_string(), "".to_string());
+                    }
+                    (false, true) => {
+                        diff1.prepend(ASTDiffElem::Different("".to_string(), "(".to_string()));
+                        diff1.diff("".to_string(), ")".to_string());
+                    }
+                    (false, false) => {}
+                }
                 let diff2 = AST::diff(old, new, old.get_second(expr1), new.get_second(expr2));
-                diff.insert_diffs_with_separator(diff1, diff2, ",");
+                diff.insert_diffs_with_separator(diff1, diff2, ", ");
 
                 diff.const_str(")");
             }
@@ -438,6 +515,7 @@ impl AST {
             ASTNodeType::Literal => {
                 format!("{}", n.get_value())
             }
+            ASTNodeType::Error => format!("<error: {}>", n.get_value()),
             ASTNodeType::Application => {
                 let func = self.get_func(node);
                 let arg = self.get_arg(node);
@@ -506,9 +584,18 @@ impl AST {
                 res
             }
             ASTNodeType::Pair => {
-                let a = self.to_string_desugar_and_type(self.get_first(node));
+                let first = self.get_first(node);
+                let a = self.to_string_desugar_and_type(first);
                 let b = self.to_string_desugar_and_type(self.get_second(node));
-                format!("({}, {})", a, b)
+                if self.get(first).t == ASTNodeType::Abstraction {
+                    format!("(({}), {})", a, b)
+                } else {
+                    format!("({}, {})", a, b)
+                }
+            }
+            ASTNodeType::Annotation => {
+                let expr = self.to_string_desugar_and_type(self.get_annotated_expr(node));
+                format!("({} :: {})", expr, n.type_assignment.as_ref().unwrap())
             }
         }
     }
@@ -599,6 +686,12 @@ mod test {
         )
     }
 
+    #[test]
+    fn diff_pair_of_abstraction() -> Result<(), ParserError> {
+        diff_same_as_tostring("main = ((\\x. x), 1)", "main = ((\\x. x + 1), 2)")?;
+        diff_same_as_tostring("main = ((\\x. x), 1)", "main = (2, 1)")
+    }
+
     // #[test]
     // fn diff_test1() -> Result<(), ParserError> {
     //     diff_same_as_tostring(r#"
@@ -0,0 +1,162 @@
+use super::*;
+
+// Call-by-need keeps the AST a graph rather than a tree. Substituting an argument points every
+// use of the variable at the same node, a thunk, so contracting it once updates every use.
+
+impl AST {
+    fn append_sharing(
+        &mut self,
+        other: &AST,
+        node: usize,
+        appended: &mut HashMap<usize, usize>,
+        thunks: &[usize],
+    ) -> usize {
+        if let Some(new) = appended.get(&node) {
+            return *new;
+        }
+
+        // Copies of a thunk, such as from the body of a function that uses it, are the thunk.
+        // Thunks are closed, so equal expressions have the same value
+        let new = match thunks.iter().find(|t| AST::eq(self, other, **t, node)) {
+            Some(thunk) => *thunk,
+            None => {
+                let mut n = other.get(node).clone();
+                for c in &mut n.children {
+                    *c = self.append_sharing(other, *c, appended, thunks);
+                }
+                self.add(n)
+            }
+        };
+
+        appended.insert(node, new);
+        new
+    }
+
+    /// Add a node from another ast to this ast with its children, like append, but keep nodes
+    /// that are shared in the other ast shared in this one
+    pub fn append_shared(&mut self, other: &AST, node: usize) -> usize {
+        self.append_sharing(other, node, &mut HashMap::new(), &[])
+    }
+
+    /// Put a copy of other's root in place of old, keeping nodes that are shared in other
+    /// shared. Gives the copy
+    pub fn replace_with_shared(&mut self, old: usize, other: &AST) -> usize {
+        let new = self.append_shared(other, other.root);
+        self.replace_references_to_node(old, new);
+        new
+    }
+
+    fn count_references(&self, expr: usize, counts: &mut HashMap<usize, usize>) {
+        for &child in self.get_children(expr) {
+            let count = counts.entry(child).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                self.count_references(child, counts);
+            }
+        }
+    }
+
+    fn collect_shared(
+        &self,
+        expr: usize,
+        counts: &HashMap<usize, usize>,
+        visited: &mut HashSet<usize>,
+        shared: &mut Vec<(usize, usize)>,
+    ) {
+        if !visited.insert(expr) {
+            return;
+        }
+        for &child in self.get_children(expr) {
+            self.collect_shared(child, counts, visited, shared);
+        }
+
+        let uses = counts.get(&expr).copied().unwrap_or(0);
+        let atomic = matches!(
+            self.get(expr).t,
+            ASTNodeType::Identifier
+                | ASTNodeType::Literal
+                | ASTNodeType::Abstraction
+                | ASTNodeType::Error
+        );
+        if uses > 1 && !atomic {
+            shared.push((expr, uses));
+        }
+    }
+
+    /// The thunks in expr, with how many places use each. Thunks come before the thunks that
+    /// use them. Values that are printed in full, like literals and abstractions, are not counted
+    pub fn get_shared(&self, expr: usize) -> Vec<(usize, usize)> {
+        let mut counts = HashMap::new();
+        self.count_references(expr, &mut counts);
+
+        let mut shared = vec![];
+        self.collect_shared(expr, &counts, &mut HashSet::new(), &mut shared);
+        shared
+    }
+
+    /// Print expr with each thunk bound once by a let, and named #1, #2, ... where it is used
+    pub fn to_string_shared(&self, expr: usize) -> String {
+        let shared = self.get_shared(expr);
+        if shared.is_empty() {
+            return self.to_string_sugar(expr, false);
+        }
+
+        let mut ast = self.clone();
+        let mut bindings = vec![];
+        for (i, (thunk, _)) in shared.iter().enumerate() {
+            let n = ast.get(*thunk);
+            let (line, col) = (n.line, n.col);
+            let definition = ast.add(n.clone());
+            // Uppercase so that it is never printed as an infix operator
+            let name = ast.add_id(
+                Token {
+                    tt: TokenType::UppercaseId,
+                    value: format!("#{}", i + 1),
+                },
+                line,
+                col,
+            );
+            ast.replace_references_to_node(*thunk, name);
+            bindings.push(definition);
+        }
+
+        let mut s = String::new();
+        for (i, definition) in bindings.into_iter().enumerate() {
+            s.push_str(if i == 0 { "let " } else { "    " });
+            s.push_str(&format!(
+                "#{} = {}\n",
+                i + 1,
+                ast.to_string_sugar(definition, false)
+            ));
+        }
+        s + "in " + &ast.to_string_sugar(expr, false)
+    }
+
+    /// Do the substitution like do_rc_subst, but without copying arguments into each of their
+    /// uses. The thunks already in within stay shared with any copies of them in the contractum
+    pub fn do_rc_subst_shared(&mut self, within: usize, rc: &RCPair) -> usize {
+        let thunks: Vec<usize> = self
+            .get_shared(within)
+            .into_iter()
+            .map(|(thunk, _)| thunk)
+            .collect();
+        let other = &rc.to;
+        let new = self.append_sharing(other, other.root, &mut HashMap::new(), &thunks);
+        self.splice(within, rc.from, new)
+    }
+
+    /// Do the substitution like do_rc_subst_shared, but leave within as it was, like
+    /// do_rc_subst_persistent
+    pub fn do_rc_subst_shared_persistent(&mut self, within: usize, rc: &RCPair) -> usize {
+        let thunks: Vec<usize> = self
+            .get_shared(within)
+            .into_iter()
+            .map(|(thunk, _)| thunk)
+            .collect();
+        let new = self.append_sharing(&rc.to, rc.to.root, &mut HashMap::new(), &thunks);
+        if self.get(new).t == ASTNodeType::Error {
+            return new;
+        }
+        self.copy_replacing(within, rc.from, new)
+    }
+}
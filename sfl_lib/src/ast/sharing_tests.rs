@@ -0,0 +1,145 @@
+use super::AST;
+use crate::parsing::TokenType;
+use crate::test_utils::parse_and_typecheck;
+use crate::{find_single_redex_contraction_pair, Token};
+
+const FAC: &str = "fac :: Int -> Int\nfac n = if (n == 0) 1 (n * fac (n - 1))\n";
+
+/// main printed after each call-by-need step, until it is in normal form
+fn need_steps(program: &str) -> Vec<String> {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let root = ast.root;
+    let mut main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+    let mut steps = vec![ast.to_string_shared(main_expr)];
+    while let Some(rc) = find_single_redex_contraction_pair(&ast, Some(root), main_expr, &lt) {
+        ast.do_rc_subst_shared(main_expr, &rc);
+        main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+        steps.push(ast.to_string_shared(main_expr));
+    }
+    steps
+}
+
+#[test]
+fn argument_is_shared_by_its_uses() {
+    let program = format!("{}main :: Int\nmain = (\\x. x + x) (fac 2)", FAC);
+    let (mut ast, lt) = parse_and_typecheck(&program);
+    let root = ast.root;
+    let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+
+    let rc = find_single_redex_contraction_pair(&ast, Some(root), main_expr, &lt).unwrap();
+    ast.do_rc_subst_shared(main_expr, &rc);
+    let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+
+    let shared = ast.get_shared(main_expr);
+    assert_eq!(shared.len(), 1);
+    assert_eq!(ast.to_string_sugar(shared[0].0, false), "fac 2");
+    assert_eq!(shared[0].1, 2);
+    assert_eq!(
+        ast.to_string_shared(main_expr),
+        "let #1 = fac 2\nin #1 + #1"
+    );
+}
+
+#[test]
+fn thunk_is_evaluated_once_then_reused() {
+    let program = format!("{}main :: Int\nmain = (\\x. x + x) (fac 2)", FAC);
+    let steps = need_steps(&program);
+
+    assert_eq!(steps.last().unwrap(), "4");
+    assert_eq!(steps[steps.len() - 2], "2 + 2");
+    // Until the thunk is a value, there is one copy of it
+    for step in &steps[1..steps.len() - 2] {
+        assert!(step.starts_with("let #1 = "), "{}", step);
+        assert!(step.ends_with("in #1 + #1") || step.ends_with("in #2 + #2"));
+    }
+}
+
+#[test]
+fn single_use_arguments_are_not_shared() {
+    let program = "main :: Int\nmain = (\\x. x * 2) (1 + 2)";
+    let steps = need_steps(program);
+
+    assert_eq!(
+        steps,
+        vec!["(\\x. x * 2) (1 + 2)", "(1 + 2) * 2", "3 * 2", "6"]
+    );
+}
+
+#[test]
+fn append_shared_keeps_sharing() {
+    let program = format!("{}main :: Int\nmain = (\\x. x + x) (fac 2)", FAC);
+    let (mut ast, lt) = parse_and_typecheck(&program);
+    let root = ast.root;
+    let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+    let rc = find_single_redex_contraction_pair(&ast, Some(root), main_expr, &lt).unwrap();
+    ast.do_rc_subst_shared(main_expr, &rc);
+    let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+
+    let mut shared = AST::new();
+    shared.root = shared.append_shared(&ast, main_expr);
+    assert_eq!(
+        shared.to_string_shared(shared.root),
+        "let #1 = fac 2\nin #1 + #1"
+    );
+
+    let mut copied = AST::new();
+    copied.root = copied.append(&ast, main_expr);
+    assert_eq!(copied.to_string_shared(copied.root), "(fac 2) + (fac 2)");
+}
+
+#[test]
+fn replace_with_shared_restores_a_copied_state() {
+    let program = format!("{}main :: Int\nmain = (\\x. x + x) (fac 2)", FAC);
+    let (mut ast, lt) = parse_and_typecheck(&program);
+    let root = ast.root;
+    let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+    let rc = find_single_redex_contraction_pair(&ast, Some(root), main_expr, &lt).unwrap();
+    ast.do_rc_subst_shared(main_expr, &rc);
+    let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+
+    let mut before = AST::new();
+    before.root = before.append_shared(&ast, main_expr);
+    let rc = find_single_redex_contraction_pair(&ast, Some(root), main_expr, &lt).unwrap();
+    ast.do_rc_subst_shared(main_expr, &rc);
+    let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+    assert_ne!(
+        ast.to_string_shared(main_expr),
+        "let #1 = fac 2\nin #1 + #1"
+    );
+
+    ast.replace_with_shared(main_expr, &before);
+    let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+    assert_eq!(
+        ast.to_string_shared(main_expr),
+        "let #1 = fac 2\nin #1 + #1"
+    );
+}
+
+#[test]
+fn path_search_visits_shared_nodes_once() {
+    // Each level applies the one below to itself, so there are 2^64 ways down but 65 nodes
+    let mut ast = AST::new();
+    let mut expr = ast.add_lit(
+        Token {
+            tt: TokenType::IntLit,
+            value: "1".to_string(),
+        },
+        0,
+        0,
+    );
+    let leaf = expr;
+    for _ in 0..64 {
+        expr = ast.add_app(expr, expr, 0, 0, false);
+    }
+    let missing = ast.add_lit(
+        Token {
+            tt: TokenType::IntLit,
+            value: "2".to_string(),
+        },
+        0,
+        0,
+    );
+
+    assert_eq!(ast.get_path(expr, missing), None);
+    assert_eq!(ast.get_path(expr, leaf), Some(vec![0; 64]));
+}
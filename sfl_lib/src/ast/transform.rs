@@ -3,7 +3,7 @@ use super::*;
 impl AST {
     pub fn get_all_free_instances_of_var_in_exp(&self, exp: usize, var: &String) -> Vec<usize> {
         match self.get(exp).t {
-            ASTNodeType::Literal => {
+            ASTNodeType::Literal | ASTNodeType::Error => {
                 vec![]
             }
             ASTNodeType::Identifier => {
@@ -53,19 +53,150 @@ impl AST {
                     self.get_all_free_instances_of_var_in_exp(thing_being_matched, &var);
 
                 for (pattern, expr) in self.get_match_cases(exp) {
-                    assert!(self
-                        .get_all_free_instances_of_var_in_exp(pattern, &var)
-                        .is_empty());
-
-                    instances.extend(self.get_all_free_instances_of_var_in_exp(expr, &var));
+                    // A case whose pattern binds var shadows it, which reducing inside a
+                    // function's body before it is called can give
+                    if !self.get_pattern_bound_names(pattern).contains(var) {
+                        instances.extend(self.get_all_free_instances_of_var_in_exp(expr, &var));
+                    }
                 }
 
                 instances
             }
+            ASTNodeType::Annotation => {
+                self.get_all_free_instances_of_var_in_exp(self.get_annotated_expr(exp), var)
+            }
             _ => panic!("Cannot find var instances in non exp"),
         }
     }
 
+    /// Get the names of all variables bound by a pattern or abstraction variable
+    pub fn get_pattern_bound_names(&self, pattern: usize) -> HashSet<String> {
+        let n = self.get(pattern);
+        match n.t {
+            ASTNodeType::Identifier => {
+                let name = n.get_value();
+                if name.starts_with(|c: char| c.is_lowercase()) {
+                    HashSet::from([name])
+                } else {
+                    HashSet::new()
+                }
+            }
+            ASTNodeType::Application | ASTNodeType::Pair => {
+                let mut names = self.get_pattern_bound_names(n.children[0]);
+                names.extend(self.get_pattern_bound_names(n.children[1]));
+                names
+            }
+            _ => HashSet::new(),
+        }
+    }
+
+    /// Get the names of all identifiers that are free in an expression
+    pub fn get_free_identifiers(&self, exp: usize) -> HashSet<String> {
+        let n = self.get(exp);
+        match n.t {
+            ASTNodeType::Identifier => HashSet::from([n.get_value()]),
+            ASTNodeType::Literal | ASTNodeType::Error => HashSet::new(),
+            ASTNodeType::Application | ASTNodeType::Pair => {
+                let mut names = self.get_free_identifiers(n.children[0]);
+                names.extend(self.get_free_identifiers(n.children[1]));
+                names
+            }
+            ASTNodeType::Abstraction => {
+                let bound = self.get_pattern_bound_names(self.get_abstr_var(exp));
+                let mut names = self.get_free_identifiers(self.get_abstr_expr(exp));
+                names.retain(|name| !bound.contains(name));
+                names
+            }
+            ASTNodeType::Match => {
+                let mut names = self.get_free_identifiers(self.get_match_unpack_pattern(exp));
+                for (pattern, expr) in self.get_match_cases(exp) {
+                    let bound = self.get_pattern_bound_names(pattern);
+                    let mut case_names = self.get_free_identifiers(expr);
+                    case_names.retain(|name| !bound.contains(name));
+                    names.extend(case_names);
+                }
+                names
+            }
+            ASTNodeType::Annotation => self.get_free_identifiers(self.get_annotated_expr(exp)),
+            _ => panic!("Cannot find free identifiers in non exp"),
+        }
+    }
+
+    /// Rename the variables bound in exp whose names are in avoid, so that terms with those
+    /// names free can be substituted into exp without being captured. exp's nodes must not be
+    /// shared with anything else
+    pub fn rename_bound_vars(&mut self, exp: usize, avoid: &HashSet<String>) {
+        let mut taken = avoid.clone();
+        self.collect_identifiers(exp, &mut taken);
+        self.rename_bound_vars_in(exp, avoid, &mut taken);
+    }
+
+    fn collect_identifiers(&self, exp: usize, names: &mut HashSet<String>) {
+        let n = self.get(exp);
+        if n.t == ASTNodeType::Identifier {
+            names.insert(n.get_value());
+        }
+        for c in &n.children {
+            self.collect_identifiers(*c, names);
+        }
+    }
+
+    fn rename_bound_vars_in(
+        &mut self,
+        exp: usize,
+        avoid: &HashSet<String>,
+        taken: &mut HashSet<String>,
+    ) {
+        match self.get(exp).t {
+            ASTNodeType::Abstraction => {
+                let body = self.get_abstr_expr(exp);
+                self.rename_pattern_vars(self.get_abstr_var(exp), body, avoid, taken);
+                self.rename_bound_vars_in(body, avoid, taken);
+            }
+            ASTNodeType::Match => {
+                self.rename_bound_vars_in(self.get_match_unpack_pattern(exp), avoid, taken);
+                for (pattern, expr) in self.get_match_cases(exp) {
+                    self.rename_pattern_vars(pattern, expr, avoid, taken);
+                    self.rename_bound_vars_in(expr, avoid, taken);
+                }
+            }
+            ASTNodeType::Application | ASTNodeType::Pair | ASTNodeType::Annotation => {
+                for c in self.get(exp).children.clone() {
+                    self.rename_bound_vars_in(c, avoid, taken);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Rename the variables pattern binds in scope that are in avoid to names not yet taken
+    fn rename_pattern_vars(
+        &mut self,
+        pattern: usize,
+        scope: usize,
+        avoid: &HashSet<String>,
+        taken: &mut HashSet<String>,
+    ) {
+        for name in self.get_pattern_bound_names(pattern) {
+            if !avoid.contains(&name) {
+                continue;
+            }
+            let fresh = (1..)
+                .map(|i| format!("{}{}", name, i))
+                .find(|fresh| !taken.contains(fresh))
+                .unwrap();
+            taken.insert(fresh.clone());
+
+            let mut instances = self.get_all_free_instances_of_var_in_exp(pattern, &name);
+            instances.extend(self.get_all_free_instances_of_var_in_exp(scope, &name));
+            for instance in instances {
+                if let Some(tk) = &mut self.vec[instance].info {
+                    tk.value = fresh.clone();
+                }
+            }
+        }
+    }
+
     pub fn get_abst_var_usages(&self, abst: usize) -> Vec<usize> {
         let var_name = self.get(self.get_abstr_var(abst)).get_value();
         self.get_all_free_instances_of_var_in_exp(self.get_abstr_expr(abst), &var_name)
@@ -190,6 +321,9 @@ impl AST {
 
                 self.get_laziest_rc_recurse(x, &rc_map)
             }
+            ASTNodeType::Annotation => {
+                self.get_laziest_rc_recurse(self.get_annotated_expr(expr), rc_map)
+            }
             _ => None,
         }
     }
@@ -217,19 +351,20 @@ impl AST {
             self.root = new;
         }
 
-        #[cfg(debug_assertions)]
-        let _ast_before = self.to_string_sugar(self.root, false);
+        if old == new {
+            return;
+        }
 
-        for n in &mut self.vec {
-            for c in &mut n.children {
+        // A parent is listed once for each time old is its child, so all are replaced the
+        // first time it is seen
+        for parent in std::mem::take(&mut self.parents[old]) {
+            for c in &mut self.vec[parent].children {
                 if *c == old {
                     *c = new;
+                    self.parents[new].push(parent);
                 }
             }
         }
-
-        #[cfg(debug_assertions)]
-        let _ast_before = self.to_string_sugar(self.root, false);
     }
 
     // Add a node from another ast to this ast with its children
@@ -238,6 +373,7 @@ impl AST {
         match n.t {
             ASTNodeType::Identifier => self.add_id(n.info.clone().unwrap(), n.line, n.col),
             ASTNodeType::Literal => self.add_lit(n.info.clone().unwrap(), n.line, n.col),
+            ASTNodeType::Error => self.add_error(n.get_value(), n.line, n.col),
             ASTNodeType::Application => {
                 let f = self.append(other, other.get_func(node));
                 let x = self.append(other, other.get_arg(node));
@@ -279,6 +415,11 @@ impl AST {
                 let b = self.append(other, n.children[1]);
                 self.add_pair(a, b, n.line, n.col)
             }
+            ASTNodeType::Annotation => {
+                let expr = self.append(other, other.get_annotated_expr(node));
+                let t = n.type_assignment.clone().unwrap();
+                self.add_annotation(expr, t, n.line, n.col)
+            }
         }
     }
 
@@ -286,7 +427,15 @@ impl AST {
         self.append(other, other.root)
     }
 
-    fn rc_replacement_recurse(&mut self, within: usize, old: usize, new: usize) -> usize {
+    /// Replace old, and every expression equal to it, in within by new. The bodies of
+    /// abstractions are only looked in if into_abstractions
+    pub(super) fn rc_replacement_recurse(
+        &mut self,
+        within: usize,
+        old: usize,
+        new: usize,
+        into_abstractions: bool,
+    ) -> usize {
         #[cfg(debug_assertions)]
         let _within_str = format!("{}", self.to_string_sugar(within, false));
         #[cfg(debug_assertions)]
@@ -310,17 +459,27 @@ impl AST {
             ASTNodeType::Application | ASTNodeType::Pair => {
                 let first = within_n.children[0];
                 let second = within_n.children[1];
-                self.rc_replacement_recurse(first, old, new);
-                self.rc_replacement_recurse(second, old, new);
+                self.rc_replacement_recurse(first, old, new, into_abstractions);
+                self.rc_replacement_recurse(second, old, new, into_abstractions);
             }
             ASTNodeType::Match => {
                 let matched_thingy = self.get_match_unpack_pattern(within);
-                self.rc_replacement_recurse(matched_thingy, old, new);
+                self.rc_replacement_recurse(matched_thingy, old, new, into_abstractions);
                 for (_, match_case_expr) in self.get_match_cases(within) {
-                    self.rc_replacement_recurse(match_case_expr, old, new);
+                    self.rc_replacement_recurse(match_case_expr, old, new, into_abstractions);
+                }
+            }
+            ASTNodeType::Annotation => {
+                let annotated = self.get_annotated_expr(within);
+                self.rc_replacement_recurse(annotated, old, new, into_abstractions);
+            }
+            ASTNodeType::Abstraction => {
+                if into_abstractions {
+                    let body = self.get_abstr_expr(within);
+                    self.rc_replacement_recurse(body, old, new, into_abstractions);
                 }
             }
-            ASTNodeType::Abstraction | ASTNodeType::Literal | ASTNodeType::Identifier => {}
+            ASTNodeType::Literal | ASTNodeType::Identifier | ASTNodeType::Error => {}
             _ => {
                 panic!("Non expr node: {:?}", within_n)
             }
@@ -328,16 +487,120 @@ impl AST {
         within
     }
 
+    /// Contract the redex rc.from in place, splicing the contractum in where the redex was.
+    /// Gives the new within, which is only different if within was the redex
     pub fn do_rc_subst(&mut self, within: usize, rc: &RCPair) -> usize {
         let other = &rc.to;
-        let old = rc.from;
         let new = self.append(other, other.root);
 
         #[cfg(debug_assertions)]
-        let _old_str = self.to_string_sugar(old, false);
+        let _old_str = self.to_string_sugar(rc.from, false);
         #[cfg(debug_assertions)]
         let _new_str = self.to_string_sugar(new, false);
-        self.rc_replacement_recurse(within, old, new)
+
+        self.splice(within, rc.from, new)
+    }
+
+    /// Replace old, somewhere in within, by new. Gives the new within
+    pub(super) fn splice(&mut self, within: usize, old: usize, new: usize) -> usize {
+        // A runtime error aborts the whole computation, not just the redex it came from
+        let old = if self.get(new).t == ASTNodeType::Error {
+            within
+        } else {
+            old
+        };
+        self.replace_references_to_node(old, new);
+        if within == old {
+            new
+        } else {
+            within
+        }
+    }
+
+    /// Do the substitution like do_rc_subst, and replace every expression equal to the redex
+    /// too, looking in the bodies of abstractions only if the redex was found under lambdas.
+    /// This walks all of within, so is only for when equal redexes should be contracted together
+    pub fn do_rc_subst_replacing_equal(&mut self, within: usize, rc: &RCPair) -> usize {
+        let new = self.append(&rc.to, rc.to.root);
+        if self.get(new).t == ASTNodeType::Error {
+            return self.splice(within, rc.from, new);
+        }
+        self.rc_replacement_recurse(within, rc.from, new, rc.under_lambdas)
+    }
+
+    /// Do the substitution like do_rc_subst, but leave within as it was and give a new
+    /// expression for the result. Only the nodes above the redex are copied, the rest are
+    /// shared with within, so earlier states can be kept cheaply
+    pub fn do_rc_subst_persistent(&mut self, within: usize, rc: &RCPair) -> usize {
+        let new = self.append(&rc.to, rc.to.root);
+        if self.get(new).t == ASTNodeType::Error {
+            return new;
+        }
+        self.copy_replacing(within, rc.from, new)
+    }
+
+    /// A copy of within with old replaced by new. Only the ancestors of old are copied, found by
+    /// following its parent links, and nodes shared in within stay shared. Parents outside
+    /// within, such as in other states that share old, are not followed, so the cost doesn't
+    /// grow with the states kept
+    pub(super) fn copy_replacing(&mut self, within: usize, old: usize, new: usize) -> usize {
+        let mut reachable = HashSet::from([within]);
+        let mut stack = vec![within];
+        while let Some(node) = stack.pop() {
+            for &c in self.get_children(node) {
+                if reachable.insert(c) {
+                    stack.push(c);
+                }
+            }
+        }
+
+        let mut ancestors = HashSet::new();
+        let mut stack = vec![old];
+        while let Some(node) = stack.pop() {
+            if node == within {
+                continue;
+            }
+            for &parent in &self.parents[node] {
+                if reachable.contains(&parent) && ancestors.insert(parent) {
+                    stack.push(parent);
+                }
+            }
+        }
+        self.copy_ancestors(within, old, new, &ancestors, &mut HashMap::new())
+    }
+
+    fn copy_ancestors(
+        &mut self,
+        within: usize,
+        old: usize,
+        new: usize,
+        ancestors: &HashSet<usize>,
+        copied: &mut HashMap<usize, usize>,
+    ) -> usize {
+        if within == old {
+            return new;
+        }
+        if !ancestors.contains(&within) {
+            return within;
+        }
+        if let Some(copy) = copied.get(&within) {
+            return *copy;
+        }
+
+        let mut children = self.get_children(within).to_vec();
+        for c in &mut children {
+            *c = self.copy_ancestors(*c, old, new, ancestors, copied);
+        }
+
+        let copy = if children == self.get_children(within) {
+            within
+        } else {
+            let mut n = self.get(within).clone();
+            n.children = children;
+            self.add(n)
+        };
+        copied.insert(within, copy);
+        copy
     }
 
     pub fn filter_identical_rcs(&self, rcs: &Vec<RCPair>) -> Vec<RCPair> {
@@ -0,0 +1,226 @@
+use super::{
+    EvaluationStrategy, ModuleContext, NonTermination, RCPair, RedexKind, ReductionRules,
+    TerminationChecker, DEFAULT_SIZE_BUDGET, DEFAULT_STEP_LIMIT,
+};
+use crate::parsing::TypeMap;
+use crate::{KnownTypeLabelTable, PreservationChecker, AST};
+use std::fmt::Display;
+
+/// Called with main's expression and the redex picked before each step of a reduction is taken
+pub type BeforeStep<'a> = Box<dyn FnMut(&AST, usize, &RCPair) + 'a>;
+
+/// Limits on a reduction run by reduce_within_budget, and a way to stop it part way through
+pub struct EvaluationConfig<'a> {
+    /// The most steps to take
+    pub max_steps: usize,
+    /// The most nodes main can grow to
+    pub max_nodes: usize,
+    /// Called before each step with the number of steps taken so far. Returning true cancels
+    /// the reduction there, so a caller can stop it after a deadline or when asked to
+    pub cancel: Option<Box<dyn FnMut(usize) -> bool + 'a>>,
+    /// The rules the strategy picks redexes with
+    pub rules: ReductionRules,
+    /// Typecheck main after every step, stopping at the first step that changes its type. Main
+    /// must have been typechecked with these declared types
+    pub check_preservation: Option<&'a TypeMap>,
+    /// Shows the reduction as it goes
+    pub before_step: Option<BeforeStep<'a>>,
+}
+
+impl Default for EvaluationConfig<'_> {
+    fn default() -> Self {
+        Self {
+            max_steps: DEFAULT_STEP_LIMIT,
+            max_nodes: DEFAULT_SIZE_BUDGET,
+            cancel: None,
+            rules: ReductionRules::default(),
+            check_preservation: None,
+            before_step: None,
+        }
+    }
+}
+
+impl EvaluationConfig<'_> {
+    /// The limit a reduction taken one step at a time is over, having taken steps steps to get
+    /// main to expr. reduce_within_budget stops at the same limits
+    pub fn exceeded(&self, ast: &AST, expr: usize, steps: usize) -> Option<Outcome> {
+        let (budget, limit) = if steps >= self.max_steps {
+            (Budget::Steps, self.max_steps)
+        } else if ast.term_size(expr) > self.max_nodes {
+            (Budget::Nodes, self.max_nodes)
+        } else {
+            return None;
+        };
+        Some(Outcome::BudgetExceeded { budget, limit })
+    }
+}
+
+/// A limit in an EvaluationConfig
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Budget {
+    Steps,
+    Nodes,
+}
+
+/// How a reduction run by reduce_within_budget ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Main has no redexes left
+    NormalForm,
+    /// Another step would take more steps than the limit, or the last step made main bigger
+    /// than it
+    BudgetExceeded { budget: Budget, limit: usize },
+    /// The cancel callback asked to stop
+    Cancelled,
+    /// Main came back to a state it was in before, so it would never stop
+    Loop(NonTermination),
+    /// The last step changed main's type, as found when checking preservation
+    NotPreserved(String),
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::NormalForm => write!(f, "reached normal form"),
+            Outcome::BudgetExceeded {
+                budget: Budget::Steps,
+                limit,
+            } => write!(f, "budget exceeded: stopped after {} steps", limit),
+            Outcome::BudgetExceeded {
+                budget: Budget::Nodes,
+                limit,
+            } => write!(f, "budget exceeded: main grew past {} nodes", limit),
+            Outcome::Cancelled => write!(f, "cancelled"),
+            Outcome::Loop(e) => write!(f, "{}", e),
+            Outcome::NotPreserved(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// One step of a reduction, as printed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    /// Where the redex was in main before the step, from AST::get_path
+    pub location: Vec<usize>,
+    pub kind: RedexKind,
+    pub msg_before: String,
+    pub msg_after: String,
+    pub redex: String,
+    pub contractum: String,
+    /// Main after the step
+    pub term: String,
+}
+
+/// The steps a reduction took, up to where it stopped, and why it stopped
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reduction {
+    /// The name of the strategy that picked the redexes
+    pub strategy: &'static str,
+    /// Main before the first step
+    pub start: String,
+    pub steps: Vec<TraceStep>,
+    pub outcome: Outcome,
+}
+
+/// Reduce main in place with strategy until it is in normal form or the reduction has to stop,
+/// giving the steps taken either way. Main is printed with its thunks bound once if the
+/// strategy shares arguments
+pub fn reduce_within_budget(
+    ast: &mut AST,
+    lt: &KnownTypeLabelTable,
+    strategy: &mut dyn EvaluationStrategy,
+    mut config: EvaluationConfig,
+) -> Reduction {
+    let shares = strategy.shares_arguments();
+    let show = |ast: &AST, expr: usize| {
+        if shares {
+            ast.to_string_shared(expr)
+        } else {
+            ast.to_string_sugar(expr, false)
+        }
+    };
+    let main_expr = |ast: &AST| {
+        ast.get_assign_exp(
+            ast.get_main(ast.root)
+                .expect("no main, should have been caught by parser"),
+        )
+    };
+
+    let mut preservation = config
+        .check_preservation
+        .map(|type_map| PreservationChecker::new(ast, type_map));
+    let mut termination =
+        TerminationChecker::new(config.max_nodes, strategy.depends_only_on_expression());
+    let mut expr = main_expr(ast);
+    let mut ctx = ModuleContext::new(ast, Some(ast.root));
+    let mut reduction = Reduction {
+        strategy: strategy.name(),
+        start: show(ast, expr),
+        steps: vec![],
+        outcome: Outcome::NormalForm,
+    };
+
+    loop {
+        if let Err(e) = termination.check(ast, expr) {
+            reduction.outcome = match e {
+                NonTermination::Growth { budget, .. } => Outcome::BudgetExceeded {
+                    budget: Budget::Nodes,
+                    limit: budget,
+                },
+                e => Outcome::Loop(e),
+            };
+            return reduction;
+        }
+
+        if let Some(cancel) = &mut config.cancel {
+            if cancel(reduction.steps.len()) {
+                reduction.outcome = Outcome::Cancelled;
+                return reduction;
+            }
+        }
+
+        let rc = match strategy.next_redex_in(ast, &ctx, expr, lt, config.rules) {
+            Some(rc) => rc,
+            None => return reduction,
+        };
+        if reduction.steps.len() >= config.max_steps {
+            reduction.outcome = Outcome::BudgetExceeded {
+                budget: Budget::Steps,
+                limit: config.max_steps,
+            };
+            return reduction;
+        }
+
+        let location = ast
+            .get_path(expr, rc.from)
+            .expect("the strategy picked a redex outside main");
+        if let Some(before_step) = &mut config.before_step {
+            before_step(ast, expr, &rc);
+        }
+        let redex = ast.to_string_sugar(rc.from, false);
+        let preserved = match &mut preservation {
+            Some(preservation) if shares => preservation.do_rc_subst_shared(ast, lt, &rc),
+            Some(preservation) => preservation.do_rc_subst(ast, lt, &rc),
+            None if shares => Ok(ast.do_rc_subst_shared(expr, &rc)),
+            None => Ok(ast.do_rc_subst(expr, &rc)),
+        };
+        if ast.collect_garbage() {
+            ctx = ModuleContext::new(ast, Some(ast.root));
+        }
+        expr = main_expr(ast);
+
+        reduction.steps.push(TraceStep {
+            location,
+            kind: rc.kind,
+            msg_before: rc.msg_before,
+            msg_after: rc.msg_after,
+            redex,
+            contractum: rc.to.to_string_sugar(rc.to.root, false),
+            term: show(ast, expr),
+        });
+        if let Err(e) = preserved {
+            reduction.outcome = Outcome::NotPreserved(format!("{:?}", e));
+            return reduction;
+        }
+    }
+}
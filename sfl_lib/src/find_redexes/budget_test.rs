@@ -0,0 +1,199 @@
+use crate::parsing::TypeMap;
+use crate::test_utils::parse_and_typecheck;
+use crate::{
+    reduce_within_budget, Budget, EvaluationConfig, NonTermination, NormalOrder, Outcome, Parser,
+    Reduction, SeededRandom, DEFAULT_SIZE_BUDGET, DEFAULT_STEP_LIMIT,
+};
+
+/// Reduce main lazily within config. The trace tests reduce with it too
+pub(super) fn reduce(program: &str, config: EvaluationConfig) -> Reduction {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    reduce_within_budget(&mut ast, &lt, &mut NormalOrder, config)
+}
+
+#[test]
+fn reduction_within_budget_reaches_normal_form() {
+    let reduction = reduce(
+        "main :: Int\nmain = sum (range 1 5)",
+        EvaluationConfig::default(),
+    );
+    assert_eq!(reduction.outcome, Outcome::NormalForm);
+    assert_eq!(reduction.start, "sum (range 1 5)");
+    assert_eq!(reduction.steps.last().unwrap().term, "10");
+}
+
+#[test]
+fn step_budget_keeps_partial_trace() {
+    let config = EvaluationConfig {
+        max_steps: 5,
+        ..Default::default()
+    };
+    let reduction = reduce("main :: Int\nmain = sum (range 1 100)", config);
+    assert_eq!(
+        reduction.outcome,
+        Outcome::BudgetExceeded {
+            budget: Budget::Steps,
+            limit: 5
+        }
+    );
+    assert_eq!(reduction.steps.len(), 5);
+    assert_eq!(
+        reduction.outcome.to_string(),
+        "budget exceeded: stopped after 5 steps"
+    );
+
+    // A budget that is just enough doesn't count as exceeded
+    let steps = reduce(
+        "main :: Int\nmain = sum (range 1 5)",
+        EvaluationConfig::default(),
+    )
+    .steps
+    .len();
+    let config = EvaluationConfig {
+        max_steps: steps,
+        ..Default::default()
+    };
+    let reduction = reduce("main :: Int\nmain = sum (range 1 5)", config);
+    assert_eq!(reduction.outcome, Outcome::NormalForm);
+}
+
+#[test]
+fn node_budget_stops_growth() {
+    let config = EvaluationConfig {
+        max_nodes: 100,
+        ..Default::default()
+    };
+    let reduction = reduce("main :: List Int\nmain = repeat 1", config);
+    assert_eq!(
+        reduction.outcome,
+        Outcome::BudgetExceeded {
+            budget: Budget::Nodes,
+            limit: 100
+        }
+    );
+    assert!(!reduction.steps.is_empty());
+}
+
+#[test]
+fn cancel_stops_mid_run() {
+    let mut asked = vec![];
+    let config = EvaluationConfig {
+        cancel: Some(Box::new(|steps| {
+            asked.push(steps);
+            steps == 3
+        })),
+        ..Default::default()
+    };
+    let reduction = reduce("main :: Int\nmain = sum (range 1 100)", config);
+    assert_eq!(reduction.outcome, Outcome::Cancelled);
+    assert_eq!(reduction.steps.len(), 3);
+    assert_eq!(asked, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn loops_stop_unless_the_strategy_is_random() {
+    let program = "loop :: Int\nloop = loop\nmain :: Int\nmain = loop";
+    let reduction = reduce(program, EvaluationConfig::default());
+    assert_eq!(
+        reduction.outcome,
+        Outcome::Loop(NonTermination::Loop {
+            step: 2,
+            earlier: 1
+        })
+    );
+
+    // Random never finds the loop, but the step budget still stops it
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let config = EvaluationConfig {
+        max_steps: 50,
+        ..Default::default()
+    };
+    let reduction = reduce_within_budget(&mut ast, &pr.lt, &mut SeededRandom::new(1), config);
+    assert_eq!(reduction.steps.len(), 50);
+    assert!(matches!(
+        reduction.outcome,
+        Outcome::BudgetExceeded {
+            budget: Budget::Steps,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn default_budget_stops_growth_quickly() {
+    let reduction = reduce(
+        "main :: List Int\nmain = repeat 1",
+        EvaluationConfig::default(),
+    );
+    assert_eq!(
+        reduction.outcome,
+        Outcome::BudgetExceeded {
+            budget: Budget::Nodes,
+            limit: DEFAULT_SIZE_BUDGET
+        }
+    );
+    assert!(reduction.steps.len() < 500);
+}
+
+#[test]
+fn before_step_sees_each_step() {
+    let mut seen = vec![];
+    // The program declares no types of its own
+    let type_map = TypeMap::new();
+    let config = EvaluationConfig {
+        check_preservation: Some(&type_map),
+        before_step: Some(Box::new(|ast, expr, rc| {
+            seen.push((
+                ast.to_string_sugar(expr, false),
+                ast.to_string_sugar(rc.from, false),
+            ))
+        })),
+        ..Default::default()
+    };
+    let reduction = reduce("main :: Int\nmain = 1 + 2 * 3", config);
+    assert_eq!(reduction.outcome, Outcome::NormalForm);
+    assert_eq!(
+        seen,
+        vec![
+            ("1 + (2 * 3)".to_string(), "2 * 3".to_string()),
+            ("1 + 6".to_string(), "1 + 6".to_string())
+        ]
+    );
+}
+
+#[test]
+fn exceeded_matches_reduce_within_budget() {
+    let pr = Parser::from_string("main :: List Int\nmain = repeat 1".to_string())
+        .parse_module(true)
+        .unwrap();
+    let ast = pr.ast;
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let config = EvaluationConfig {
+        max_steps: 5,
+        max_nodes: 3,
+        ..Default::default()
+    };
+    assert_eq!(config.exceeded(&ast, main_expr, 4), None);
+    assert_eq!(
+        config.exceeded(&ast, main_expr, 5),
+        Some(Outcome::BudgetExceeded {
+            budget: Budget::Steps,
+            limit: 5
+        })
+    );
+    let config = EvaluationConfig {
+        max_nodes: 1,
+        ..Default::default()
+    };
+    assert_eq!(
+        config.exceeded(&ast, main_expr, 0),
+        Some(Outcome::BudgetExceeded {
+            budget: Budget::Nodes,
+            limit: 1
+        })
+    );
+    assert_eq!(EvaluationConfig::default().max_steps, DEFAULT_STEP_LIMIT);
+}
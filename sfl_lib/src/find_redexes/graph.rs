@@ -0,0 +1,320 @@
+use super::trace::{json_string, kind_json};
+use super::{ModuleContext, RedexKind, ReductionRules};
+use crate::{KnownTypeLabelTable, AST};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// Every redex of a term is contracted to find the terms one step from it, so the graph shows
+// every order a reduction could go in. By Church-Rosser, all the normal forms it finds are the
+// same term.
+
+/// The most terms explore_reductions finds when no bound is given
+pub const DEFAULT_GRAPH_BOUND: usize = 1000;
+
+/// The version of the graph format written by ReductionGraph::to_json
+pub const GRAPH_FORMAT_VERSION: usize = 1;
+
+/// A term in a ReductionGraph, which stands for every term alpha-equivalent to it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphTerm {
+    pub term: String,
+    /// The fewest steps from main to the term
+    pub depth: usize,
+    /// Whether every step from the term is in the graph, which it isn't if the bound was
+    /// reached first
+    pub explored: bool,
+    pub normal_form: bool,
+}
+
+/// A step from one term in a ReductionGraph to another
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphStep {
+    pub from: usize,
+    pub to: usize,
+    pub kind: RedexKind,
+    pub redex: String,
+    pub msg_before: String,
+}
+
+/// The terms reachable from main, and the steps between them. Term 0 is main
+pub struct ReductionGraph {
+    pub terms: Vec<GraphTerm>,
+    pub steps: Vec<GraphStep>,
+    /// Whether every reachable term was found within the bound
+    pub complete: bool,
+    /// The step each term was first found by, which is on a shortest path to it
+    found_by: Vec<Option<usize>>,
+}
+
+/// Find the terms reachable from main breadth first, up to max_terms of them, contracting
+/// every redex of each
+pub fn explore_reductions(ast: &AST, lt: &KnownTypeLabelTable, max_terms: usize) -> ReductionGraph {
+    explore_reductions_with(ast, lt, max_terms, ReductionRules::default())
+}
+
+/// explore_reductions, also contracting the redexes the rules allow
+pub fn explore_reductions_with(
+    ast: &AST,
+    lt: &KnownTypeLabelTable,
+    max_terms: usize,
+    rules: ReductionRules,
+) -> ReductionGraph {
+    // Each term is a new expression in the same AST, sharing what it didn't change
+    let mut ast = ast.clone();
+    let module = ast.root;
+    let start = ast.get_assign_exp(
+        ast.get_main(module)
+            .expect("no main, should have been caught by parser"),
+    );
+
+    let mut graph = ReductionGraph {
+        terms: vec![GraphTerm {
+            term: ast.to_string_sugar(start, false),
+            depth: 0,
+            explored: false,
+            normal_form: false,
+        }],
+        steps: vec![],
+        complete: true,
+        found_by: vec![None],
+    };
+    let ctx = ModuleContext::new(&ast, Some(module));
+    let mut exprs = vec![start];
+    let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+    by_hash.insert(ast.alpha_hash(start), vec![0]);
+    let mut queue = VecDeque::from([0]);
+
+    while let Some(term) = queue.pop_front() {
+        // Each redex is contracted only once its step is taken, as an opaque one is evaluated
+        let redexes = ctx.find_all_redexes(&ast, exprs[term], lt, rules);
+        graph.terms[term].normal_form = redexes.is_empty();
+        graph.terms[term].explored = true;
+
+        for redex in redexes {
+            let rc = ctx.contract(&ast, redex, lt, rules);
+            let expr = ast.do_rc_subst_persistent(exprs[term], &rc);
+            let hash = ast.alpha_hash(expr);
+            let existing = by_hash
+                .get(&hash)
+                .and_then(|terms| terms.iter().find(|t| ast.alpha_eq(exprs[**t], expr)));
+            let to = match existing {
+                Some(&to) => to,
+                None if graph.terms.len() >= max_terms => {
+                    graph.terms[term].explored = false;
+                    graph.complete = false;
+                    continue;
+                }
+                None => {
+                    let to = graph.terms.len();
+                    graph.terms.push(GraphTerm {
+                        term: ast.to_string_sugar(expr, false),
+                        depth: graph.terms[term].depth + 1,
+                        explored: false,
+                        normal_form: false,
+                    });
+                    graph.found_by.push(Some(graph.steps.len()));
+                    exprs.push(expr);
+                    by_hash.entry(hash).or_default().push(to);
+                    queue.push_back(to);
+                    to
+                }
+            };
+
+            graph.steps.push(GraphStep {
+                from: term,
+                to,
+                kind: rc.kind,
+                redex: ast.to_string_sugar(rc.from, false),
+                msg_before: rc.msg_before,
+            });
+        }
+    }
+    graph
+}
+
+impl ReductionGraph {
+    /// The normal forms found. If the graph is complete and reduction is confluent, there is
+    /// at most one
+    pub fn normal_forms(&self) -> Vec<usize> {
+        (0..self.terms.len())
+            .filter(|t| self.terms[*t].normal_form)
+            .collect()
+    }
+
+    /// The terms on a path with the fewest steps from main to term
+    pub fn shortest_path(&self, term: usize) -> Vec<usize> {
+        let mut path = vec![term];
+        while let Some(step) = self.found_by[path[path.len() - 1]] {
+            path.push(self.steps[step].from);
+        }
+        path.reverse();
+        path
+    }
+
+    /// The terms on a path with the most steps from main to term, among the steps found, or
+    /// None if a path to term can go round a cycle, so has no most
+    pub fn longest_path(&self, term: usize) -> Option<Vec<usize>> {
+        // Only the terms that lead to term
+        let mut leads = HashSet::from([term]);
+        let mut stack = vec![term];
+        while let Some(t) = stack.pop() {
+            for step in self.steps.iter().filter(|step| step.to == t) {
+                if leads.insert(step.from) {
+                    stack.push(step.from);
+                }
+            }
+        }
+        let steps: Vec<&GraphStep> = self
+            .steps
+            .iter()
+            .filter(|step| leads.contains(&step.from) && leads.contains(&step.to))
+            .collect();
+
+        // Longest paths in topological order, which there is none of if there's a cycle
+        let mut indegree: HashMap<usize, usize> = leads.iter().map(|t| (*t, 0)).collect();
+        for step in &steps {
+            *indegree.get_mut(&step.to).unwrap() += 1;
+        }
+        let mut longest: HashMap<usize, (usize, Option<usize>)> = HashMap::from([(0, (0, None))]);
+        let mut ready: Vec<usize> = leads.iter().filter(|t| indegree[t] == 0).copied().collect();
+        let mut sorted = 0;
+        while let Some(t) = ready.pop() {
+            sorted += 1;
+            for step in steps.iter().filter(|step| step.from == t) {
+                if let Some(&(length, _)) = longest.get(&t) {
+                    if longest
+                        .get(&step.to)
+                        .is_none_or(|(best, _)| length + 1 > *best)
+                    {
+                        longest.insert(step.to, (length + 1, Some(t)));
+                    }
+                }
+                let d = indegree.get_mut(&step.to).unwrap();
+                *d -= 1;
+                if *d == 0 {
+                    ready.push(step.to);
+                }
+            }
+        }
+        if sorted < leads.len() {
+            return None;
+        }
+
+        let mut path = vec![term];
+        while let Some(&(_, Some(from))) = longest.get(&path[path.len() - 1]) {
+            path.push(from);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// The graph in Graphviz's DOT language. Main is bold, normal forms have a double border
+    /// and terms that weren't explored are dashed
+    pub fn to_dot(&self) -> String {
+        let mut s =
+            "digraph reductions {\n  node [shape=box, fontname=\"monospace\"];\n".to_string();
+        for (id, term) in self.terms.iter().enumerate() {
+            let mut attributes = format!("label={}", dot_string(&term.term));
+            let mut styles = vec![];
+            if id == 0 {
+                styles.push("bold");
+            }
+            if !term.explored {
+                styles.push("dashed");
+            }
+            if !styles.is_empty() {
+                attributes.push_str(&format!(", style=\"{}\"", styles.join(",")));
+            }
+            if term.normal_form {
+                attributes.push_str(", peripheries=2");
+            }
+            s.push_str(&format!("  t{} [{}];\n", id, attributes));
+        }
+        for step in &self.steps {
+            s.push_str(&format!(
+                "  t{} -> t{} [label={}];\n",
+                step.from,
+                step.to,
+                dot_string(&step.msg_before)
+            ));
+        }
+        s + "}\n"
+    }
+
+    /// The graph as JSON, in the format described in the README
+    pub fn to_json(&self) -> String {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .enumerate()
+            .map(|(id, term)| {
+                format!(
+                    "    {{\"id\": {}, \"term\": {}, \"depth\": {}, \"explored\": {}, \"normal_form\": {}}}",
+                    id,
+                    json_string(&term.term),
+                    term.depth,
+                    term.explored,
+                    term.normal_form
+                )
+            })
+            .collect();
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|step| {
+                format!(
+                    "    {{\"from\": {}, \"to\": {}, {}, \"redex\": {}, \"msg_before\": {}}}",
+                    step.from,
+                    step.to,
+                    kind_json(step.kind),
+                    json_string(&step.redex),
+                    json_string(&step.msg_before)
+                )
+            })
+            .collect();
+        let path_json = |path: &[usize]| {
+            let ids: Vec<String> = path.iter().map(|t| t.to_string()).collect();
+            format!("[{}]", ids.join(", "))
+        };
+        let normal_forms: Vec<String> = self
+            .normal_forms()
+            .into_iter()
+            .map(|t| {
+                format!(
+                    "    {{\"id\": {}, \"shortest\": {}, \"longest\": {}}}",
+                    t,
+                    path_json(&self.shortest_path(t)),
+                    self.longest_path(t)
+                        .map_or("null".to_string(), |path| path_json(&path))
+                )
+            })
+            .collect();
+        let list = |items: Vec<String>| {
+            if items.is_empty() {
+                String::new()
+            } else {
+                format!("\n{}", items.join(",\n"))
+            }
+        };
+        format!(
+            "{{\n  \"format\": \"sfl-graph\",\n  \"version\": {},\n  \"complete\": {},\n  \"terms\": [{}\n  ],\n  \"steps\": [{}\n  ],\n  \"normal_forms\": [{}\n  ]\n}}\n",
+            GRAPH_FORMAT_VERSION,
+            self.complete,
+            list(terms),
+            list(steps),
+            list(normal_forms)
+        )
+    }
+}
+
+/// s as a DOT string literal, with each line of a term on its own line
+fn dot_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\l");
+    if s.contains('\n') {
+        format!("\"{}\\l\"", escaped)
+    } else {
+        format!("\"{}\"", escaped)
+    }
+}
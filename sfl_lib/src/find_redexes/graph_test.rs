@@ -0,0 +1,120 @@
+use crate::test_utils::parse_and_typecheck;
+use crate::{explore_reductions, Parser, ReductionGraph, DEFAULT_GRAPH_BOUND};
+
+fn graph(program: &str, max_terms: usize) -> ReductionGraph {
+    let (ast, lt) = parse_and_typecheck(program);
+    explore_reductions(&ast, &lt, max_terms)
+}
+
+#[test]
+fn every_order_reaches_the_same_normal_form() {
+    let g = graph("main :: Int\nmain = (1 + 2) * (3 + 4)", DEFAULT_GRAPH_BOUND);
+    assert!(g.complete);
+    let terms: Vec<&str> = g.terms.iter().map(|t| t.term.as_str()).collect();
+    assert_eq!(
+        terms,
+        vec![
+            "(1 + 2) * (3 + 4)",
+            "3 * (3 + 4)",
+            "(1 + 2) * 7",
+            "3 * 7",
+            "21"
+        ]
+    );
+    assert_eq!(g.steps.len(), 5);
+    assert_eq!(g.normal_forms(), vec![4]);
+    assert_eq!(g.shortest_path(4), vec![0, 1, 3, 4]);
+    assert_eq!(g.longest_path(4).unwrap().len(), 4);
+
+    let dot = g.to_dot();
+    assert!(dot.starts_with("digraph reductions {\n"));
+    assert!(dot.contains("  t4 [label=\"21\", peripheries=2];\n"));
+    assert!(dot.contains("  t2 -> t3 [label=\"Apply inbuilt + to 1 and 2\"];\n"));
+    let json = g.to_json();
+    assert!(json.contains("\"complete\": true"));
+    assert!(
+        json.contains("    {\"id\": 4, \"shortest\": [0, 1, 3, 4], \"longest\": [0, 2, 3, 4]}\n")
+    );
+}
+
+#[test]
+fn equal_redexes_are_separate_steps() {
+    let g = graph("main :: Int\nmain = (1 + 2) * (1 + 2)", DEFAULT_GRAPH_BOUND);
+    let terms: Vec<&str> = g.terms.iter().map(|t| t.term.as_str()).collect();
+    assert_eq!(
+        terms,
+        vec![
+            "(1 + 2) * (1 + 2)",
+            "3 * (1 + 2)",
+            "(1 + 2) * 3",
+            "3 * 3",
+            "9"
+        ]
+    );
+    assert_eq!(g.steps.len(), 5);
+}
+
+#[test]
+fn alpha_equivalent_terms_are_one_term() {
+    // Contracting either abstraction gives the identity applied to 1, with x or y bound
+    let g = graph(
+        "main :: Int\nmain = (\\y. y) ((\\x. x) 1)",
+        DEFAULT_GRAPH_BOUND,
+    );
+    assert_eq!(g.terms.len(), 3);
+    assert_eq!(g.steps.len(), 3);
+    assert_eq!(g.steps[0].to, g.steps[1].to);
+
+    let ast = Parser::from_string(
+        "a = \\x. \\y. x\nb = \\p. \\q. p\nc = \\p. \\q. q\nmain = 1".to_string(),
+    )
+    .parse_module(true)
+    .unwrap()
+    .ast;
+    let expr =
+        |name: &str| ast.get_assign_exp(ast.get_assign_to(ast.root, name.to_string()).unwrap());
+    assert!(ast.alpha_eq(expr("a"), expr("b")));
+    assert!(!ast.alpha_eq(expr("a"), expr("c")));
+}
+
+#[test]
+fn cycles_have_no_longest_path() {
+    let g = graph(
+        "loop :: Int\nloop = loop\nmain :: Int\nmain = if true 1 loop",
+        DEFAULT_GRAPH_BOUND,
+    );
+    assert!(g.complete);
+    let normal_forms = g.normal_forms();
+    assert_eq!(normal_forms.len(), 1);
+    assert_eq!(g.terms[normal_forms[0]].term, "1");
+    assert_eq!(g.shortest_path(normal_forms[0]).len(), 3);
+    assert_eq!(g.longest_path(normal_forms[0]), None);
+}
+
+#[test]
+fn exploration_stops_at_the_bound() {
+    let g = graph("main :: List Int\nmain = repeat 1", 20);
+    assert!(!g.complete);
+    assert_eq!(g.terms.len(), 20);
+    assert!(g.normal_forms().is_empty());
+    assert!(g.terms.iter().any(|t| !t.explored));
+    assert!(g.to_dot().contains("style=\"dashed\""));
+}
+
+#[test]
+fn graph_steps_into_infinite_opaque_values() {
+    let (ast, mut lt) = parse_and_typecheck("main :: List Int\nmain = take 3 (infiniteFrom 1)");
+    for label in ast.get_prelude_labels(ast.root) {
+        lt.set_opaque(&label).unwrap();
+    }
+
+    // Any order can unfold infiniteFrom forever, so the graph stops at its bound
+    let g = explore_reductions(&ast, &lt, 100);
+    assert!(!g.complete);
+    let normal_forms = g.normal_forms();
+    assert_eq!(normal_forms.len(), 1);
+    assert_eq!(
+        g.terms[normal_forms[0]].term,
+        "Cons 1 (Cons 2 (Cons 3 Nil))"
+    );
+}
@@ -1,16 +1,121 @@
 use crate::{ASTNodeType, AST};
+mod budget;
+mod graph;
 mod reduce;
+mod session;
+mod strategy;
+mod termination;
+mod trace;
 
-pub use reduce::{find_all_redex_contraction_pairs, find_single_redex_contraction_pair};
+pub use budget::{
+    reduce_within_budget, BeforeStep, Budget, EvaluationConfig, Outcome, Reduction, TraceStep,
+};
+pub use graph::{
+    explore_reductions, explore_reductions_with, GraphStep, GraphTerm, ReductionGraph,
+    DEFAULT_GRAPH_BOUND, GRAPH_FORMAT_VERSION,
+};
+pub(crate) use pattern_match::{match_pattern, Match, Scrutinee, Shape};
+pub use reduce::{
+    contract, contract_with, find_all_redex_contraction_pairs,
+    find_all_redex_contraction_pairs_with, find_all_redexes, find_all_redexes_with,
+    find_single_redex, find_single_redex_contraction_pair, find_single_redex_contraction_pair_with,
+    find_single_redex_with, ModuleContext,
+};
+pub use session::ReductionSession;
+pub use strategy::{
+    strategy_from_name, ApplicativeOrder, CallByNeed, EvaluationStrategy, InnermostRightmost,
+    NormalOrder, SeededRandom, STRATEGY_NAMES,
+};
+pub use termination::{
+    NonTermination, TerminationChecker, DEFAULT_SIZE_BUDGET, DEFAULT_STEP_LIMIT,
+};
+pub use trace::TRACE_FORMAT_VERSION;
+
+/// What kind of step contracts a redex
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedexKind {
+    /// An inbuilt applied to as many literals as it takes
+    Inbuilt,
+    /// A label defined in the module applied to as many arguments as it has variables
+    Function,
+    /// An abstraction applied to as many arguments as it has variables
+    Abstraction,
+    /// A label on its own, substituted by its definition
+    Label,
+    /// A match whose scrutinee matches the pattern of the case at this index
+    Match(usize),
+    /// A type annotation, which is erased
+    Annotation,
+    /// An abstraction \x. f x where x is not free in f, which is replaced by f
+    Eta,
+    /// A call to an opaque label, or an opaque label that isn't a function, which is evaluated
+    /// to its value in one step
+    Opaque,
+}
+
+impl RedexKind {
+    /// The name of the rule, as written in traces
+    pub fn name(&self) -> &'static str {
+        match self {
+            RedexKind::Inbuilt => "inbuilt",
+            RedexKind::Function => "function",
+            RedexKind::Abstraction => "abstraction",
+            RedexKind::Label => "label",
+            RedexKind::Match(_) => "match",
+            RedexKind::Annotation => "annotation",
+            RedexKind::Eta => "eta",
+            RedexKind::Opaque => "opaque",
+        }
+    }
+}
+
+/// A redex, found without building its contractum, so that only the one that is picked needs
+/// contracting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Redex {
+    pub from: usize,
+    pub kind: RedexKind,
+    /// Whether the redex is inside the body of an abstraction or match case, which only
+    /// reduction under lambdas looks in
+    pub under_binder: bool,
+}
+
+/// Reduction rules beyond the ones evaluation always uses, which stops at weak head normal
+/// form. Off by default
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReductionRules {
+    /// Reduce inside the bodies of abstractions and match cases, so that reduction goes on to
+    /// beta normal form.
+    /// Arguments are then no longer closed, so this isn't for strategies that share them
+    pub under_lambdas: bool,
+    /// Eta-reduce \x. f x to f when x is not free in f
+    pub eta: bool,
+}
 
 #[derive(Clone)]
 pub struct RCPair {
     pub from: usize,
+    pub kind: RedexKind,
     pub to: AST,
     pub msg_after: String,
     pub msg_before: String,
+    /// Whether the redex was found by reducing under lambdas, so that
+    /// do_rc_subst_replacing_equal looks for equal redexes in the bodies of abstractions too
+    pub under_lambdas: bool,
 }
 
+#[cfg(test)]
+mod budget_test;
+#[cfg(test)]
+mod graph_test;
 mod pattern_match;
 #[cfg(test)]
 mod reduce_test;
+#[cfg(test)]
+mod session_test;
+#[cfg(test)]
+mod strategy_test;
+#[cfg(test)]
+mod termination_test;
+#[cfg(test)]
+mod trace_test;
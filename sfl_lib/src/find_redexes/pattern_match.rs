@@ -8,194 +8,137 @@ pub enum PatternMatchResult {
     Unknown,
 }
 
-use PatternMatchResult::*;
+/// As much of an expression as a pattern can see, without evaluating it any further
+pub(crate) enum Shape<S> {
+    Literal(String),
+    Pair(S, S),
+    /// A constructor, by name, applied to its arguments so far
+    Constructor(String, Vec<S>),
+    /// An abstraction, or another function, which only variables and wildcards match
+    Function,
+    /// Not evaluated far enough to tell what it is
+    Unevaluated,
+}
 
-fn match_against_app(ast: &AST, expr: usize, pattern: usize) -> PatternMatchResult {
-    let expr_n = ast.get(expr);
-    let pattern_n = ast.get(pattern);
+/// Something that can be matched against a pattern: an expression for the stepper, or a thunk for
+/// the interpreter. Both match by the same rules, as they must agree on which case is taken
+pub(crate) trait Scrutinee: Clone {
+    fn shape(&self, ast: &AST) -> Shape<Self>;
+}
 
-    #[cfg(debug_assertions)]
-    let _expr_str = ast.to_string_sugar(expr, false);
-    #[cfg(debug_assertions)]
-    let _pat_str = ast.to_string_sugar(pattern, false);
+pub(crate) enum Match<S> {
+    Refute,
+    /// The variables the pattern binds, and what they are bound to
+    Success(Vec<(String, S)>),
+    /// The scrutinee must be evaluated further before the pattern can be matched or refuted.
+    /// Gives the first part of it that must be
+    Unknown(S),
+}
 
-    assert_eq!(pattern_n.t, ASTNodeType::Application);
+impl Scrutinee for usize {
+    fn shape(&self, ast: &AST) -> Shape<Self> {
+        let expr_n = ast.get(*self);
+        match expr_n.t {
+            ASTNodeType::Literal => Shape::Literal(expr_n.get_value()),
+            ASTNodeType::Pair => Shape::Pair(ast.get_first(*self), ast.get_second(*self)),
+            ASTNodeType::Abstraction => Shape::Function,
+            ASTNodeType::Identifier | ASTNodeType::Application => {
+                let head = ast.get_app_head(*self);
+                let head_n = ast.get(head);
+                // Only a constructor can be told apart without reducing it
+                if head_n.t != ASTNodeType::Identifier || !head_n.is_uppercase() {
+                    return Shape::Unevaluated;
+                }
 
-    match expr_n.t {
-        ASTNodeType::Application => {
-            let lhs = pattern_match(ast, ast.get_func(expr), ast.get_func(pattern));
-            let rhs = pattern_match(ast, ast.get_arg(expr), ast.get_arg(pattern));
-            match (lhs, rhs) {
-                (Success(mut lhs), Success(rhs)) => {
-                    lhs.extend(rhs);
-                    Success(lhs)
+                let mut args = vec![];
+                let mut expr = *self;
+                while expr != head {
+                    args.push(ast.get_arg(expr));
+                    expr = ast.get_func(expr);
                 }
-                (Unknown, _) | (_, Unknown) => Unknown,
-                (Refute, _) | (_, Refute) => Refute,
-            }
-        }
-        ASTNodeType::Pair | ASTNodeType::Literal | ASTNodeType::Abstraction => Refute,
-        ASTNodeType::Identifier => {
-            if expr_n.is_uppercase() {
-                Refute
-            } else {
-                Unknown
+                args.reverse();
+                Shape::Constructor(head_n.get_value(), args)
             }
+            ASTNodeType::Match | ASTNodeType::Annotation => Shape::Unevaluated,
+            _ => unreachable!("Not an expression"),
         }
-        ASTNodeType::Match => Unknown,
-        _ => unreachable!(),
     }
 }
 
-fn match_against_pair(ast: &AST, expr: usize, pattern: usize) -> PatternMatchResult {
-    let expr_n = ast.get(expr);
-    let pattern_n = ast.get(pattern);
-
-    #[cfg(debug_assertions)]
-    let _expr_str = ast.to_string_sugar(expr, false);
-    #[cfg(debug_assertions)]
-    let _pat_str = ast.to_string_sugar(pattern, false);
-
-    assert_eq!(pattern_n.t, ASTNodeType::Pair);
-
-    match expr_n.t {
-        ASTNodeType::Application => {
-            // If the head is a constructor then refute
-            if ast.get(ast.get_app_head(expr)).is_uppercase() {
-                Refute
-            } else {
-                Unknown
-            }
-        }
-        ASTNodeType::Pair => {
-            let lhs = pattern_match(ast, ast.get_first(expr), ast.get_first(pattern));
-            let rhs = pattern_match(ast, ast.get_second(expr), ast.get_second(pattern));
-            match (lhs, rhs) {
-                (Success(mut lhs), Success(rhs)) => {
-                    lhs.extend(rhs);
-                    Success(lhs)
-                }
-                (Unknown, _) | (_, Unknown) => Unknown,
-                (Refute, _) | (_, Refute) => Refute,
-            }
+/// Match each of the scrutinees against its pattern. If any can't be told yet, the whole match
+/// can't be, even if another is refuted
+fn match_all<S: Scrutinee>(ast: &AST, pairs: impl IntoIterator<Item = (usize, S)>) -> Match<S> {
+    let mut bindings = vec![];
+    let mut refuted = false;
+    for (pattern, s) in pairs {
+        match match_pattern(ast, pattern, &s) {
+            Match::Success(more) => bindings.extend(more),
+            Match::Refute => refuted = true,
+            unknown @ Match::Unknown(_) => return unknown,
         }
-        ASTNodeType::Identifier => {
-            if expr_n.is_uppercase() {
-                Refute
-            } else {
-                Unknown
-            }
-        }
-        ASTNodeType::Literal | ASTNodeType::Abstraction => Refute,
-        ASTNodeType::Match => Unknown,
-        _ => unreachable!(),
+    }
+    if refuted {
+        Match::Refute
+    } else {
+        Match::Success(bindings)
     }
 }
 
-fn match_against_literal(ast: &AST, expr: usize, pattern: usize) -> PatternMatchResult {
-    let expr_n = ast.get(expr);
+/// Match a scrutinee against a pattern, giving the bindings it makes
+pub(crate) fn match_pattern<S: Scrutinee>(ast: &AST, pattern: usize, s: &S) -> Match<S> {
     let pattern_n = ast.get(pattern);
 
-    #[cfg(debug_assertions)]
-    let _expr_str = ast.to_string_sugar(expr, false);
     #[cfg(debug_assertions)]
     let _pat_str = ast.to_string_sugar(pattern, false);
 
-    assert_eq!(pattern_n.t, ASTNodeType::Literal);
-
-    match expr_n.t {
-        ASTNodeType::Application => {
-            // If the head is a constructor then refute
-            if ast.get(ast.get_app_head(expr)).is_uppercase() {
-                Refute
-            } else {
-                Unknown
-            }
+    if pattern_n.t == ASTNodeType::Identifier {
+        match pattern_n.value_str().chars().next().unwrap() {
+            // Wildcard
+            'a'..='z' => return Match::Success(vec![(pattern_n.get_value(), s.clone())]),
+            // Non binding wildcard
+            '_' => return Match::Success(vec![]),
+            _ => {}
         }
-        ASTNodeType::Literal => {
-            if expr_n.get_value() == pattern_n.get_value() {
-                Success(HashMap::new())
+    }
+
+    match (pattern_n.t, s.shape(ast)) {
+        (_, Shape::Unevaluated) => Match::Unknown(s.clone()),
+        (ASTNodeType::Literal, Shape::Literal(value)) => {
+            if value == pattern_n.value_str() {
+                Match::Success(vec![])
             } else {
-                Refute
+                Match::Refute
             }
         }
-        ASTNodeType::Identifier => {
-            if expr_n.is_uppercase() {
-                Refute
-            } else {
-                Unknown
+        (ASTNodeType::Pair, Shape::Pair(a, b)) => match_all(
+            ast,
+            [(ast.get_first(pattern), a), (ast.get_second(pattern), b)],
+        ),
+        // A constructor, applied to patterns for its arguments
+        (ASTNodeType::Identifier | ASTNodeType::Application, Shape::Constructor(c, args)) => {
+            let head = ast.get_app_head(pattern);
+            let mut arg_patterns = vec![];
+            let mut p = pattern;
+            while p != head {
+                arg_patterns.push(ast.get_arg(p));
+                p = ast.get_func(p);
             }
-        }
-        ASTNodeType::Abstraction | ASTNodeType::Pair => Refute,
-        ASTNodeType::Match => Unknown,
-        _ => unreachable!(),
-    }
-}
-
-fn match_against_identifier(ast: &AST, expr: usize, pattern: usize) -> PatternMatchResult {
-    let expr_n = ast.get(expr);
-    let pattern_n = ast.get(pattern);
+            arg_patterns.reverse();
 
-    #[cfg(debug_assertions)]
-    let _expr_str = ast.to_string_sugar(expr, false);
-    #[cfg(debug_assertions)]
-    let _pat_str = ast.to_string_sugar(pattern, false);
-
-    assert_eq!(pattern_n.t, ASTNodeType::Identifier);
-
-    let pat_first_char = pattern_n.get_value().chars().nth(0).unwrap();
-    match pat_first_char {
-        // Wildcard
-        'a'..='z' => {
-            let mut map: HashMap<String, usize> = HashMap::new();
-            map.insert(pattern_n.get_value(), expr);
-            return Success(map);
-        }
-        // Non binding wildcard
-        '_' => return Success(HashMap::new()),
-
-        // Constructor
-        'A'..='Z' => match expr_n.t {
-            ASTNodeType::Identifier => {
-                let expr_first_char = expr_n.get_value().chars().nth(0).unwrap();
-                match expr_first_char {
-                    'A'..='Z' => {
-                        // If its another constructor, then we can refute the pattern
-                        if expr_n.get_value() == pattern_n.get_value() {
-                            Success(HashMap::new())
-                        } else {
-                            Refute
-                        }
-                    }
-                    // If its not a constructor we cant resolve the pattern
-                    _ => Unknown,
-                }
-            }
-            ASTNodeType::Application => {
-                let head_n = ast.get(ast.get_app_head(expr));
-                // We can only refute if the head of our application is a constructor as that means
-                // it will definitely not eval to this pattern
-                if head_n.is_uppercase() {
-                    Refute
-                } else {
-                    Unknown
-                }
+            if ast.get(head).value_str() != c || arg_patterns.len() != args.len() {
+                return Match::Refute;
             }
-            ASTNodeType::Literal | ASTNodeType::Pair | ASTNodeType::Abstraction => Refute,
-            ASTNodeType::Match => Unknown,
-            _ => unreachable!("Not an expression"),
-        },
-        _ => unreachable!("invalid first char"),
+            match_all(ast, arg_patterns.into_iter().zip(args))
+        }
+        _ => Match::Refute,
     }
 }
 
 /// Get if pattern is matched, and returns bindings
 pub fn pattern_match(ast: &AST, expr: usize, pattern: usize) -> PatternMatchResult {
-    match ast.get(pattern).t {
-        ASTNodeType::Identifier => match_against_identifier(ast, expr, pattern),
-        ASTNodeType::Application => match_against_app(ast, expr, pattern),
-        ASTNodeType::Pair => match_against_pair(ast, expr, pattern),
-        ASTNodeType::Literal => match_against_literal(ast, expr, pattern),
-        _ => unreachable!(),
+    match match_pattern(ast, pattern, &expr) {
+        Match::Refute => PatternMatchResult::Refute,
+        Match::Success(bindings) => PatternMatchResult::Success(bindings.into_iter().collect()),
+        Match::Unknown(_) => PatternMatchResult::Unknown,
     }
 }
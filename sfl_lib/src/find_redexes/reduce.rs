@@ -1,8 +1,11 @@
 use super::pattern_match::PatternMatchResult;
 use super::*;
 use crate::find_redexes::pattern_match::pattern_match;
-use crate::functions::KnownTypeLabelTable;
-use std::collections::HashMap;
+use crate::functions::{KnownTypeLabelTable, Label};
+use crate::interpreter::evaluate_within;
+use crate::ASTNode;
+use std::collections::{HashMap, HashSet};
+use std::iter::zip;
 
 fn comma_ify(vec: Vec<String>) -> String {
     match vec.len() {
@@ -20,219 +23,708 @@ fn comma_ify(vec: Vec<String>) -> String {
     }
 }
 
-/// This will check for applications to functions:
-/// - lables with func types
-/// - lambda abstractions
-/// - inbuilt functions
-/// with the right num of args.
-/// For example, a call to a inbuilt add could be: add 2 3
-/// Which would look like
-/// App[[App add 2], 3]
-/// This function checks that the rhs is a literal, and the lhs is
-/// either a function or an App of a function in the set of funcs
-/// with the right num of args
-fn check_for_valid_call(
-    ast: &AST,
-    expr: usize,
-    lt: &KnownTypeLabelTable,
-    am: HashMap<String, usize>,
-) -> Option<RCPair> {
-    let mut f = ast.get_func(expr);
-    let mut x = ast.get_arg(expr);
-    let mut argv = vec![];
-    let mut argv_ids = vec![];
+/// Call an inbuilt, replacing the result with an error term if it fails at runtime.
+/// Returns whether the call failed alongside the contractum
+fn call_inbuilt_or_error(label: &Label, call: &ASTNode, args: Vec<&ASTNode>) -> (AST, bool) {
+    match label.call_inbuilt(call, args) {
+        Ok(ast) => (ast, false),
+        Err(e) => {
+            let mut ast = AST::new();
+            ast.root = ast.add_error(e, call.line, call.col);
+            (ast, true)
+        }
+    }
+}
 
-    let mut argv_strs = vec![];
+/// The steps the interpreter may take to evaluate an opaque redex before it is stepped into
+/// instead, as its value may be infinite
+const OPAQUE_STEP_LIMIT: usize = 10_000;
 
-    // True if only literals encountered. If true, then we can call inbuilt functions
-    let mut literals_only = true;
+/// How many arguments an opaque label is evaluated once it has: as many as its type takes, or
+/// as many variables as its definition has if it has no type
+fn opaque_arity(ast: &AST, label: &Label, assign_exp: usize) -> usize {
+    match &label.label_type {
+        Some(t) => t.get_arity(),
+        None => ast.get_n_abstr_vars(assign_exp, usize::MAX).len(),
+    }
+}
 
-    loop {
-        argv.push(ast.get(x));
-        argv_ids.push(x);
+/// What redex search needs to know about a module: where each of its labels is defined. Found
+/// once and reused for every step of a reduction, rather than at every search. Compacting the
+/// AST renumbers the assignments, so a context must be made again after compacting
+#[derive(Clone, Debug, Default)]
+pub struct ModuleContext {
+    module: Option<usize>,
+    assigns: HashMap<String, usize>,
+}
 
-        let arg_str = ast.to_string_sugar(x, false);
-        match ast.get(x).t {
-            ASTNodeType::Application | ASTNodeType::Abstraction => {
-                argv_strs.push(format!("({})", arg_str));
-            }
-            _ => {
-                argv_strs.push(arg_str);
-            }
+impl ModuleContext {
+    pub fn new(ast: &AST, module: Option<usize>) -> Self {
+        // Dont need to worry about this as main must be at the end, so everything defined in
+        // the module is defined here
+        let assigns = match module {
+            Some(m) => ast.get_assigns_map(m),
+            None => HashMap::new(),
+        };
+        Self { module, assigns }
+    }
+
+    pub fn module(&self) -> Option<usize> {
+        self.module
+    }
+
+    /// find_all_redexes_with, in this module
+    pub fn find_all_redexes(
+        &self,
+        ast: &AST,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Vec<Redex> {
+        let mut redexes = vec![];
+        Search::new(ast, self, lt, rules).find_all(expr, false, &mut redexes);
+        redexes
+    }
+
+    /// find_single_redex_with, in this module
+    pub fn find_single_redex(
+        &self,
+        ast: &AST,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<Redex> {
+        Search::new(ast, self, lt, rules).find_single(expr, false)
+    }
+
+    /// contract_with, in this module
+    pub fn contract(
+        &self,
+        ast: &AST,
+        redex: Redex,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> RCPair {
+        Search::new(ast, self, lt, rules).contract(redex)
+    }
+
+    /// find_all_redex_contraction_pairs_with, in this module
+    pub fn find_all_redex_contraction_pairs(
+        &self,
+        ast: &AST,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Vec<RCPair> {
+        let search = Search::new(ast, self, lt, rules);
+        let mut redexes = vec![];
+        search.find_all(expr, false, &mut redexes);
+        redexes
+            .into_iter()
+            .map(|redex| search.contract(redex))
+            .collect()
+    }
+
+    /// find_single_redex_contraction_pair_with, in this module
+    pub fn find_single_redex_contraction_pair(
+        &self,
+        ast: &AST,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<RCPair> {
+        let search = Search::new(ast, self, lt, rules);
+        search
+            .find_single(expr, false)
+            .map(|redex| search.contract(redex))
+    }
+}
+
+/// A search for redexes in one state of a module
+struct Search<'a> {
+    ast: &'a AST,
+    ctx: &'a ModuleContext,
+    lt: &'a KnownTypeLabelTable,
+    rules: ReductionRules,
+}
+
+impl<'a> Search<'a> {
+    fn new(
+        ast: &'a AST,
+        ctx: &'a ModuleContext,
+        lt: &'a KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Self {
+        Self {
+            ast,
+            ctx,
+            lt,
+            rules,
         }
+    }
 
-        match ast.get(x).t {
-            ASTNodeType::Literal => {}
-            _ => literals_only = false,
+    /// The function at the head of an application, and its arguments, last first
+    fn spine(&self, expr: usize) -> (usize, Vec<usize>) {
+        let mut f = expr;
+        let mut args = vec![];
+        while self.ast.get(f).t == ASTNodeType::Application {
+            args.push(self.ast.get_arg(f));
+            f = self.ast.get_func(f);
         }
-        let f_node = ast.get(f);
+        (f, args)
+    }
+
+    /// Whether the abstraction has a variable for each argument, last first, and each
+    /// argument for a pair pattern is a pair
+    fn takes_args(&self, abst: usize, args: &[usize]) -> bool {
+        let vars = self.ast.get_n_abstr_vars(abst, args.len());
+        vars.len() == args.len()
+            && zip(args, vars).all(|(arg, var)| {
+                self.ast.get(var).t != ASTNodeType::Pair
+                    || self.ast.get(*arg).t == ASTNodeType::Pair
+            })
+    }
+
+    /// This will check for applications to functions:
+    /// - lables with func types
+    /// - lambda abstractions
+    /// - inbuilt functions
+    /// with the right num of args.
+    /// For example, a call to a inbuilt add could be: add 2 3
+    /// Which would look like
+    /// App[[App add 2], 3]
+    /// Inbuilts can only be called once all their args are literals.
+    /// A call to an opaque label with all its arguments is evaluated, unless it is under a
+    /// binder, where its arguments may not be closed
+    fn find_call(&self, expr: usize, under: bool) -> Option<RedexKind> {
+        let (f, args) = self.spine(expr);
+        let literals_only = args
+            .iter()
+            .all(|arg| self.ast.get(*arg).t == ASTNodeType::Literal);
+        let f_node = self.ast.get(f);
 
         match f_node.t {
             ASTNodeType::Identifier => {
                 let name = f_node.get_value();
+                let label = self.lt.get(&name)?;
+                if label
+                    .inbuilt_reduction_arity
+                    .is_some_and(|arity| arity != args.len())
+                {
+                    return None;
+                }
 
-                return if let Some(label) = lt.get(&name) {
-                    if let Some(reduction_arity) = label.inbuilt_reduction_arity {
-                        if reduction_arity != argv.len() {
-                            return None;
-                        }
+                if label.is_inbuilt() {
+                    literals_only.then_some(RedexKind::Inbuilt)
+                } else {
+                    let assign_exp = self.ast.get_assign_exp(*self.ctx.assigns.get(&name)?);
+                    if self.lt.is_opaque(&name)
+                        && !under
+                        && opaque_arity(self.ast, label, assign_exp) == args.len()
+                    {
+                        Some(RedexKind::Opaque)
+                    } else {
+                        self.takes_args(assign_exp, &args)
+                            .then_some(RedexKind::Function)
                     }
+                }
+            }
+            ASTNodeType::Abstraction => (!(f_node.wait_for_args && literals_only)
+                && self.takes_args(f, &args))
+            .then_some(RedexKind::Abstraction),
+            _ => None,
+        }
+    }
 
-                    let argv_comma_str = comma_ify(argv_strs.iter().rev().cloned().collect());
-                    if label.is_inbuilt() {
-                        if literals_only {
-                            Some(RCPair {
-                                from: expr,
-                                to: label.call_inbuilt(f_node, argv),
-                                msg_after: format!(
-                                    "Applied inbuilt {} to {}",
-                                    name, &argv_comma_str
-                                ),
-                                msg_before: format!(
-                                    "Apply inbuilt {} to {}",
-                                    name, &argv_comma_str
-                                ),
-                            })
-                        } else {
-                            None
-                        }
-                    } else {
-                        let assign = match am.get(&name) {
-                            Some(a) => *a,
-                            None => return None,
-                        };
-
-                        let assign_exp = ast.get_assign_exp(assign);
-                        let n_args = ast.get_n_abstr_vars(assign_exp, argv.len());
-
-                        if ast.get_n_abstr_vars(assign_exp, argv.len()).len() != argv.len() {
-                            return None;
-                        }
-
-                        #[cfg(debug_assertions)]
-                        let _n_abstr_vars_strs = n_args
-                            .iter()
-                            .map(|a| ast.to_string_sugar(*a, false))
-                            .collect::<Vec<_>>();
-
-                        // Stop it being a ready call when a pair is expected but we dont have it
-                        for i in 0..argv.len() {
-                            let _cmp_pair = (&argv[i].t, &ast.get(n_args[i]).t);
-                            match (&argv[i].t, &ast.get(n_args[i]).t) {
-                                (ASTNodeType::Pair, ASTNodeType::Pair) => {}
-                                (_, ASTNodeType::Pair) => return None,
-                                _ => {}
-                            }
-                        }
-
-                        // let argv_ids = argv_ids.reverse();
-                        let call_result = ast.do_multiple_abst_substs(assign_exp, argv_ids);
-
-                        #[cfg(debug_assertions)]
-                        let _ready_call_result_str =
-                            call_result.to_string_sugar(call_result.root, false);
-
-                        Some(RCPair {
-                            from: expr,
-                            to: call_result,
-                            msg_after: format!("Applied function {} to {}", name, &argv_comma_str),
-                            msg_before: format!("Apply function {} to {}", name, &argv_comma_str),
-                        })
+    /// A label on its own is substituted by its definition, and an inbuilt that takes no
+    /// arguments is called. An opaque label that isn't a function is evaluated
+    fn find_label(&self, expr: usize) -> Option<RedexKind> {
+        let name = self.ast.get(expr).get_value();
+        let label = self.lt.get(&name)?;
+        if label.is_inbuilt() {
+            // It should not be non zero_ary func as otherwise it would be caught by the app case
+            return (label.inbuilt_reduction_arity.unwrap_or(0) == 0).then_some(RedexKind::Label);
+        }
+        let assign_exp = self.ast.get_assign_exp(*self.ctx.assigns.get(&name)?);
+        if self.lt.is_opaque(&name) && opaque_arity(self.ast, label, assign_exp) == 0 {
+            Some(RedexKind::Opaque)
+        } else {
+            Some(RedexKind::Label)
+        }
+    }
+
+    /// The case of the match that its scrutinee matches, if it can be told yet
+    fn find_match(&self, expr: usize) -> Option<RedexKind> {
+        let unpack_expr = self.ast.get_match_unpack_pattern(expr);
+        for (i, (pattern, _)) in self.ast.get_match_cases(expr).into_iter().enumerate() {
+            match pattern_match(self.ast, unpack_expr, pattern) {
+                PatternMatchResult::Success(_) => return Some(RedexKind::Match(i)),
+                // The scrutinee must be reduced further before this case can be tried
+                PatternMatchResult::Unknown => break,
+                PatternMatchResult::Refute => {}
+            }
+        }
+        None
+    }
+
+    /// Whether the abstraction is \x. f x with x not free in f, so it can be eta-reduced to f
+    fn is_eta_redex(&self, abst: usize) -> bool {
+        let var = self.ast.get(self.ast.get_abstr_var(abst));
+        let body = self.ast.get_abstr_expr(abst);
+        if var.t != ASTNodeType::Identifier || self.ast.get(body).t != ASTNodeType::Application {
+            return false;
+        }
+        let name = var.get_value();
+        let arg = self.ast.get(self.ast.get_arg(body));
+        arg.t == ASTNodeType::Identifier
+            && arg.get_value() == name
+            && !self
+                .ast
+                .get_free_identifiers(self.ast.get_func(body))
+                .contains(&name)
+    }
+
+    /// under is whether expr is inside the body of an abstraction or match case, which the
+    /// search only goes into if the rules reduce under lambdas
+    fn find_all(&self, expr: usize, under: bool, redexes: &mut Vec<Redex>) {
+        #[cfg(debug_assertions)]
+        let _exp_str = self.ast.to_string_sugar(expr, false);
+
+        let redex = |kind| Redex {
+            from: expr,
+            kind,
+            under_binder: under,
+        };
+        match self.ast.get(expr).t {
+            ASTNodeType::Literal | ASTNodeType::Error => {}
+            ASTNodeType::Abstraction => {
+                if self.rules.eta && self.is_eta_redex(expr) {
+                    redexes.push(redex(RedexKind::Eta));
+                }
+                if self.rules.under_lambdas {
+                    self.find_all(self.ast.get_abstr_expr(expr), true, redexes);
+                }
+            }
+            ASTNodeType::Application => {
+                if let Some(kind) = self.find_call(expr, under) {
+                    redexes.push(redex(kind));
+                }
+                self.find_all(self.ast.get_func(expr), under, redexes);
+                self.find_all(self.ast.get_arg(expr), under, redexes);
+            }
+            ASTNodeType::Pair => {
+                self.find_all(self.ast.get_first(expr), under, redexes);
+                self.find_all(self.ast.get_second(expr), under, redexes);
+            }
+            ASTNodeType::Match => {
+                match self.find_match(expr) {
+                    Some(kind) => redexes.push(redex(kind)),
+                    None => redexes
+                        .extend(self.find_single(self.ast.get_match_unpack_pattern(expr), under)),
+                }
+                if self.rules.under_lambdas {
+                    for (_, case_expr) in self.ast.get_match_cases(expr) {
+                        self.find_all(case_expr, true, redexes);
                     }
+                }
+            }
+            ASTNodeType::Identifier => redexes.extend(self.find_single(expr, under)),
+            ASTNodeType::Annotation => {
+                redexes.push(redex(RedexKind::Annotation));
+                self.find_all(self.ast.get_annotated_expr(expr), under, redexes);
+            }
+            _ => panic!("Expected expression"),
+        }
+    }
+
+    fn find_single(&self, expr: usize, under: bool) -> Option<Redex> {
+        #[cfg(debug_assertions)]
+        let _exp_str = self.ast.to_string_sugar(expr, false);
+
+        let redex = |kind| Redex {
+            from: expr,
+            kind,
+            under_binder: under,
+        };
+        match self.ast.get(expr).t {
+            ASTNodeType::Literal | ASTNodeType::Error => None,
+            // The abstraction itself is outside anything in its body
+            ASTNodeType::Abstraction => {
+                if self.rules.eta && self.is_eta_redex(expr) {
+                    Some(redex(RedexKind::Eta))
+                } else if self.rules.under_lambdas {
+                    self.find_single(self.ast.get_abstr_expr(expr), true)
                 } else {
-                    let _x = lt.get_non_builtin_type_map();
                     None
-                };
+                }
             }
-            ASTNodeType::Abstraction => {
-                return if !(f_node.wait_for_args && literals_only) {
-                    let n_args = ast.get_n_abstr_vars(f, argv.len());
+            ASTNodeType::Pair => self
+                .find_single(self.ast.get_first(expr), under)
+                .or_else(|| self.find_single(self.ast.get_second(expr), under)),
+            ASTNodeType::Identifier => self.find_label(expr).map(redex),
+            ASTNodeType::Application => match self.find_call(expr, under) {
+                Some(kind) => Some(redex(kind)),
+                None => self
+                    .find_single(self.ast.get_func(expr), under)
+                    .or_else(|| self.find_single(self.ast.get_arg(expr), under)),
+            },
+            ASTNodeType::Match => match self.find_match(expr) {
+                Some(kind) => Some(redex(kind)),
+                None => self
+                    .find_single(self.ast.get_match_unpack_pattern(expr), under)
+                    .or_else(|| {
+                        // Like the body of an abstraction, the cases bind their patterns' variables
+                        self.ast
+                            .get_match_cases(expr)
+                            .into_iter()
+                            .filter(|_| self.rules.under_lambdas)
+                            .find_map(|(_, case_expr)| self.find_single(case_expr, true))
+                    }),
+            },
+            ASTNodeType::Annotation => Some(redex(RedexKind::Annotation)),
+            _ => None,
+        }
+    }
 
-                    if argv.len() != n_args.len() {
-                        return None;
-                    }
+    fn contract(&self, redex: Redex) -> RCPair {
+        let expr = redex.from;
+        let mut rc = match redex.kind {
+            RedexKind::Inbuilt | RedexKind::Function | RedexKind::Abstraction => {
+                self.contract_call(expr, redex.kind)
+            }
+            RedexKind::Label => self.contract_label(expr),
+            RedexKind::Match(case) => self.contract_match(expr, case),
+            RedexKind::Annotation => erase_annotation(self.ast, expr),
+            RedexKind::Eta => eta_reduce(self.ast, expr),
+            RedexKind::Opaque => self.contract_opaque(expr),
+        };
+        rc.under_lambdas = self.rules.under_lambdas;
+        if redex.under_binder {
+            rc.msg_before += " under a binder";
+            rc.msg_after += " under a binder";
+        }
+        rc
+    }
+
+    /// Substitute args into the abstraction f. Under lambdas, the arguments can have variables
+    /// free that f binds, so f's variables are renamed apart from them first
+    fn substitute(&self, f: usize, args: Vec<usize>) -> AST {
+        if !self.rules.under_lambdas {
+            return self.ast.do_multiple_abst_substs(f, args);
+        }
+        let avoid: HashSet<String> = args
+            .iter()
+            .flat_map(|arg| self.ast.get_free_identifiers(*arg))
+            .collect();
+        let mut renamed = self.ast.clone_node(f);
+        let root = renamed.root;
+        renamed.rename_bound_vars(root, &avoid);
+        let args = args
+            .iter()
+            .map(|arg| renamed.append(self.ast, *arg))
+            .collect();
+        renamed.do_multiple_abst_substs(root, args)
+    }
 
-                    for i in 0..argv.len() {
-                        match (&argv[i].t, &ast.get(n_args[i]).t) {
-                            (ASTNodeType::Pair, ASTNodeType::Pair) => {}
-                            (_, ASTNodeType::Pair) => return None,
-                            _ => {}
-                        }
+    fn contract_call(&self, expr: usize, kind: RedexKind) -> RCPair {
+        let ast = self.ast;
+        let (f, args) = self.spine(expr);
+        let argv_strs: Vec<String> = args
+            .iter()
+            .rev()
+            .map(|arg| {
+                let arg_str = ast.to_string_sugar(*arg, false);
+                match ast.get(*arg).t {
+                    ASTNodeType::Application | ASTNodeType::Abstraction => {
+                        format!("({})", arg_str)
                     }
+                    _ => arg_str,
+                }
+            })
+            .collect();
+        let argv_comma_str = comma_ify(argv_strs);
 
-                    let argv_comma_str = comma_ify(argv_strs.iter().rev().cloned().collect());
+        match kind {
+            RedexKind::Inbuilt => {
+                let f_node = ast.get(f);
+                let name = f_node.get_value();
+                let label = self.lt.get(&name).unwrap();
+                let argv = args.iter().map(|arg| ast.get(*arg)).collect();
+                let (to, failed) = call_inbuilt_or_error(label, f_node, argv);
+                let msg_after = if failed {
+                    format!(
+                        "Runtime error applying inbuilt {} to {}",
+                        name, &argv_comma_str
+                    )
+                } else {
+                    format!("Applied inbuilt {} to {}", name, &argv_comma_str)
+                };
+                RCPair {
+                    from: expr,
+                    kind,
+                    to,
+                    msg_after,
+                    msg_before: format!("Apply inbuilt {} to {}", name, &argv_comma_str),
+                    under_lambdas: false,
+                }
+            }
+            RedexKind::Function => {
+                let name = ast.get(f).get_value();
+                let assign_exp = ast.get_assign_exp(self.ctx.assigns[&name]);
+                let call_result = self.substitute(assign_exp, args);
+
+                #[cfg(debug_assertions)]
+                let _ready_call_result_str = call_result.to_string_sugar(call_result.root, false);
+
+                RCPair {
+                    from: expr,
+                    kind,
+                    to: call_result,
+                    msg_after: format!("Applied function {} to {}", name, &argv_comma_str),
+                    msg_before: format!("Apply function {} to {}", name, &argv_comma_str),
+                    under_lambdas: false,
+                }
+            }
+            RedexKind::Abstraction => {
+                let call_result = self.substitute(f, args);
+
+                #[cfg(debug_assertions)]
+                let _ready_call_result_str = call_result.to_string_sugar(call_result.root, false);
+
+                RCPair {
+                    from: expr,
+                    kind,
+                    to: call_result,
+                    msg_after: format!("Apply abstraction to {}", &argv_comma_str),
+                    msg_before: format!("Apply abstraction to {}", &argv_comma_str),
+                    under_lambdas: false,
+                }
+            }
+            _ => unreachable!("Not a call"),
+        }
+    }
 
-                    let call_result = ast.do_multiple_abst_substs(f, argv_ids);
+    /// Evaluate the call, or label, with the interpreter, so that its steps aren't shown. If that
+    /// takes too long, its first step is taken instead
+    fn contract_opaque(&self, expr: usize) -> RCPair {
+        let module = self
+            .ctx
+            .module
+            .expect("opaque labels are only found in a module");
+        let to = match evaluate_within(self.ast, module, expr, self.lt, OPAQUE_STEP_LIMIT) {
+            Some(to) => to,
+            None => return self.step_into_opaque(expr),
+        };
+        let expr_str = self.ast.to_string_sugar(expr, false);
+        let msg_after = if to.get(to.root).t == ASTNodeType::Error {
+            format!("Runtime error evaluating {}", expr_str)
+        } else {
+            format!("Evaluated {}", expr_str)
+        };
+        RCPair {
+            from: expr,
+            kind: RedexKind::Opaque,
+            to,
+            msg_after,
+            msg_before: format!("Evaluate {}", expr_str),
+            under_lambdas: false,
+        }
+    }
 
-                    #[cfg(debug_assertions)]
-                    let _ready_call_result_str =
-                        call_result.to_string_sugar(call_result.root, false);
+    /// The step an opaque call, or label, would take if it weren't opaque
+    fn step_into_opaque(&self, expr: usize) -> RCPair {
+        let (f, args) = self.spine(expr);
+        let assign_exp = self
+            .ast
+            .get_assign_exp(self.ctx.assigns[&self.ast.get(f).get_value()]);
+        // A label defined without all the variables its type takes, like sum, is substituted on
+        // its own
+        let mut rc = if !args.is_empty() && self.takes_args(assign_exp, &args) {
+            self.contract_call(expr, RedexKind::Function)
+        } else {
+            self.contract_label(f)
+        };
+        rc.msg_before += " (too long to evaluate at once)";
+        rc.msg_after += " (too long to evaluate at once)";
+        rc
+    }
 
-                    Some(RCPair {
-                        from: expr,
-                        to: call_result,
-                        msg_after: format!("Apply abstraction to {}", &argv_comma_str),
-                        msg_before: format!("Apply abstraction to {}", &argv_comma_str),
-                    })
-                } else {
-                    None
-                }
+    fn contract_label(&self, expr: usize) -> RCPair {
+        let value = self.ast.get(expr).get_value();
+        let label = self.lt.get(&value).unwrap();
+        if label.is_inbuilt() {
+            let (subst_result, failed) = call_inbuilt_or_error(label, self.ast.get(expr), vec![]);
+            let msg_after = if failed {
+                format!("Runtime error substituting label {}", &value)
+            } else {
+                format!("Substituted label {}", &value)
+            };
+            RCPair {
+                from: expr,
+                kind: RedexKind::Label,
+                to: subst_result,
+                msg_after,
+                msg_before: format!("Substitute label {}", &value),
+                under_lambdas: false,
             }
-            ASTNodeType::Application => {
-                x = ast.get_arg(f);
-                f = ast.get_func(f);
+        } else {
+            let assign_exp = self.ast.get_assign_exp(self.ctx.assigns[&value]);
+            RCPair {
+                from: expr,
+                kind: RedexKind::Label,
+                to: self.ast.clone_node(assign_exp),
+                msg_after: format!("Substituted label {}", &value),
+                msg_before: format!("Substitute label {}", &value),
+                under_lambdas: false,
+            }
+        }
+    }
+
+    fn contract_match(&self, expr: usize, case: usize) -> RCPair {
+        let ast = self.ast;
+        let unpack_expr = ast.get_match_unpack_pattern(expr);
+        let (pattern, pattern_expr) = ast.get_match_cases(expr)[case];
+        let bindings = match pattern_match(ast, unpack_expr, pattern) {
+            PatternMatchResult::Success(bindings) => bindings,
+            _ => unreachable!("The case was found to match"),
+        };
+
+        let case_str = ast.to_string_sugar(pattern, false);
+        let mut pat_expr_cloned = ast.clone_node(pattern_expr);
+        if self.rules.under_lambdas {
+            let avoid: HashSet<String> = bindings
+                .values()
+                .flat_map(|replacement| ast.get_free_identifiers(*replacement))
+                .collect();
+            let root = pat_expr_cloned.root;
+            pat_expr_cloned.rename_bound_vars(root, &avoid);
+        }
+        for (var, replacement) in bindings {
+            let replacement_appended = pat_expr_cloned.append(ast, replacement);
+            let usages =
+                pat_expr_cloned.get_all_free_instances_of_var_in_exp(pat_expr_cloned.root, &var);
+            for usage in usages {
+                pat_expr_cloned.replace_references_to_node(usage, replacement_appended);
             }
-            _ => return None,
         }
+        RCPair {
+            from: expr,
+            kind: RedexKind::Match(case),
+            to: pat_expr_cloned.clone_node(pat_expr_cloned.root),
+            msg_after: format!("Matched to pattern {}", case_str),
+            msg_before: format!("Match to pattern {}", case_str),
+            under_lambdas: false,
+        }
+    }
+}
+
+/// Erase the annotation from (e :: T), leaving e. Annotations only matter to the typechecker
+fn erase_annotation(ast: &AST, anno: usize) -> RCPair {
+    let t = ast.get(anno).type_assignment.as_ref().unwrap();
+    RCPair {
+        from: anno,
+        kind: RedexKind::Annotation,
+        to: ast.clone_node(ast.get_annotated_expr(anno)),
+        msg_after: format!("Erased type annotation {}", t),
+        msg_before: format!("Erase type annotation {}", t),
+        under_lambdas: false,
     }
 }
 
+/// Contract \x. f x to f
+fn eta_reduce(ast: &AST, abst: usize) -> RCPair {
+    let var = ast.get(ast.get_abstr_var(abst)).get_value();
+    RCPair {
+        from: abst,
+        kind: RedexKind::Eta,
+        to: ast.clone_node(ast.get_func(ast.get_abstr_expr(abst))),
+        msg_after: format!("Eta-reduced abstraction over {}", var),
+        msg_before: format!("Eta-reduce abstraction over {}", var),
+        under_lambdas: false,
+    }
+}
+
+/// Every redex in expr, in the order they are found: a call before the redexes inside it, left
+/// to right. Contracta are not built, so this is cheap even when most are never contracted
+pub fn find_all_redexes(
+    ast: &AST,
+    module: Option<usize>,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+) -> Vec<Redex> {
+    find_all_redexes_with(ast, module, expr, lt, ReductionRules::default())
+}
+
+/// find_all_redexes, also finding the redexes the rules allow
+pub fn find_all_redexes_with(
+    ast: &AST,
+    module: Option<usize>,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+    rules: ReductionRules,
+) -> Vec<Redex> {
+    ModuleContext::new(ast, module).find_all_redexes(ast, expr, lt, rules)
+}
+
+/// The leftmost outermost redex in expr, which lazy evaluation contracts next
+pub fn find_single_redex(
+    ast: &AST,
+    module: Option<usize>,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+) -> Option<Redex> {
+    find_single_redex_with(ast, module, expr, lt, ReductionRules::default())
+}
+
+/// find_single_redex, also finding the redexes the rules allow
+pub fn find_single_redex_with(
+    ast: &AST,
+    module: Option<usize>,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+    rules: ReductionRules,
+) -> Option<Redex> {
+    ModuleContext::new(ast, module).find_single_redex(ast, expr, lt, rules)
+}
+
+/// Build the contractum of a redex found by find_all_redexes or find_single_redex
+pub fn contract(
+    ast: &AST,
+    module: Option<usize>,
+    redex: Redex,
+    lt: &KnownTypeLabelTable,
+) -> RCPair {
+    contract_with(ast, module, redex, lt, ReductionRules::default())
+}
+
+/// Build the contractum of a redex found with the same rules
+pub fn contract_with(
+    ast: &AST,
+    module: Option<usize>,
+    redex: Redex,
+    lt: &KnownTypeLabelTable,
+    rules: ReductionRules,
+) -> RCPair {
+    ModuleContext::new(ast, module).contract(ast, redex, lt, rules)
+}
+
 pub fn find_all_redex_contraction_pairs(
     ast: &AST,
     module: Option<usize>,
     expr: usize,
     lt: &KnownTypeLabelTable,
 ) -> Vec<RCPair> {
-    let mut pairs: Vec<RCPair> = vec![];
-
-    #[cfg(debug_assertions)]
-    let _exp_str = ast.to_string_sugar(expr, false);
-
-    // Dont need to worry about this as main must be at the end, so everything defined in
-    // the module is defined here
-    let am: HashMap<String, usize> = match module {
-        Some(m) => ast.get_assigns_map(m),
-        None => HashMap::new(),
-    };
-
-    match ast.get(expr).t {
-        ASTNodeType::Literal | ASTNodeType::Abstraction => {}
-        ASTNodeType::Application => {
-            let f = ast.get_func(expr);
-            let x = ast.get_arg(expr);
-
-            if let Some(inbuilt_reduction) = check_for_valid_call(ast, expr, &lt, am) {
-                pairs.push(inbuilt_reduction);
-            }
-
-            pairs.extend(find_all_redex_contraction_pairs(ast, module, f, &lt));
-            pairs.extend(find_all_redex_contraction_pairs(ast, module, x, &lt));
-        }
-        ASTNodeType::Pair => {
-            let left_rcs = find_all_redex_contraction_pairs(ast, module, ast.get_first(expr), lt);
-            let right_rcs = find_all_redex_contraction_pairs(ast, module, ast.get_second(expr), lt);
-            pairs.extend(left_rcs);
-            pairs.extend(right_rcs);
-        }
-        ASTNodeType::Match | ASTNodeType::Identifier => {
-            if let Some(rc) = find_single_redex_contraction_pair(ast, module, expr, lt) {
-                pairs.push(rc);
-            }
-        }
-        _ => panic!("Expected expression"),
-    }
+    find_all_redex_contraction_pairs_with(ast, module, expr, lt, ReductionRules::default())
+}
 
-    pairs
+pub fn find_all_redex_contraction_pairs_with(
+    ast: &AST,
+    module: Option<usize>,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+    rules: ReductionRules,
+) -> Vec<RCPair> {
+    ModuleContext::new(ast, module).find_all_redex_contraction_pairs(ast, expr, lt, rules)
 }
 
 pub fn find_single_redex_contraction_pair(
@@ -241,130 +733,15 @@ pub fn find_single_redex_contraction_pair(
     expr: usize,
     lt: &KnownTypeLabelTable,
 ) -> Option<RCPair> {
-    #[cfg(debug_assertions)]
-    let _exp_str = ast.to_string_sugar(expr, false);
-
-    // Dont need to worry about this as main must be at the end, so everything defined in
-    // the module is defined here
-    let am: HashMap<String, usize> = match module {
-        Some(m) => ast.get_assigns_map(m),
-        None => HashMap::new(),
-    };
-
-    match ast.get(expr).t {
-        ASTNodeType::Literal | ASTNodeType::Abstraction => None,
-        ASTNodeType::Pair => {
-            if let Some(left_rc) =
-                find_single_redex_contraction_pair(ast, module, ast.get_first(expr), lt)
-            {
-                Some(left_rc)
-            } else {
-                find_single_redex_contraction_pair(ast, module, ast.get_second(expr), lt)
-            }
-        }
-        ASTNodeType::Identifier => {
-            let value = ast.get(expr).get_value();
+    find_single_redex_contraction_pair_with(ast, module, expr, lt, ReductionRules::default())
+}
 
-            // It should not be non zero_ary func as otherwise it would be caught by the app case
-            if let Some(label) = lt.get(&value) {
-                return if label.is_inbuilt() {
-                    // If the reduction arity isn't 0 then wth
-                    if let Some(reduction_arity) = label.inbuilt_reduction_arity {
-                        if reduction_arity != 0 {
-                            return None;
-                        }
-                    }
-                    let subst_result = label.call_inbuilt(&ast.get(expr), vec![]);
-                    Some(RCPair {
-                        from: expr,
-                        to: subst_result,
-                        msg_after: format!("Substituted label {}", &value),
-                        msg_before: format!("Substitute label {}", &value),
-                    })
-                } else {
-                    let assign = if let Some(assign) = am.get(&value) {
-                        *assign
-                    } else {
-                        return None;
-                    };
-
-                    let assign_exp = ast.get_assign_exp(assign);
-                    let subst_result = ast.clone_node(assign_exp);
-
-                    Some(RCPair {
-                        from: expr,
-                        to: subst_result,
-                        msg_after: format!("Substituted label {}", &value),
-                        msg_before: format!("Substitute label {}", &value),
-                    })
-                };
-            }
-            None
-        }
-        ASTNodeType::Application => {
-            if let Some(ready_call_reduction) = check_for_valid_call(ast, expr, &lt, am) {
-                Some(ready_call_reduction)
-            } else if let Some(f_rc) =
-                find_single_redex_contraction_pair(ast, module, ast.get_func(expr), lt)
-            {
-                Some(f_rc)
-            } else {
-                find_single_redex_contraction_pair(ast, module, ast.get_arg(expr), lt)
-            }
-        }
-        ASTNodeType::Match => {
-            let unpack_expr = ast.get_match_unpack_pattern(expr);
-            let mut refuted = vec![];
-            for (i, (pattern, pattern_expr)) in ast.get_match_cases(expr).into_iter().enumerate() {
-                let result = pattern_match(ast, unpack_expr, pattern);
-                match result {
-                    PatternMatchResult::Success(bindings) => {
-                        let case_str = ast.to_string_sugar(pattern, false);
-                        let mut pat_expr_cloned = ast.clone_node(pattern_expr);
-                        for (var, replacement) in bindings {
-                            let replacement_appended = pat_expr_cloned.append(ast, replacement);
-                            let usages = pat_expr_cloned
-                                .get_all_free_instances_of_var_in_exp(pat_expr_cloned.root, &var);
-                            for usage in usages {
-                                pat_expr_cloned
-                                    .replace_references_to_node(usage, replacement_appended);
-                            }
-                        }
-                        return Some(RCPair {
-                            from: expr,
-                            to: pat_expr_cloned.clone_node(pat_expr_cloned.root),
-                            msg_after: format!("Matched to pattern {}", case_str),
-                            msg_before: format!("Match to pattern {}", case_str),
-                        });
-                    }
-                    PatternMatchResult::Unknown => {
-                        // return if let Some(rc) = find_single_redex_contraction_pair(ast, module, unpack_expr, lt) {
-                        //     let mut match_cloned = ast.clone_node(expr);
-                        //     let match_expr = match_cloned.get_match_unpack_pattern(match_cloned.root);
-                        //     match_cloned.do_rc_subst(match_cloned.root, &RCPair {
-                        //         from: match_expr,
-                        //         to: rc.to,
-                        //         msg_after: String::new(),
-                        //         msg_before: String::new()
-                        //     });
-                        //     Some(RCPair {
-                        //         from: expr,
-                        //         to: match_cloned,
-                        //         msg_before: rc.msg_before + &format!(", and refute patterns {:?}. ", refuted),
-                        //         msg_after: rc.msg_after + &format!(", and refuted patterns {:?}. ", refuted),
-                        //     })
-                        // } else {
-                        //     None
-                        // }
-                        return find_single_redex_contraction_pair(ast, module, unpack_expr, lt);
-                    }
-                    PatternMatchResult::Refute => {
-                        refuted.push(i);
-                    }
-                }
-            }
-            find_single_redex_contraction_pair(ast, module, unpack_expr, lt)
-        }
-        _ => None,
-    }
+pub fn find_single_redex_contraction_pair_with(
+    ast: &AST,
+    module: Option<usize>,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+    rules: ReductionRules,
+) -> Option<RCPair> {
+    ModuleContext::new(ast, module).find_single_redex_contraction_pair(ast, expr, lt, rules)
 }
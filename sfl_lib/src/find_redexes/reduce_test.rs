@@ -1,3 +1,4 @@
+use crate::test_utils::parse_and_typecheck;
 /// O(n^2) so only use for small things
 #[allow(unused)]
 fn assert_eq_in_any_order<T: PartialEq>(a: &Vec<T>, b: &Vec<T>) {
@@ -13,7 +14,10 @@ fn assert_eq_in_any_order<T: PartialEq>(a: &Vec<T>, b: &Vec<T>) {
 }
 
 use crate::find_redexes::reduce::find_single_redex_contraction_pair;
-use crate::{find_all_redex_contraction_pairs, typecheck, KnownTypeLabelTable, Parser};
+use crate::{
+    contract, find_all_redex_contraction_pairs, find_all_redexes, find_single_redex, typecheck,
+    KnownTypeLabelTable, ModuleContext, Parser, RedexKind, ReductionRules,
+};
 
 #[test]
 fn basic_add_test() {
@@ -122,3 +126,201 @@ fn weird_halt_bug() {
     let rcs = find_all_redex_contraction_pairs(&ast, Some(module), exp, &lt);
     assert_eq!(rcs.len(), 1);
 }
+
+#[test]
+fn erase_annotation() {
+    let program = "main :: Int\nmain = (\\x. x :: Int -> Int) (1 + 2 :: Int)";
+
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(false)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let tm = pr.tm;
+    let module = ast.root;
+
+    let exp = ast.get_assign_exp(ast.get_main(module).unwrap());
+    typecheck(&mut ast, module, &mut lt, &tm).unwrap();
+
+    // Both annotations can be erased, and the addition under one of them reduced
+    let rcs = find_all_redex_contraction_pairs(&ast, Some(module), exp, &lt);
+    assert_eq!(rcs.len(), 3);
+
+    let rc = find_single_redex_contraction_pair(&ast, Some(module), exp, &lt).unwrap();
+    assert_eq!("(\\x. x :: Int -> Int) -> \\x. x", ast.rc_to_str(&rc));
+    assert_eq!(rc.msg_before, "Erase type annotation Int -> Int");
+    let exp = ast.do_rc_subst(exp, &rc);
+
+    let rc = find_single_redex_contraction_pair(&ast, Some(module), exp, &lt).unwrap();
+    assert_eq!(
+        "(\\x. x) (1 + 2 :: Int) -> (1 + 2 :: Int)",
+        ast.rc_to_str(&rc)
+    );
+}
+
+#[test]
+fn redexes_are_found_without_contracting() {
+    let program = "fac :: Int -> Int\nfac n = if (n == 0) 1 (n * fac (n - 1))\nmain :: (Int, Int)\nmain = (fac (1 + 2), match Just 3 {\n  | Nothing -> 0\n  | Just x -> (\\y. y) x\n})";
+
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let tm = pr.tm;
+    let module = ast.root;
+
+    let exp = ast.get_assign_exp(ast.get_main(module).unwrap());
+    typecheck(&mut ast, module, &mut lt, &tm).unwrap();
+
+    let redexes = find_all_redexes(&ast, Some(module), exp, &lt);
+    let kinds: Vec<RedexKind> = redexes.iter().map(|redex| redex.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            RedexKind::Function,
+            RedexKind::Label,
+            RedexKind::Inbuilt,
+            RedexKind::Match(1)
+        ]
+    );
+
+    // Contracting each is the same as finding them with their contracta
+    let rcs = find_all_redex_contraction_pairs(&ast, Some(module), exp, &lt);
+    assert_eq!(rcs.len(), redexes.len());
+    for (redex, rc) in redexes.iter().zip(&rcs) {
+        let contracted = contract(&ast, Some(module), *redex, &lt);
+        assert_eq!(ast.rc_to_str(&contracted), ast.rc_to_str(rc));
+        assert_eq!(contracted.msg_before, rc.msg_before);
+    }
+
+    let redex = find_single_redex(&ast, Some(module), exp, &lt).unwrap();
+    assert_eq!(redex, redexes[0]);
+    assert_eq!(
+        contract(&ast, Some(module), redex, &lt).msg_before,
+        "Apply function fac to (1 + 2)"
+    );
+    assert_eq!(
+        ast.rc_to_str(&contract(&ast, Some(module), redexes[3], &lt)),
+        "match (Just 3) {\n  | Nothing -> 0\n  | Just x -> (\\y. y) x\n} -> (\\y. y) 3"
+    );
+}
+
+#[test]
+fn module_context_is_reused_across_steps() {
+    let program =
+        "fac :: Int -> Int\nfac n = if (n == 0) 1 (n * fac (n - 1))\nmain :: Int\nmain = fac 3";
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let module = ast.root;
+
+    // Steps only change main's expression, so the assignments found at the start stay right
+    let ctx = ModuleContext::new(&ast, Some(module));
+    let rules = ReductionRules::default();
+    let mut exp = ast.get_assign_exp(ast.get_main(module).unwrap());
+    while let Some(rc) = ctx.find_single_redex_contraction_pair(&ast, exp, &lt, rules) {
+        let fresh = find_single_redex_contraction_pair(&ast, Some(module), exp, &lt).unwrap();
+        assert_eq!(ast.rc_to_str(&rc), ast.rc_to_str(&fresh));
+        assert_eq!(
+            ctx.find_all_redexes(&ast, exp, &lt, rules),
+            find_all_redexes(&ast, Some(module), exp, &lt)
+        );
+        exp = ast.do_rc_subst(exp, &rc);
+    }
+    assert_eq!(ast.to_string_sugar(exp, false), "6");
+}
+
+/// main's normal form under normal order with the rules, and the message of each step
+fn normalise(program: &str, rules: crate::ReductionRules) -> (String, Vec<String>) {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let module = ast.root;
+
+    let config = crate::EvaluationConfig {
+        rules,
+        ..Default::default()
+    };
+    let reduction = crate::reduce_within_budget(&mut ast, &lt, &mut crate::NormalOrder, config);
+    let main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    let msgs = reduction.steps.into_iter().map(|s| s.msg_before).collect();
+    (ast.to_string_sugar(main_expr, false), msgs)
+}
+
+#[test]
+fn reduces_under_lambdas_only_when_asked() {
+    let program = "main :: Int -> Int\nmain = \\x. (\\y. y) x";
+    let (term, msgs) = normalise(program, Default::default());
+    assert_eq!(term, "\\x. (\\y. y) x");
+    assert!(msgs.is_empty());
+
+    let under_lambdas = crate::ReductionRules {
+        under_lambdas: true,
+        ..Default::default()
+    };
+    let (term, msgs) = normalise(program, under_lambdas);
+    assert_eq!(term, "\\x. x");
+    assert_eq!(msgs, vec!["Apply abstraction to x under a binder"]);
+
+    let eta = crate::ReductionRules {
+        eta: true,
+        ..Default::default()
+    };
+    let (term, msgs) = normalise(program, eta);
+    assert_eq!(term, "\\y. y");
+    assert_eq!(msgs, vec!["Eta-reduce abstraction over x"]);
+}
+
+#[test]
+fn eta_needs_the_variable_not_free() {
+    let program = "f :: Int -> Int -> Int\nf a b = a + b\nmain :: Int -> Int\nmain = \\x. f x x";
+    let rules = crate::ReductionRules {
+        eta: true,
+        ..Default::default()
+    };
+    let (term, _) = normalise(program, rules);
+    assert_eq!(term, "\\x. f x x");
+}
+
+#[test]
+fn reducing_under_lambdas_avoids_capture() {
+    let program = "k :: a -> b -> a\nk x y = x\nmain :: Int -> Int -> Int\nmain = \\y. k y";
+    let rules = crate::ReductionRules {
+        under_lambdas: true,
+        ..Default::default()
+    };
+    let (term, msgs) = normalise(program, rules);
+    assert_eq!(term, "\\y. \\y1. y");
+    assert_eq!(msgs, vec!["Apply function k to y under a binder"]);
+}
+
+#[test]
+fn opaque_labels_are_evaluated_in_one_step() {
+    let program = "main :: Int\nmain = sum (map (\\x. x * x) (range 1 4))";
+    let (term, msgs) = normalise(program, Default::default());
+    assert_eq!(term, "14");
+    assert!(msgs.len() > 10);
+
+    let (term, msgs) = normalise(
+        &format!("{{-# OPAQUE sum #-}}\n{}", program),
+        Default::default(),
+    );
+    assert_eq!(term, "14");
+    assert_eq!(msgs, vec!["Evaluate sum (map (\\x. x * x) (range 1 4))"]);
+
+    let (term, msgs) = normalise(
+        &format!("{{-# OPAQUE map #-}}\n{}", program),
+        Default::default(),
+    );
+    assert_eq!(term, "14");
+    assert!(msgs.contains(&"Evaluate map (\\x. x * x) (range 1 4)".to_string()));
+    assert!(!msgs.iter().any(|msg| msg.contains("function map")));
+}
+
+#[test]
+fn infinite_opaque_values_are_stepped_into() {
+    let program = "{-# OPAQUE repeat #-}\nmain :: List Int\nmain = take 3 (repeat 1)";
+    let (term, msgs) = normalise(program, Default::default());
+    assert_eq!(term, "Cons 1 (Cons 1 (Cons 1 Nil))");
+    assert_eq!(
+        msgs[1],
+        "Apply function repeat to 1 (too long to evaluate at once)"
+    );
+}
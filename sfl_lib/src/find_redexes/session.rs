@@ -0,0 +1,244 @@
+use super::{EvaluationStrategy, ModuleContext, RCPair, ReductionRules, TraceStep};
+use crate::{KnownTypeLabelTable, AST};
+
+// Every state of a session lives in the same AST. A step copies only the nodes above the redex,
+// so a state shares everything else with the one it came from, and the module and prelude are
+// stored once for all of them.
+
+/// A state reached in a ReductionSession, and how it was reached
+struct State {
+    /// Main's expression in this state
+    expr: usize,
+    parent: Option<usize>,
+    /// The step from the parent to this state
+    step: Option<TraceStep>,
+    children: Vec<usize>,
+    /// The child redo goes to: the one last stepped to, or undone from
+    redo: Option<usize>,
+}
+
+/// A reduction explored by hand, which keeps the tree of every state reached. States are
+/// numbered in the order they were reached, starting with 0 for the program as written.
+/// Stepping from a state that has already been stepped from forks a new branch beside the
+/// others, and undo, redo and jump move between states without losing any of them
+pub struct ReductionSession {
+    ast: AST,
+    /// The module's assignments are never renumbered, as the AST is never compacted
+    ctx: ModuleContext,
+    lt: KnownTypeLabelTable,
+    shares: bool,
+    rules: ReductionRules,
+    states: Vec<State>,
+    current: usize,
+}
+
+impl ReductionSession {
+    /// A session starting at main. If shares, steps keep arguments shared as
+    /// AST::do_rc_subst_shared does, for strategies that share them
+    pub fn new(ast: AST, lt: KnownTypeLabelTable, shares: bool) -> Result<Self, String> {
+        let main = ast.get_main(ast.root).ok_or("Main not found")?;
+        let expr = ast.get_assign_exp(main);
+        Ok(Self {
+            ctx: ModuleContext::new(&ast, Some(ast.root)),
+            ast,
+            lt,
+            shares,
+            rules: ReductionRules::default(),
+            states: vec![State {
+                expr,
+                parent: None,
+                step: None,
+                children: vec![],
+                redo: None,
+            }],
+            current: 0,
+        })
+    }
+
+    /// The AST all the states are in. Main's assignment still holds the first state, so use
+    /// expr for the current one
+    pub fn ast(&self) -> &AST {
+        &self.ast
+    }
+
+    pub fn lt(&self) -> &KnownTypeLabelTable {
+        &self.lt
+    }
+
+    /// Set the rules redexes are found with from now on. States already reached are kept
+    pub fn set_rules(&mut self, rules: ReductionRules) {
+        self.rules = rules;
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Main's expression in the current state
+    pub fn expr(&self) -> usize {
+        self.states[self.current].expr
+    }
+
+    /// Main in the current state, printed with thunks bound once if the session shares
+    pub fn term(&self) -> String {
+        self.term_at(self.current)
+    }
+
+    pub fn term_at(&self, state: usize) -> String {
+        let expr = self.states[state].expr;
+        if self.shares {
+            self.ast.to_string_shared(expr)
+        } else {
+            self.ast.to_string_sugar(expr, false)
+        }
+    }
+
+    /// The number of states reached
+    pub fn state_count(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn parent(&self, state: usize) -> Option<usize> {
+        self.states[state].parent
+    }
+
+    /// The states stepped to from state, in the order they were first reached
+    pub fn children(&self, state: usize) -> &[usize] {
+        &self.states[state].children
+    }
+
+    /// The step that reached state, which the first state doesn't have
+    pub fn step_to(&self, state: usize) -> Option<&TraceStep> {
+        self.states[state].step.as_ref()
+    }
+
+    /// The states from the first to the current one
+    pub fn history(&self) -> Vec<usize> {
+        let mut history = vec![self.current];
+        while let Some(parent) = self.states[history[history.len() - 1]].parent {
+            history.push(parent);
+        }
+        history.reverse();
+        history
+    }
+
+    /// Every redex in the current state, for the user to pick from
+    pub fn redexes(&self) -> Vec<RCPair> {
+        self.ctx
+            .find_all_redex_contraction_pairs(&self.ast, self.expr(), &self.lt, self.rules)
+    }
+
+    /// Contract rc, one of the redexes in the current state, and move to the state it gives.
+    /// If that step was already taken from here, the state it reached is moved to rather than
+    /// reached again. Gives the state moved to
+    pub fn step(&mut self, rc: &RCPair) -> usize {
+        let expr = self.expr();
+        let location = self
+            .ast
+            .get_path(expr, rc.from)
+            .expect("the redex is not in the current state");
+
+        let existing = self.states[self.current].children.iter().find(|child| {
+            let step = self.states[**child].step.as_ref().unwrap();
+            step.location == location && step.kind == rc.kind
+        });
+        if let Some(&child) = existing {
+            self.states[self.current].redo = Some(child);
+            self.current = child;
+            return child;
+        }
+
+        let redex = self.ast.to_string_sugar(rc.from, false);
+        let new_expr = if self.shares {
+            self.ast.do_rc_subst_shared_persistent(expr, rc)
+        } else {
+            self.ast.do_rc_subst_persistent(expr, rc)
+        };
+
+        let state = self.states.len();
+        self.states.push(State {
+            expr: new_expr,
+            parent: Some(self.current),
+            step: None,
+            children: vec![],
+            redo: None,
+        });
+        self.states[state].step = Some(TraceStep {
+            location,
+            kind: rc.kind,
+            msg_before: rc.msg_before.clone(),
+            msg_after: rc.msg_after.clone(),
+            redex,
+            contractum: rc.to.to_string_sugar(rc.to.root, false),
+            term: self.term_at(state),
+        });
+        self.states[self.current].children.push(state);
+        self.states[self.current].redo = Some(state);
+        self.current = state;
+        state
+    }
+
+    /// Take the step strategy picks in the current state, if it isn't in normal form
+    pub fn step_with(&mut self, strategy: &mut dyn EvaluationStrategy) -> Option<usize> {
+        let rc = strategy.next_redex_in(&self.ast, &self.ctx, self.expr(), &self.lt, self.rules)?;
+        Some(self.step(&rc))
+    }
+
+    /// Move back to the state before the current one. Gives whether there was one
+    pub fn undo(&mut self) -> bool {
+        match self.states[self.current].parent {
+            Some(parent) => {
+                self.states[parent].redo = Some(self.current);
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move forward to the state last undone from, or last stepped to, from the current one.
+    /// Gives whether there was one
+    pub fn redo(&mut self) -> bool {
+        match self.states[self.current].redo {
+            Some(child) => {
+                self.current = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move to any state reached so far, so that stepping from it forks a new branch. Redo
+    /// leads back towards the state jumped from if it is below state, as if it had been undone
+    /// to. Otherwise, redo leads down to state from where the two branches meet, as if it had
+    /// been stepped to
+    pub fn jump(&mut self, state: usize) -> Result<(), String> {
+        if state >= self.states.len() {
+            return Err(format!("No state {}", state));
+        }
+        let mut path = vec![state];
+        while let Some(parent) = self.states[path[path.len() - 1]].parent {
+            path.push(parent);
+        }
+
+        // The first state is on every path, so undoing stops there at the latest
+        while !path.contains(&self.current) {
+            self.undo();
+        }
+        let meet = path.iter().position(|s| *s == self.current).unwrap();
+        for i in (1..=meet).rev() {
+            self.states[path[i]].redo = Some(path[i - 1]);
+        }
+        self.current = state;
+        Ok(())
+    }
+
+    /// A standalone AST of the program in the current state, with main set to it and the other
+    /// states left out
+    pub fn current_ast(&self) -> AST {
+        let mut ast = self.ast.clone();
+        ast.replace_references_to_node(self.states[0].expr, self.expr());
+        ast.compact();
+        ast
+    }
+}
@@ -0,0 +1,135 @@
+use crate::test_utils::parse_and_typecheck;
+use crate::{
+    reduce_within_budget, CallByNeed, EvaluationConfig, EvaluationStrategy, NormalOrder,
+    ReductionSession,
+};
+
+fn session(program: &str, shares: bool) -> ReductionSession {
+    let (ast, lt) = parse_and_typecheck(program);
+    ReductionSession::new(ast, lt, shares).unwrap()
+}
+
+#[test]
+fn session_steps_like_reduction_in_place() {
+    let program = "main :: Int\nmain = sum (map (\\x. x * x) (range 1 4))";
+    let strategies: [fn() -> Box<dyn EvaluationStrategy>; 2] =
+        [|| Box::new(NormalOrder), || Box::new(CallByNeed)];
+    for strategy in strategies {
+        let mut strategy = strategy();
+        let mut s = session(program, strategy.shares_arguments());
+        let mut terms = vec![];
+        while s.step_with(&mut *strategy).is_some() {
+            terms.push(s.term());
+        }
+
+        let mut ast = s.ast().clone();
+        let reduction = reduce_within_budget(
+            &mut ast,
+            s.lt(),
+            &mut *strategy,
+            EvaluationConfig::default(),
+        );
+        let expected: Vec<String> = reduction.steps.into_iter().map(|step| step.term).collect();
+        assert_eq!(terms, expected);
+        assert_eq!(s.term(), "14");
+    }
+}
+
+#[test]
+fn undo_redo_and_jump() {
+    let mut s = session("main :: Int\nmain = (1 + 2) * (3 + 4)", false);
+    for _ in 0..3 {
+        s.step_with(&mut NormalOrder);
+    }
+    assert_eq!(s.term(), "21");
+    assert!(s.step_with(&mut NormalOrder).is_none());
+
+    assert!(s.undo());
+    assert!(s.undo());
+    assert_eq!(s.term(), "3 * (3 + 4)");
+    assert!(s.redo());
+    assert_eq!(s.term(), "3 * 7");
+    assert_eq!(s.history(), vec![0, 1, 2]);
+
+    s.jump(0).unwrap();
+    assert!(!s.undo());
+    assert_eq!(s.term(), "(1 + 2) * (3 + 4)");
+    // Redo goes back down the branch jumped from
+    assert!(s.redo());
+    assert!(s.redo());
+    assert!(s.redo());
+    assert_eq!(s.term(), "21");
+    assert!(!s.redo());
+    assert!(s.jump(10).is_err());
+}
+
+#[test]
+fn stepping_from_an_earlier_state_forks() {
+    let mut s = session("main :: Int\nmain = (1 + 2) * (3 + 4)", false);
+    let redexes = s.redexes();
+    assert_eq!(redexes.len(), 2);
+
+    let left = s.step(&redexes[0]);
+    assert_eq!(s.term(), "3 * (3 + 4)");
+    s.undo();
+    let right = s.step(&redexes[1]);
+    assert_eq!(s.term(), "(1 + 2) * 7");
+    assert_eq!(s.children(0), &[left, right]);
+    assert_eq!(s.parent(right), Some(0));
+    assert_eq!(s.step_to(right).unwrap().location, vec![1]);
+
+    // The same step again goes to the state it reached before
+    s.undo();
+    assert_eq!(s.step(&redexes[0]), left);
+    assert_eq!(s.state_count(), 3);
+
+    // Earlier states are left as they were
+    assert_eq!(s.term_at(0), "(1 + 2) * (3 + 4)");
+    assert_eq!(s.term_at(right), "(1 + 2) * 7");
+}
+
+#[test]
+fn jump_to_another_branch_redoes_along_it() {
+    let mut s = session("main :: Int\nmain = (1 + 2) * (3 + 4)", false);
+    let redexes = s.redexes();
+    let left = s.step(&redexes[0]);
+    s.step_with(&mut NormalOrder);
+    s.jump(0).unwrap();
+    let right = s.step(&redexes[1]);
+    let below_right = s.step_with(&mut NormalOrder).unwrap();
+
+    s.jump(left).unwrap();
+    assert_eq!(s.term(), "3 * (3 + 4)");
+    // Going back up and down again stays on the branch jumped to
+    assert!(s.undo());
+    assert!(s.redo());
+    assert_eq!(s.current(), left);
+
+    s.jump(below_right).unwrap();
+    assert_eq!(s.term(), "3 * 7");
+    s.undo();
+    s.undo();
+    assert_eq!(s.current(), 0);
+    assert!(s.redo());
+    assert_eq!(s.current(), right);
+    assert!(s.redo());
+    assert_eq!(s.current(), below_right);
+}
+
+#[test]
+fn states_share_structure() {
+    let mut s = session("main :: Int\nmain = sum (range 1 20)", false);
+    let start = s.ast().node_count();
+    let mut sizes = 0;
+    for _ in 0..100 {
+        s.step_with(&mut NormalOrder);
+        sizes += s.ast().term_size(s.expr());
+    }
+    // Copying every state whole would add every node of each
+    assert!(s.ast().node_count() - start < sizes / 2);
+
+    let ast = s.current_ast();
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    assert_eq!(ast.to_string_sugar(main_expr, false), s.term());
+    assert!(ast.node_count() < s.ast().node_count());
+}
@@ -0,0 +1,260 @@
+use super::*;
+use crate::KnownTypeLabelTable;
+use std::collections::HashSet;
+
+/// The names strategies can be selected by, with random also taking a seed as random:<seed>
+pub const STRATEGY_NAMES: [&str; 5] = [
+    "normal",
+    "need",
+    "applicative",
+    "innermost-rightmost",
+    "random",
+];
+
+/// How to pick the next redex to contract in an expression
+pub trait EvaluationStrategy {
+    /// The name the strategy is selected by
+    fn name(&self) -> &'static str;
+
+    /// The redex to contract next in expr, or None if it is in normal form
+    fn next_redex(
+        &mut self,
+        ast: &AST,
+        module: Option<usize>,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+    ) -> Option<RCPair> {
+        self.next_redex_with(ast, module, expr, lt, ReductionRules::default())
+    }
+
+    /// The redex to contract next in expr, or None if it is in normal form, also contracting
+    /// the redexes the rules allow
+    fn next_redex_with(
+        &mut self,
+        ast: &AST,
+        module: Option<usize>,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<RCPair> {
+        self.next_redex_in(ast, &ModuleContext::new(ast, module), expr, lt, rules)
+    }
+
+    /// next_redex_with, in a module whose context has already been found, so that a reduction
+    /// can find it once for all of its steps
+    fn next_redex_in(
+        &mut self,
+        ast: &AST,
+        ctx: &ModuleContext,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<RCPair>;
+
+    /// Whether contracting should share arguments between their uses, with
+    /// AST::do_rc_subst_shared, rather than copying them
+    fn shares_arguments(&self) -> bool {
+        false
+    }
+
+    /// Whether the redex picked only depends on the expression, so that a reduction that comes
+    /// back to an expression it has been at before loops forever
+    fn depends_only_on_expression(&self) -> bool {
+        true
+    }
+}
+
+/// Leftmost outermost, which is lazy: arguments are only reduced once they are needed
+pub struct NormalOrder;
+
+/// Normal order, but each argument is a thunk shared by all of its uses, so it is evaluated at
+/// most once
+pub struct CallByNeed;
+
+/// Leftmost innermost, or call-by-value: arguments are reduced before the function is applied,
+/// even if they are never used
+pub struct ApplicativeOrder;
+
+/// Rightmost innermost, so the last argument is reduced first
+pub struct InnermostRightmost;
+
+/// Any redex, picked at random. The same seed always picks the same redexes
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// SplitMix64, which is plenty for picking redexes and keeps rand out of the library
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// The strategy with the given name, from STRATEGY_NAMES
+pub fn strategy_from_name(name: &str) -> Result<Box<dyn EvaluationStrategy>, String> {
+    match name.to_lowercase().as_str() {
+        "normal" => Ok(Box::new(NormalOrder)),
+        "need" => Ok(Box::new(CallByNeed)),
+        "applicative" => Ok(Box::new(ApplicativeOrder)),
+        "innermost-rightmost" => Ok(Box::new(InnermostRightmost)),
+        "random" => Ok(Box::new(SeededRandom::new(0))),
+        s => match s.strip_prefix("random:").map(|seed| seed.parse()) {
+            Some(Ok(seed)) => Ok(Box::new(SeededRandom::new(seed))),
+            Some(Err(_)) => Err(format!("Invalid seed for random strategy: {}", name)),
+            None => Err(format!(
+                "Unknown evaluation strategy {}, expected one of {}",
+                name,
+                STRATEGY_NAMES.join(", ")
+            )),
+        },
+    }
+}
+
+/// The redexes in expr with no others inside them, from left to right. Gives whether expr
+/// contains a redex
+fn innermost_redexes(
+    ast: &AST,
+    expr: usize,
+    redexes: &HashSet<usize>,
+    out: &mut Vec<usize>,
+) -> bool {
+    let mut contains_redex = false;
+    for &child in ast.get_children(expr) {
+        contains_redex |= innermost_redexes(ast, child, redexes, out);
+    }
+
+    if !contains_redex && redexes.contains(&expr) {
+        out.push(expr);
+    }
+    contains_redex || redexes.contains(&expr)
+}
+
+/// The innermost redex that comes first, or last if rightmost
+fn innermost(
+    ast: &AST,
+    ctx: &ModuleContext,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+    rules: ReductionRules,
+    rightmost: bool,
+) -> Option<RCPair> {
+    let found = ctx.find_all_redexes(ast, expr, lt, rules);
+    let redexes: HashSet<usize> = found.iter().map(|redex| redex.from).collect();
+
+    let mut order = vec![];
+    innermost_redexes(ast, expr, &redexes, &mut order);
+    let from = if rightmost {
+        *order.last()?
+    } else {
+        *order.first()?
+    };
+
+    let redex = found.into_iter().find(|redex| redex.from == from)?;
+    Some(ctx.contract(ast, redex, lt, rules))
+}
+
+impl EvaluationStrategy for NormalOrder {
+    fn name(&self) -> &'static str {
+        "normal"
+    }
+
+    fn next_redex_in(
+        &mut self,
+        ast: &AST,
+        ctx: &ModuleContext,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<RCPair> {
+        ctx.find_single_redex_contraction_pair(ast, expr, lt, rules)
+    }
+}
+
+impl EvaluationStrategy for CallByNeed {
+    fn name(&self) -> &'static str {
+        "need"
+    }
+
+    fn next_redex_in(
+        &mut self,
+        ast: &AST,
+        ctx: &ModuleContext,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<RCPair> {
+        ctx.find_single_redex_contraction_pair(ast, expr, lt, rules)
+    }
+
+    fn shares_arguments(&self) -> bool {
+        true
+    }
+}
+
+impl EvaluationStrategy for ApplicativeOrder {
+    fn name(&self) -> &'static str {
+        "applicative"
+    }
+
+    fn next_redex_in(
+        &mut self,
+        ast: &AST,
+        ctx: &ModuleContext,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<RCPair> {
+        innermost(ast, ctx, expr, lt, rules, false)
+    }
+}
+
+impl EvaluationStrategy for InnermostRightmost {
+    fn name(&self) -> &'static str {
+        "innermost-rightmost"
+    }
+
+    fn next_redex_in(
+        &mut self,
+        ast: &AST,
+        ctx: &ModuleContext,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<RCPair> {
+        innermost(ast, ctx, expr, lt, rules, true)
+    }
+}
+
+impl EvaluationStrategy for SeededRandom {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn next_redex_in(
+        &mut self,
+        ast: &AST,
+        ctx: &ModuleContext,
+        expr: usize,
+        lt: &KnownTypeLabelTable,
+        rules: ReductionRules,
+    ) -> Option<RCPair> {
+        let redexes = ctx.find_all_redexes(ast, expr, lt, rules);
+        if redexes.is_empty() {
+            return None;
+        }
+        let i = (self.next_u64() % redexes.len() as u64) as usize;
+        Some(ctx.contract(ast, redexes[i], lt, rules))
+    }
+
+    fn depends_only_on_expression(&self) -> bool {
+        false
+    }
+}
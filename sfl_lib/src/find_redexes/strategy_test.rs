@@ -0,0 +1,118 @@
+use crate::test_utils::parse_and_typecheck;
+use crate::{strategy_from_name, EvaluationStrategy, STRATEGY_NAMES};
+
+/// The first redex strategy picks in main
+fn first_redex(program: &str, strategy: &str) -> String {
+    let (ast, lt) = parse_and_typecheck(program);
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let rc = strategy_from_name(strategy)
+        .unwrap()
+        .next_redex(&ast, Some(ast.root), main_expr, &lt)
+        .unwrap();
+    ast.to_string_sugar(rc.from, false)
+}
+
+/// Main after at most max_steps of strategy
+fn run(program: &str, strategy: &mut dyn EvaluationStrategy, max_steps: usize) -> String {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let root = ast.root;
+    for _ in 0..max_steps {
+        let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+        match strategy.next_redex(&ast, Some(root), main_expr, &lt) {
+            Some(rc) if strategy.shares_arguments() => {
+                ast.do_rc_subst_shared(main_expr, &rc);
+            }
+            Some(rc) => {
+                ast.do_rc_subst(main_expr, &rc);
+            }
+            None => break,
+        }
+    }
+    ast.to_string_sugar(ast.get_assign_exp(ast.get_main(root).unwrap()), false)
+}
+
+#[test]
+fn strategy_names() {
+    for name in STRATEGY_NAMES {
+        assert_eq!(strategy_from_name(name).unwrap().name(), name);
+    }
+    assert_eq!(strategy_from_name("random:42").unwrap().name(), "random");
+    assert!(strategy_from_name("random:x").is_err());
+    assert!(strategy_from_name("eager").is_err());
+}
+
+#[test]
+fn strategies_pick_different_redexes() {
+    let program = "main :: Int\nmain = (\\x. x * 2) ((1 + 2) * (3 + 4))";
+
+    assert_eq!(
+        first_redex(program, "normal"),
+        "(\\x. x * 2) ((1 + 2) * (3 + 4))"
+    );
+    assert_eq!(first_redex(program, "applicative"), "1 + 2");
+    assert_eq!(first_redex(program, "innermost-rightmost"), "3 + 4");
+}
+
+#[test]
+fn strategies_reach_the_same_value() {
+    let program = "main :: Int\nmain = (\\x. x * 2) ((1 + 2) * (3 + 4))";
+
+    for name in STRATEGY_NAMES {
+        let mut strategy = strategy_from_name(name).unwrap();
+        assert_eq!(run(program, strategy.as_mut(), 100), "42", "{}", name);
+    }
+}
+
+#[test]
+fn applicative_order_evaluates_unused_arguments() {
+    let program = "loop :: Int -> Int\nloop x = loop x\nmain :: Int\nmain = (\\y. 1) (loop 0)";
+
+    assert_eq!(
+        run(program, strategy_from_name("normal").unwrap().as_mut(), 100),
+        "1"
+    );
+    assert_ne!(
+        run(
+            program,
+            strategy_from_name("applicative").unwrap().as_mut(),
+            100
+        ),
+        "1"
+    );
+}
+
+#[test]
+fn seeded_random_is_reproducible() {
+    let program = "main :: Int\nmain = (1 + 2) + (3 + 4) + (5 + 6) + (7 + 8)";
+
+    let picks = |seed: u64| {
+        let (mut ast, lt) = parse_and_typecheck(program);
+        let root = ast.root;
+        let mut strategy = strategy_from_name(&format!("random:{}", seed)).unwrap();
+        let mut picks = vec![];
+        loop {
+            let main_expr = ast.get_assign_exp(ast.get_main(root).unwrap());
+            match strategy.next_redex(&ast, Some(root), main_expr, &lt) {
+                Some(rc) => {
+                    picks.push(ast.to_string_sugar(rc.from, false));
+                    ast.do_rc_subst(main_expr, &rc);
+                }
+                None => return picks,
+            }
+        }
+    };
+
+    assert_eq!(picks(7), picks(7));
+    assert_eq!(picks(7).len(), 7);
+}
+
+#[test]
+fn random_order_reduces_inside_recursive_matches() {
+    let program =
+        "square :: Int -> Int\nsquare x = x * x\nmain :: Int\nmain = sum $ map square $ range 1 5";
+
+    for seed in 0..10 {
+        let mut strategy = strategy_from_name(&format!("random:{}", seed)).unwrap();
+        assert_eq!(run(program, strategy.as_mut(), 1000), "30", "{}", seed);
+    }
+}
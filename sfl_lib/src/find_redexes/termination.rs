@@ -0,0 +1,111 @@
+use crate::AST;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+/// The size budget used when none is given: the number of nodes main can grow to before its
+/// reduction is taken not to terminate. Every step walks main, so this keeps a reduction that
+/// grows forever, like repeat 1, to a few hundred steps
+pub const DEFAULT_SIZE_BUDGET: usize = 1_000;
+
+/// The number of steps a reduction takes when no limit is given, which stops the ones that run
+/// for long without growing or repeating a state
+pub const DEFAULT_STEP_LIMIT: usize = 20_000;
+
+/// Why a reduction was found not to terminate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NonTermination {
+    /// The term after step is alpha-equivalent to the one after an earlier step, so the steps
+    /// in between repeat forever
+    Loop { step: usize, earlier: usize },
+    /// The term after step has more nodes than the budget allows
+    Growth {
+        step: usize,
+        size: usize,
+        budget: usize,
+    },
+}
+
+impl Display for NonTermination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NonTermination::Loop { step, earlier } => write!(
+                f,
+                "this reduction loops (state at step {} = step {})",
+                step, earlier
+            ),
+            NonTermination::Growth { step, size, budget } => write!(
+                f,
+                "this reduction grows without bound (state at step {} has {} nodes, over the budget of {})",
+                step, size, budget
+            ),
+        }
+    }
+}
+
+/// Watches the states of a reduction for one that repeats, or one that is too big. A repeated
+/// state only means a loop if the strategy always picks the same redex in the same term, so
+/// loops are only looked for if it does.
+///
+/// Only the alpha_hash of each state is kept. A state is copied the first time its hash comes up
+/// again, so a loop is found on its second time round, when a state is alpha-equivalent to one
+/// of these copies
+pub struct TerminationChecker {
+    /// The copies of states whose hash had been seen before, as main is changed in place by each
+    /// step
+    states: AST,
+    /// The alpha_hash of each state seen
+    hashes: HashSet<u64>,
+    /// The copies of the states with each alpha_hash, and the step each was seen at. States
+    /// with the same hash are only the same if they are alpha-equivalent too
+    seen: HashMap<u64, Vec<(usize, usize)>>,
+    step: usize,
+    size_budget: usize,
+    detect_loops: bool,
+}
+
+impl TerminationChecker {
+    pub fn new(size_budget: usize, detect_loops: bool) -> Self {
+        Self {
+            states: AST::new(),
+            hashes: HashSet::new(),
+            seen: HashMap::new(),
+            step: 0,
+            size_budget,
+            detect_loops,
+        }
+    }
+
+    /// Check the state of main, expr, after the next step, starting with the state before any
+    /// steps as step 0
+    pub fn check(&mut self, ast: &AST, expr: usize) -> Result<(), NonTermination> {
+        let step = self.step;
+        self.step += 1;
+
+        let size = ast.term_size(expr);
+        if size > self.size_budget {
+            return Err(NonTermination::Growth {
+                step,
+                size,
+                budget: self.size_budget,
+            });
+        }
+
+        if self.detect_loops {
+            let hash = ast.alpha_hash(expr);
+            if self.hashes.insert(hash) {
+                return Ok(());
+            }
+
+            let copy = self.states.append(ast, expr);
+            let same_hash = self.seen.entry(hash).or_default();
+            let earlier = same_hash
+                .iter()
+                .find(|(state, _)| self.states.alpha_eq(*state, copy));
+            if let Some(&(_, earlier)) = earlier {
+                return Err(NonTermination::Loop { step, earlier });
+            }
+            same_hash.push((copy, step));
+        }
+        Ok(())
+    }
+}
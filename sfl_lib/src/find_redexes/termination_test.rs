@@ -0,0 +1,115 @@
+use crate::test_utils::parse_and_typecheck;
+use crate::{
+    find_single_redex_contraction_pair, NonTermination, Parser, TerminationChecker,
+    DEFAULT_SIZE_BUDGET,
+};
+
+/// Reduce main lazily, checking each state, until it is in normal form or found not to terminate
+fn reduce(program: &str, size_budget: usize) -> Result<String, NonTermination> {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let module = ast.root;
+
+    let mut checker = TerminationChecker::new(size_budget, true);
+    let mut main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    checker.check(&ast, main_expr)?;
+    while let Some(rc) = find_single_redex_contraction_pair(&ast, Some(module), main_expr, &lt) {
+        ast.do_rc_subst(main_expr, &rc);
+        main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+        checker.check(&ast, main_expr)?;
+    }
+    Ok(ast.to_string_sugar(main_expr, false))
+}
+
+#[test]
+fn repeated_state_is_a_loop() {
+    let program = "loop :: Int\nloop = loop\nmain :: Int\nmain = loop";
+    let e = reduce(program, DEFAULT_SIZE_BUDGET).unwrap_err();
+    assert_eq!(
+        e,
+        NonTermination::Loop {
+            step: 2,
+            earlier: 1
+        }
+    );
+    assert_eq!(
+        e.to_string(),
+        "this reduction loops (state at step 2 = step 1)"
+    );
+
+    let program = "f :: Int -> Int\nf x = (\\y. f y) x\nmain :: Int\nmain = f 1";
+    assert_eq!(
+        reduce(program, DEFAULT_SIZE_BUDGET),
+        Err(NonTermination::Loop {
+            step: 4,
+            earlier: 2
+        })
+    );
+}
+
+#[test]
+fn states_are_kept_after_main_changes() {
+    let parse = |program: &str| {
+        let ast = Parser::from_string(program.to_string())
+            .parse_module(false)
+            .unwrap()
+            .ast;
+        let expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+        (ast, expr)
+    };
+
+    // A state is copied when its hash comes up again, and later states are compared with the
+    // copy, not where it was in the AST
+    let mut checker = TerminationChecker::new(DEFAULT_SIZE_BUDGET, true);
+    for (step, program) in ["main = \\x. x 1", "main = \\x. x 2", "main = \\y. y 1"]
+        .iter()
+        .enumerate()
+    {
+        let (ast, expr) = parse(program);
+        assert_eq!(checker.check(&ast, expr), Ok(()), "step {}", step);
+    }
+    let (ast, expr) = parse("main = \\z. z 1");
+    assert_eq!(
+        checker.check(&ast, expr),
+        Err(NonTermination::Loop {
+            step: 3,
+            earlier: 2
+        })
+    );
+}
+
+#[test]
+fn growth_past_the_budget() {
+    let program = "main :: List Int\nmain = repeat 1";
+    match reduce(program, 100) {
+        Err(NonTermination::Growth { size, budget, .. }) => {
+            assert!(size > 100);
+            assert_eq!(budget, 100);
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn terminating_reduction_passes() {
+    let program = "main :: Int\nmain = sum (range 1 5)";
+    assert_eq!(reduce(program, DEFAULT_SIZE_BUDGET), Ok("10".to_string()));
+}
+
+#[test]
+fn alpha_equivalent_expressions_hash_the_same() {
+    let program = "a = \\x. x\nb = \\y. y\nc = \\x y. x\nd = \\z w. w\ne = \\(p, q). match p {\n  | Just v -> v\n  | Nothing -> q\n}\nf = \\(r, s). match r {\n  | Just t -> t\n  | Nothing -> s\n}\ng = \\u. u a\nh = \\u2. u2 b\nmain = 1";
+    let ast = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap()
+        .ast;
+    let hash = |name: &str| {
+        let assign = ast.get_assign_to(ast.root, name.to_string()).unwrap();
+        ast.alpha_hash(ast.get_assign_exp(assign))
+    };
+
+    assert_eq!(hash("a"), hash("b"));
+    assert_ne!(hash("c"), hash("d"));
+    assert_eq!(hash("e"), hash("f"));
+    // Free variables are compared by name
+    assert_ne!(hash("g"), hash("h"));
+}
@@ -0,0 +1,100 @@
+use super::{Budget, NonTermination, Outcome, RedexKind, Reduction, TraceStep};
+
+// Traces are written as JSON, with one step to a line so that two traces diff step by step. The
+// format is described in the README and changes its version when it changes.
+
+/// The version of the trace format written by Reduction::to_json
+pub const TRACE_FORMAT_VERSION: usize = 2;
+
+impl Reduction {
+    /// The reduction as a JSON trace
+    pub fn to_json(&self) -> String {
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|step| format!("    {}", step_json(step)))
+            .collect();
+        format!(
+            "{{\n  \"format\": \"sfl-trace\",\n  \"version\": {},\n  \"strategy\": {},\n  \"start\": {},\n  \"steps\": [{}\n  ],\n  \"outcome\": {}\n}}\n",
+            TRACE_FORMAT_VERSION,
+            json_string(self.strategy),
+            json_string(&self.start),
+            if steps.is_empty() {
+                String::new()
+            } else {
+                format!("\n{}", steps.join(",\n"))
+            },
+            outcome_json(&self.outcome)
+        )
+    }
+}
+
+fn step_json(step: &TraceStep) -> String {
+    let location: Vec<String> = step.location.iter().map(|i| i.to_string()).collect();
+    format!(
+        "{{\"location\": [{}], {}, \"msg_before\": {}, \"msg_after\": {}, \"redex\": {}, \"contractum\": {}, \"term\": {}}}",
+        location.join(", "),
+        kind_json(step.kind),
+        json_string(&step.msg_before),
+        json_string(&step.msg_after),
+        json_string(&step.redex),
+        json_string(&step.contractum),
+        json_string(&step.term)
+    )
+}
+
+/// The kind of a step as JSON fields, with the case that matched for a match
+pub(super) fn kind_json(kind: RedexKind) -> String {
+    match kind {
+        RedexKind::Match(case) => format!("\"kind\": \"match\", \"case\": {}", case),
+        kind => format!("\"kind\": {}", json_string(kind.name())),
+    }
+}
+
+fn outcome_json(outcome: &Outcome) -> String {
+    let fields = match outcome {
+        Outcome::NormalForm => "\"kind\": \"normal-form\"".to_string(),
+        Outcome::BudgetExceeded { budget, limit } => format!(
+            "\"kind\": \"budget-exceeded\", \"budget\": {}, \"limit\": {}",
+            json_string(match budget {
+                Budget::Steps => "steps",
+                Budget::Nodes => "nodes",
+            }),
+            limit
+        ),
+        Outcome::Cancelled => "\"kind\": \"cancelled\"".to_string(),
+        Outcome::Loop(NonTermination::Loop { step, earlier }) => format!(
+            "\"kind\": \"loop\", \"step\": {}, \"earlier\": {}",
+            step, earlier
+        ),
+        Outcome::Loop(NonTermination::Growth { budget, .. }) => format!(
+            "\"kind\": \"budget-exceeded\", \"budget\": \"nodes\", \"limit\": {}",
+            budget
+        ),
+        Outcome::NotPreserved(_) => "\"kind\": \"not-preserved\"".to_string(),
+    };
+    format!(
+        "{{{}, \"message\": {}}}",
+        fields,
+        json_string(&outcome.to_string())
+    )
+}
+
+/// s as a JSON string literal
+pub(super) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
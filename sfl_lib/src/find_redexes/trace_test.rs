@@ -0,0 +1,50 @@
+use super::budget_test::reduce;
+use crate::{EvaluationConfig, Parser};
+
+#[test]
+fn trace_json_has_every_step() {
+    let program = "f :: Maybe Int -> Int\nf m = match m {\n  | Just x -> x\n  | Nothing -> 0\n}\nmain :: Int\nmain = f (Just (1 + 2)) * 2";
+    let json = reduce(program, EvaluationConfig::default()).to_json();
+    let expected = r#"{
+  "format": "sfl-trace",
+  "version": 2,
+  "strategy": "normal",
+  "start": "(f (Just (1 + 2))) * 2",
+  "steps": [
+    {"location": [0, 1], "kind": "function", "msg_before": "Apply function f to (Just (1 + 2))", "msg_after": "Applied function f to (Just (1 + 2))", "redex": "f (Just (1 + 2))", "contractum": "match (Just (1 + 2)) {\n  | Just x -> x\n  | Nothing -> 0\n}", "term": "match (Just (1 + 2)) {\n  | Just x -> x\n  | Nothing -> 0\n} * 2"},
+    {"location": [0, 1], "kind": "match", "case": 0, "msg_before": "Match to pattern Just x", "msg_after": "Matched to pattern Just x", "redex": "match (Just (1 + 2)) {\n  | Just x -> x\n  | Nothing -> 0\n}", "contractum": "1 + 2", "term": "(1 + 2) * 2"},
+    {"location": [0, 1], "kind": "inbuilt", "msg_before": "Apply inbuilt + to 1 and 2", "msg_after": "Applied inbuilt + to 1 and 2", "redex": "1 + 2", "contractum": "3", "term": "3 * 2"},
+    {"location": [], "kind": "inbuilt", "msg_before": "Apply inbuilt * to 3 and 2", "msg_after": "Applied inbuilt * to 3 and 2", "redex": "3 * 2", "contractum": "6", "term": "6"}
+  ],
+  "outcome": {"kind": "normal-form", "message": "reached normal form"}
+}
+"#;
+    assert_eq!(json, expected);
+}
+
+#[test]
+fn trace_json_records_why_it_stopped() {
+    let config = EvaluationConfig {
+        max_steps: 0,
+        ..Default::default()
+    };
+    let json = reduce("main :: Int\nmain = 1 + 2", config).to_json();
+    assert!(json.contains("  \"steps\": [\n  ],\n"));
+    assert!(json.contains(
+        "\"outcome\": {\"kind\": \"budget-exceeded\", \"budget\": \"steps\", \"limit\": 0, \"message\": \"budget exceeded: stopped after 0 steps\"}"
+    ));
+}
+
+#[test]
+fn paths_lead_to_the_node() {
+    let ast = Parser::from_string("f x = x\nmain = f (Just (1 + 2)) * 2".to_string())
+        .parse_module(true)
+        .unwrap()
+        .ast;
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let call = ast.get_arg(ast.get_func(main_expr));
+    assert_eq!(ast.to_string_sugar(call, false), "f (Just (1 + 2))");
+    assert_eq!(ast.get_path(main_expr, call), Some(vec![0, 1]));
+    assert_eq!(ast.get_path(main_expr, main_expr), Some(vec![]));
+    assert_eq!(ast.get_path(call, main_expr), None);
+}
@@ -8,7 +8,24 @@ use std::{
     str::FromStr,
 };
 
-fn inbuilt_binary<T>(call: &ASTNode, args: Vec<&ASTNode>, op: fn(T, T) -> T, p: Primitive) -> AST
+fn overflow_check(result: Option<i64>) -> Result<i64, String> {
+    result.ok_or_else(|| "integer overflow".to_string())
+}
+
+fn zero_check(divisor: i64) -> Result<(), String> {
+    if divisor == 0 {
+        Err("division by zero".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn inbuilt_binary<T>(
+    call: &ASTNode,
+    args: Vec<&ASTNode>,
+    op: fn(T, T) -> Result<T, String>,
+    p: Primitive,
+) -> Result<AST, String>
 where
     T: FromStr + Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Display,
     <T as FromStr>::Err: std::fmt::Debug,
@@ -22,7 +39,7 @@ where
     let a_int: T = a.get_value().parse().unwrap();
     let b_int: T = b.get_value().parse().unwrap();
 
-    let c_int = op(b_int, a_int);
+    let c_int = op(b_int, a_int)?;
     let mut ast = AST::new();
     ast.add_lit(
         Token {
@@ -32,66 +49,92 @@ where
         call.line,
         call.col,
     );
-    ast
+    Ok(ast)
 }
 
-pub fn inbuilt_int_add(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
-    inbuilt_binary(call, args, i64::add, Primitive::Int64)
+pub fn inbuilt_int_add(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    inbuilt_binary(
+        call,
+        args,
+        |x: i64, y: i64| overflow_check(x.checked_add(y)),
+        Primitive::Int64,
+    )
 }
 
-pub fn inbuilt_int_sub(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
-    inbuilt_binary(call, args, i64::sub, Primitive::Int64)
+pub fn inbuilt_int_sub(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    inbuilt_binary(
+        call,
+        args,
+        |x: i64, y: i64| overflow_check(x.checked_sub(y)),
+        Primitive::Int64,
+    )
 }
 
-pub fn inbuilt_int_mul(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
-    inbuilt_binary(call, args, i64::mul, Primitive::Int64)
+pub fn inbuilt_int_mul(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    inbuilt_binary(
+        call,
+        args,
+        |x: i64, y: i64| overflow_check(x.checked_mul(y)),
+        Primitive::Int64,
+    )
 }
 
-pub fn inbuilt_int_div(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
-    inbuilt_binary(call, args, i64::div, Primitive::Int64)
+pub fn inbuilt_int_div(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    inbuilt_binary(
+        call,
+        args,
+        |x: i64, y: i64| {
+            zero_check(y)?;
+            overflow_check(x.checked_div(y))
+        },
+        Primitive::Int64,
+    )
 }
 
-pub fn inbuilt_int_mod(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_int_mod(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary(
         call,
         args,
-        |x: i64, y: i64| x - ((x / y) * y),
+        |x: i64, y: i64| {
+            zero_check(y)?;
+            overflow_check(x.checked_rem(y))
+        },
         Primitive::Int64,
     )
 }
 
-pub fn inbuilt_int_neg(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_int_neg(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     assert_eq!(args.len(), 1);
     let x: i64 = args[0].get_value().parse().unwrap();
     let mut ast = AST::new();
     ast.add_lit(
         Token {
             tt: TokenType::IntLit,
-            value: format!("{}", -x),
+            value: format!("{}", overflow_check(x.checked_neg())?),
         },
         call.line,
         call.col,
     );
-    ast
+    Ok(ast)
 }
 
-pub fn inbuilt_float_add(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
-    inbuilt_binary(call, args, f64::add, Primitive::Float64)
+pub fn inbuilt_float_add(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    inbuilt_binary(call, args, |x, y| Ok(f64::add(x, y)), Primitive::Float64)
 }
 
-pub fn inbuilt_float_sub(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
-    inbuilt_binary(call, args, f64::sub, Primitive::Float64)
+pub fn inbuilt_float_sub(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    inbuilt_binary(call, args, |x, y| Ok(f64::sub(x, y)), Primitive::Float64)
 }
 
-pub fn inbuilt_float_mul(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
-    inbuilt_binary(call, args, f64::mul, Primitive::Float64)
+pub fn inbuilt_float_mul(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    inbuilt_binary(call, args, |x, y| Ok(f64::mul(x, y)), Primitive::Float64)
 }
 
-pub fn inbuilt_float_div(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
-    inbuilt_binary(call, args, f64::div, Primitive::Float64)
+pub fn inbuilt_float_div(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    inbuilt_binary(call, args, |x, y| Ok(f64::div(x, y)), Primitive::Float64)
 }
 
-pub fn inbuilt_float_neg(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_float_neg(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     assert_eq!(args.len(), 1);
     let x: f64 = args[0].get_value().parse().unwrap();
     let mut ast = AST::new();
@@ -103,11 +146,11 @@ pub fn inbuilt_float_neg(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
         call.line,
         call.col,
     );
-    ast
+    Ok(ast)
 }
 
 #[cfg(test)]
-pub fn inbuilt_int_zero(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_int_zero(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     assert!(args.len() == 0);
 
     let mut ast = AST::new();
@@ -119,7 +162,7 @@ pub fn inbuilt_int_zero(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
         call.line,
         call.col,
     );
-    ast
+    Ok(ast)
 }
 
 fn inbuilt_binary_boolean<T>(
@@ -127,7 +170,7 @@ fn inbuilt_binary_boolean<T>(
     args: Vec<&ASTNode>,
     op: fn(T, T) -> bool,
     p: Primitive,
-) -> AST
+) -> Result<AST, String>
 where
     T: FromStr + Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Display,
     <T as FromStr>::Err: std::fmt::Debug,
@@ -152,45 +195,45 @@ where
         call.line,
         call.col,
     );
-    ast
+    Ok(ast)
 }
 
-pub fn inbuilt_int_eq(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_int_eq(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: i64, y: i64| x == y, Primitive::Int64)
 }
 
-pub fn inbuilt_int_lt(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_int_lt(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: i64, y: i64| x < y, Primitive::Int64)
 }
 
-pub fn inbuilt_int_gt(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_int_gt(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: i64, y: i64| x > y, Primitive::Int64)
 }
 
-pub fn inbuilt_int_lte(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_int_lte(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: i64, y: i64| x <= y, Primitive::Int64)
 }
 
-pub fn inbuilt_int_gte(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_int_gte(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: i64, y: i64| x >= y, Primitive::Int64)
 }
 
-pub fn inbuilt_float_eq(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_float_eq(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: f64, y: f64| x == y, Primitive::Float64)
 }
 
-pub fn inbuilt_float_lt(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_float_lt(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: f64, y: f64| x < y, Primitive::Float64)
 }
 
-pub fn inbuilt_float_gt(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_float_gt(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: f64, y: f64| x > y, Primitive::Float64)
 }
 
-pub fn inbuilt_float_lte(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_float_lte(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: f64, y: f64| x <= y, Primitive::Float64)
 }
 
-pub fn inbuilt_float_gte(call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+pub fn inbuilt_float_gte(call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
     inbuilt_binary_boolean(call, args, |x: f64, y: f64| x >= y, Primitive::Float64)
 }
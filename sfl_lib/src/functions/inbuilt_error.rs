@@ -0,0 +1,7 @@
+use crate::*;
+
+/// Bottom. Evaluating it halts the program, so it can show that an unused argument is never evaluated
+pub fn inbuilt_undefined(_: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
+    assert!(args.is_empty());
+    Err("undefined".to_string())
+}
@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use inbuilt_arith::*;
+use inbuilt_error::*;
 
 use crate::*;
 mod inbuilt_arith;
+mod inbuilt_error;
 
 #[cfg(test)]
 mod test;
@@ -19,7 +21,8 @@ fn assert_prim_type(x: &Type, p: Primitive) {
     }
 }
 
-type InbuiltFuncPointer = fn(&ASTNode, Vec<&ASTNode>) -> AST;
+/// Inbuilts return Err with a message if they fail at runtime, e.g. on division by zero
+type InbuiltFuncPointer = fn(&ASTNode, Vec<&ASTNode>) -> Result<AST, String>;
 
 #[derive(Clone, Debug)]
 pub struct Label {
@@ -31,7 +34,7 @@ pub struct Label {
 }
 
 impl Label {
-    pub fn call_inbuilt(&self, call: &ASTNode, args: Vec<&ASTNode>) -> AST {
+    pub fn call_inbuilt(&self, call: &ASTNode, args: Vec<&ASTNode>) -> Result<AST, String> {
         assert_eq!(self.inbuilt_reduction_arity, Some(args.len()));
         assert!(self.inbuilt.is_some());
         self.inbuilt.unwrap()(call, args)
@@ -47,12 +50,15 @@ pub struct KnownTypeLabelTable {
     /// Sorted by arity. So inbuilts[0] will be all inbuilts with arity 0
     /// inbuilts[1] will be all inbuilts with arity 1, etc.
     pub func_map: HashMap<String, Label>,
+    /// Labels whose calls are evaluated in one step rather than stepped through
+    opaque: HashSet<String>,
 }
 
 impl KnownTypeLabelTable {
     pub fn new() -> Self {
         let mut s = Self {
             func_map: HashMap::new(),
+            opaque: HashSet::new(),
         };
         s.populate_inbuilts();
         s
@@ -121,11 +127,11 @@ impl KnownTypeLabelTable {
             let ass_n = &ast.get(assign);
             let proclaimed_type = match &ass_n.type_assignment {
                 None => {
-                    return Err(TypeError {
-                        e: format!("Label {} has no type assignment", name),
-                        line: ast.get(assign).line,
-                        col: ast.get(assign).col,
-                    })
+                    return Err(TypeError::new(
+                        format!("Label {} has no type assignment", name),
+                        ast.get(assign).line,
+                        ast.get(assign).col,
+                    ))
                 }
                 Some(t) => t.clone(),
             };
@@ -140,6 +146,27 @@ impl KnownTypeLabelTable {
         self.func_map.get(name)
     }
 
+    /// Make calls to the label evaluate to a value in one step. Inbuilts already do
+    pub fn set_opaque(&mut self, name: &str) -> Result<(), String> {
+        match self.func_map.get(name) {
+            None => Err(format!("Unknown label {} can't be made opaque", name)),
+            Some(_) if name.starts_with(|c: char| c.is_uppercase()) => {
+                Err(format!("Constructor {} can't be made opaque", name))
+            }
+            Some(label) if label.is_inbuilt() => {
+                Err(format!("Inbuilt {} is already evaluated in one step", name))
+            }
+            Some(_) => {
+                self.opaque.insert(name.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn is_opaque(&self, name: &str) -> bool {
+        self.opaque.contains(name)
+    }
+
     fn populate_inbuilts(&mut self) {
         let binary_int_type = Type::Function(
             Box::new(Type::Primitive(Primitive::Int64)),
@@ -325,6 +352,13 @@ impl KnownTypeLabelTable {
         self.add_inbuilt("neg".to_string(), 1, inbuilt_int_neg, unary_int_type);
         self.add_inbuilt("negf".to_string(), 1, inbuilt_float_neg, unary_float_type);
 
+        self.add_inbuilt(
+            "undefined".to_string(),
+            0,
+            inbuilt_undefined,
+            Type::fa(vec!["a".to_string()], Type::tv("a".to_string())),
+        );
+
         #[cfg(test)]
         self.add_inbuilt(
             "zero_ary_test".to_string(),
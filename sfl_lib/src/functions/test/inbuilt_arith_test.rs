@@ -49,10 +49,18 @@ fn test_basic_int_arith() {
         );
         let call = call_ast.get(0);
 
-        let c_add = add.call_inbuilt(&call, vec![ast.get(b), ast.get(a)]);
-        let c_sub = sub.call_inbuilt(&call, vec![ast.get(b), ast.get(a)]);
-        let c_mul = mul.call_inbuilt(&call, vec![ast.get(b), ast.get(a)]);
-        let c_div = div.call_inbuilt(&call, vec![ast.get(b), ast.get(a)]);
+        let c_add = add
+            .call_inbuilt(&call, vec![ast.get(b), ast.get(a)])
+            .unwrap();
+        let c_sub = sub
+            .call_inbuilt(&call, vec![ast.get(b), ast.get(a)])
+            .unwrap();
+        let c_mul = mul
+            .call_inbuilt(&call, vec![ast.get(b), ast.get(a)])
+            .unwrap();
+        let c_div = div
+            .call_inbuilt(&call, vec![ast.get(b), ast.get(a)])
+            .unwrap();
 
         matches!(
             c_add.get(0).get_lit_type(),
@@ -127,10 +135,18 @@ fn test_basic_float_arith() {
         );
         let call = call_ast.get(0);
 
-        let c_add = add.call_inbuilt(&call, vec![ast.get(b), ast.get(a)]);
-        let c_sub = sub.call_inbuilt(&call, vec![ast.get(b), ast.get(a)]);
-        let c_mul = mul.call_inbuilt(&call, vec![ast.get(b), ast.get(a)]);
-        let c_div = div.call_inbuilt(&call, vec![ast.get(b), ast.get(a)]);
+        let c_add = add
+            .call_inbuilt(&call, vec![ast.get(b), ast.get(a)])
+            .unwrap();
+        let c_sub = sub
+            .call_inbuilt(&call, vec![ast.get(b), ast.get(a)])
+            .unwrap();
+        let c_mul = mul
+            .call_inbuilt(&call, vec![ast.get(b), ast.get(a)])
+            .unwrap();
+        let c_div = div
+            .call_inbuilt(&call, vec![ast.get(b), ast.get(a)])
+            .unwrap();
 
         matches!(
             c_add.get(0).get_lit_type(),
@@ -167,3 +183,46 @@ fn test_basic_float_arith() {
         );
     }
 }
+
+#[test]
+fn test_int_arith_runtime_errors() {
+    let mut ast = AST::new();
+    let labels = KnownTypeLabelTable::new();
+
+    let lit = |ast: &mut AST, value: &str| {
+        ast.add_lit(
+            Token {
+                tt: parsing::TokenType::IntLit,
+                value: value.to_string(),
+            },
+            0,
+            0,
+        )
+    };
+    let zero = lit(&mut ast, "0");
+    let one = lit(&mut ast, "1");
+    let max = lit(&mut ast, &i64::MAX.to_string());
+    let min = lit(&mut ast, &i64::MIN.to_string());
+    let minus_one = lit(&mut ast, "-1");
+
+    let call = ast.get(zero);
+
+    // Args are in reverse order, so these are 1 / 0, 1 % 0, MAX + 1, MIN - 1, MAX * MAX and MIN / -1
+    let cases = vec![
+        ("div", one, zero),
+        ("mod", one, zero),
+        ("add", max, one),
+        ("sub", min, one),
+        ("mul", max, max),
+        ("div", min, minus_one),
+    ];
+    for (name, x, y) in cases {
+        let label = labels.get(&name.to_string()).unwrap();
+        assert!(label
+            .call_inbuilt(call, vec![ast.get(y), ast.get(x)])
+            .is_err());
+    }
+
+    let neg = labels.get(&"neg".to_string()).unwrap();
+    assert!(neg.call_inbuilt(call, vec![ast.get(min)]).is_err());
+}
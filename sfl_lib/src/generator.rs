@@ -0,0 +1,404 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The types generated programs use. Data types refer to the declarations by index, and only to
+/// those before them, so that every type has values that can be built without recursion
+#[derive(Clone, PartialEq)]
+enum GenType {
+    Int,
+    Bool,
+    Pair(Box<GenType>, Box<GenType>),
+    Function(Box<GenType>, Box<GenType>),
+    Data(usize),
+}
+
+struct DataDecl {
+    name: String,
+    constructors: Vec<(String, Vec<GenType>)>,
+}
+
+/// The variables in scope, with their types
+type Scope = Vec<(String, GenType)>;
+
+/// A random well-typed module, as source
+pub struct GeneratedModule {
+    /// The data declarations, which the printer does not output
+    pub data: String,
+    /// The labels, ending with main
+    pub labels: String,
+}
+
+impl GeneratedModule {
+    pub fn source(&self) -> String {
+        format!("{}\n{}", self.data, self.labels)
+    }
+}
+
+/// Builds random well-typed modules from a seed, with data types, matches, lambdas and
+/// arithmetic. Labels only refer to those before them, and data types are not recursive, so
+/// every program terminates. main is an Int or a Bool, so it reduces to a literal
+pub struct Generator {
+    rng: StdRng,
+    data: Vec<DataDecl>,
+    labels: Scope,
+    next_var: usize,
+}
+
+const MAX_DEPTH: usize = 3;
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            data: vec![],
+            labels: vec![],
+            next_var: 0,
+        }
+    }
+
+    pub fn module(&mut self) -> GeneratedModule {
+        let mut data = String::new();
+        for _ in 0..self.rng.gen_range(1..=2) {
+            data += &self.data_decl();
+            data += "\n";
+        }
+
+        let mut labels = String::new();
+        for i in 0..self.rng.gen_range(1..=3) {
+            let t = self.gen_type(2);
+            labels += &self.label(format!("f{}", i), t);
+        }
+        let main_type = if self.rng.gen_bool(0.5) {
+            GenType::Int
+        } else {
+            GenType::Bool
+        };
+        labels += &self.label("main".to_string(), main_type);
+
+        GeneratedModule { data, labels }
+    }
+
+    fn data_decl(&mut self) -> String {
+        let index = self.data.len();
+        let name = format!("D{}", index);
+
+        let mut constructors = vec![];
+        for i in 0..self.rng.gen_range(1..=3) {
+            let fields = (0..self.rng.gen_range(0..=2))
+                .map(|_| self.field_type())
+                .collect();
+            constructors.push((format!("{}{}", name, (b'A' + i) as char), fields));
+        }
+
+        let decl = format!(
+            "data {} = {}",
+            name,
+            constructors
+                .iter()
+                .map(|(c, fields)| {
+                    let mut s = c.clone();
+                    for field in fields {
+                        s += &format!(" {}", type_to_string(field, &self.data, true));
+                    }
+                    s
+                })
+                .collect::<Vec<_>>()
+                .join(" | ")
+        );
+        self.data.push(DataDecl { name, constructors });
+        decl
+    }
+
+    /// A type for a constructor's field, which can only be a data type declared before
+    fn field_type(&mut self) -> GenType {
+        match self.rng.gen_range(0..4) {
+            0 if !self.data.is_empty() => GenType::Data(self.rng.gen_range(0..self.data.len())),
+            1 => GenType::Pair(Box::new(GenType::Int), Box::new(GenType::Bool)),
+            2 => GenType::Bool,
+            _ => GenType::Int,
+        }
+    }
+
+    fn gen_type(&mut self, depth: usize) -> GenType {
+        let choice = if depth == 0 {
+            self.rng.gen_range(0..3)
+        } else {
+            self.rng.gen_range(0..5)
+        };
+        match choice {
+            0 => GenType::Int,
+            1 => GenType::Bool,
+            2 => GenType::Data(self.rng.gen_range(0..self.data.len())),
+            3 => GenType::Pair(
+                Box::new(self.gen_type(depth - 1)),
+                Box::new(self.gen_type(depth - 1)),
+            ),
+            _ => GenType::Function(
+                Box::new(self.gen_type(depth - 1)),
+                Box::new(self.gen_type(depth - 1)),
+            ),
+        }
+    }
+
+    fn fresh_var(&mut self) -> String {
+        self.next_var += 1;
+        format!("v{}", self.next_var - 1)
+    }
+
+    /// A type assignment and definition. Functions are sometimes defined with their arguments
+    /// on the left
+    fn label(&mut self, name: String, t: GenType) -> String {
+        let mut s = format!("{} :: {}\n", name, type_to_string(&t, &self.data, false));
+
+        let body = match &t {
+            GenType::Function(from, to) if self.rng.gen_bool(0.5) => {
+                let var = self.fresh_var();
+                let scope = vec![(var.clone(), from.as_ref().clone())];
+                format!("{} {} = {}", name, var, self.expr(to, &scope, MAX_DEPTH))
+            }
+            _ => format!("{} = {}", name, self.expr(&t, &vec![], MAX_DEPTH)),
+        };
+        s += &body;
+        s += "\n\n";
+
+        self.labels.push((name, t));
+        s
+    }
+
+    /// A random expression of type t, using the variables in scope and the labels so far
+    fn expr(&mut self, t: &GenType, scope: &Scope, depth: usize) -> String {
+        if depth == 0 {
+            return self.leaf(t, scope);
+        }
+
+        match self.rng.gen_range(0..8) {
+            0 => self.leaf(t, scope),
+            1 => self.call(t, scope, depth),
+            2 => {
+                // A beta redex
+                let arg_type = self.gen_type(1);
+                let var = self.fresh_var();
+                let mut inner = scope.clone();
+                inner.push((var.clone(), arg_type.clone()));
+                let body = self.expr(t, &inner, depth - 1);
+                let arg = self.expr(&arg_type, scope, depth - 1);
+                format!("((\\{}. {}) {})", var, body, arg)
+            }
+            3 => self.match_expr(t, scope, depth),
+            4 => {
+                let cond = self.expr(&GenType::Bool, scope, depth - 1);
+                let then_branch = self.expr(t, scope, depth - 1);
+                let else_branch = self.expr(t, scope, depth - 1);
+                format!("(if {} {} {})", cond, then_branch, else_branch)
+            }
+            5 => format!(
+                "({} :: {})",
+                self.expr(t, scope, depth - 1),
+                type_to_string(t, &self.data, false)
+            ),
+            _ => self.build(t, scope, depth),
+        }
+    }
+
+    /// An expression built from the parts of t: arithmetic, comparisons, pairs, lambdas and
+    /// constructors
+    fn build(&mut self, t: &GenType, scope: &Scope, depth: usize) -> String {
+        match t {
+            GenType::Int => {
+                let op = ["+", "-", "*"][self.rng.gen_range(0..3)];
+                let lhs = self.expr(&GenType::Int, scope, depth - 1);
+                let rhs = self.expr(&GenType::Int, scope, depth - 1);
+                format!("({} {} {})", lhs, op, rhs)
+            }
+            GenType::Bool => {
+                let op = ["==", "<", "<=", ">", ">="][self.rng.gen_range(0..5)];
+                let lhs = self.expr(&GenType::Int, scope, depth - 1);
+                let rhs = self.expr(&GenType::Int, scope, depth - 1);
+                format!("({} {} {})", lhs, op, rhs)
+            }
+            GenType::Pair(t1, t2) => format!(
+                "({}, {})",
+                self.expr(t1, scope, depth - 1),
+                self.expr(t2, scope, depth - 1)
+            ),
+            GenType::Function(from, to) => {
+                let mut inner = scope.clone();
+                let var = match from.as_ref() {
+                    GenType::Pair(t1, t2) if self.rng.gen_bool(0.5) => {
+                        let (v1, v2) = (self.fresh_var(), self.fresh_var());
+                        inner.push((v1.clone(), t1.as_ref().clone()));
+                        inner.push((v2.clone(), t2.as_ref().clone()));
+                        format!("({}, {})", v1, v2)
+                    }
+                    GenType::Int | GenType::Bool if self.rng.gen_bool(0.5) => {
+                        let var = self.fresh_var();
+                        inner.push((var.clone(), from.as_ref().clone()));
+                        format!("{} :: {}", var, type_to_string(from, &self.data, false))
+                    }
+                    _ => {
+                        let var = self.fresh_var();
+                        inner.push((var.clone(), from.as_ref().clone()));
+                        var
+                    }
+                };
+                format!("(\\{}. {})", var, self.expr(to, &inner, depth - 1))
+            }
+            GenType::Data(d) => {
+                let i = self.rng.gen_range(0..self.data[*d].constructors.len());
+                let (name, fields) = self.data[*d].constructors[i].clone();
+                let mut s = name;
+                for field in fields {
+                    s += &format!(" {}", self.expr(&field, scope, depth - 1));
+                }
+                format!("({})", s)
+            }
+        }
+    }
+
+    /// A variable, label or value of type t, with no more than one level of nesting
+    fn leaf(&mut self, t: &GenType, scope: &Scope) -> String {
+        let vars: Vec<String> = scope
+            .iter()
+            .chain(self.labels.iter())
+            .filter(|(_, var_type)| var_type == t)
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !vars.is_empty() && self.rng.gen_bool(0.5) {
+            return vars[self.rng.gen_range(0..vars.len())].clone();
+        }
+
+        match t {
+            GenType::Int => self.rng.gen_range(0..10).to_string(),
+            GenType::Bool => self.rng.gen_bool(0.5).to_string(),
+            GenType::Pair(t1, t2) => {
+                format!("({}, {})", self.leaf(t1, scope), self.leaf(t2, scope))
+            }
+            GenType::Function(from, to) => {
+                let var = self.fresh_var();
+                let mut inner = scope.clone();
+                inner.push((var.clone(), from.as_ref().clone()));
+                format!("(\\{}. {})", var, self.leaf(to, &inner))
+            }
+            GenType::Data(d) => {
+                let i = self.rng.gen_range(0..self.data[*d].constructors.len());
+                let (name, fields) = self.data[*d].constructors[i].clone();
+                let mut s = name;
+                for field in fields {
+                    s += &format!(" {}", self.leaf(&field, scope));
+                }
+                format!("({})", s)
+            }
+        }
+    }
+
+    /// A call of a function in scope that returns t, if there is one
+    fn call(&mut self, t: &GenType, scope: &Scope, depth: usize) -> String {
+        let functions: Vec<(String, Vec<GenType>)> = scope
+            .iter()
+            .chain(self.labels.iter())
+            .filter_map(|(name, var_type)| {
+                let mut args = vec![];
+                let mut result = var_type;
+                while let GenType::Function(from, to) = result {
+                    args.push(from.as_ref().clone());
+                    result = to;
+                    if result == t {
+                        return Some((name.clone(), args));
+                    }
+                }
+                None
+            })
+            .collect();
+        if functions.is_empty() {
+            return self.build(t, scope, depth);
+        }
+
+        let (name, args) = functions[self.rng.gen_range(0..functions.len())].clone();
+        let mut s = name;
+        for arg in args {
+            s += &format!(" {}", self.expr(&arg, scope, depth - 1));
+        }
+        format!("({})", s)
+    }
+
+    /// A match on a Bool, a pair or a data type, with a case for every possibility
+    fn match_expr(&mut self, t: &GenType, scope: &Scope, depth: usize) -> String {
+        let scrutinee_type = match self.rng.gen_range(0..3) {
+            0 => GenType::Bool,
+            1 => GenType::Pair(Box::new(GenType::Int), Box::new(self.gen_type(0))),
+            _ => GenType::Data(self.rng.gen_range(0..self.data.len())),
+        };
+        let scrutinee = self.expr(&scrutinee_type, scope, depth - 1);
+
+        let mut cases = vec![];
+        match &scrutinee_type {
+            GenType::Bool => {
+                for b in ["true", "false"] {
+                    cases.push((b.to_string(), scope.clone()));
+                }
+            }
+            GenType::Pair(t1, t2) => {
+                let (v1, v2) = (self.fresh_var(), self.fresh_var());
+                let mut inner = scope.clone();
+                inner.push((v1.clone(), t1.as_ref().clone()));
+                inner.push((v2.clone(), t2.as_ref().clone()));
+                cases.push((format!("({}, {})", v1, v2), inner));
+            }
+            GenType::Data(d) => {
+                let constructors = self.data[*d].constructors.clone();
+                for (name, fields) in constructors {
+                    let mut pattern = name;
+                    let mut inner = scope.clone();
+                    for field in fields {
+                        let var = self.fresh_var();
+                        pattern += &format!(" {}", var);
+                        inner.push((var, field));
+                    }
+                    cases.push((pattern, inner));
+                }
+                // Sometimes the last cases are covered by a wildcard
+                if cases.len() > 1 && self.rng.gen_bool(0.3) {
+                    cases.pop();
+                    cases.push(("_".to_string(), scope.clone()));
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        let mut s = format!("(match {} {{\n", scrutinee);
+        for (pattern, inner) in cases {
+            s += &format!("  | {} -> {}\n", pattern, self.expr(t, &inner, depth - 1));
+        }
+        s += "})";
+        s
+    }
+}
+
+/// The type in SFL syntax. In a constructor's fields, types with spaces are bracketed
+fn type_to_string(t: &GenType, data: &[DataDecl], bracket: bool) -> String {
+    let s = match t {
+        GenType::Int => return "Int".to_string(),
+        GenType::Bool => return "Bool".to_string(),
+        GenType::Data(d) => return data[*d].name.clone(),
+        GenType::Pair(t1, t2) => {
+            return format!(
+                "({}, {})",
+                type_to_string(t1, data, false),
+                type_to_string(t2, data, false)
+            )
+        }
+        GenType::Function(from, to) => {
+            let from_str = type_to_string(from, data, false);
+            let from_str = match from.as_ref() {
+                GenType::Function(_, _) => format!("({})", from_str),
+                _ => from_str,
+            };
+            format!("{} -> {}", from_str, type_to_string(to, data, false))
+        }
+    };
+    if bracket {
+        format!("({})", s)
+    } else {
+        s
+    }
+}
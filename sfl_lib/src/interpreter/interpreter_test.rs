@@ -0,0 +1,127 @@
+use crate::test_utils::parse_and_typecheck;
+use crate::{
+    evaluate, evaluate_within, find_single_redex_contraction_pair, typecheck, ASTNodeType, Parser,
+};
+
+fn run(program: &str) -> String {
+    let (ast, lt) = parse_and_typecheck(program);
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let value = evaluate(&ast, ast.root, main_expr, &lt);
+    value.to_string_sugar(value.root, false)
+}
+
+fn step(program: &str) -> String {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let module = ast.root;
+    let mut main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    while let Some(rc) = find_single_redex_contraction_pair(&ast, Some(module), main_expr, &lt) {
+        ast.do_rc_subst(main_expr, &rc);
+        main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    }
+    ast.to_string_sugar(main_expr, false)
+}
+
+#[test]
+fn examples_agree_with_the_stepper() {
+    let path = format!("{}/../examples", env!("CARGO_MANIFEST_DIR"));
+    for entry in std::fs::read_dir(path).unwrap() {
+        let program = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        let pr = Parser::from_string(program.clone())
+            .parse_module(true)
+            .unwrap();
+        let mut ast = pr.ast;
+        let mut lt = pr.lt;
+        let module = ast.root;
+        if typecheck(&mut ast, module, &mut lt, &pr.tm).is_ok() {
+            assert_eq!(run(&program), step(&program), "{}", program);
+        }
+    }
+}
+
+#[test]
+fn values() {
+    assert_eq!(run("main :: Int\nmain = 2 * (3 + 4)"), "14");
+    assert_eq!(
+        run("main :: (Int, List Bool)\nmain = (1 + 1, map (\\x. x > 1) (range 0 3))"),
+        "(2, Cons false (Cons false (Cons true Nil)))"
+    );
+    assert_eq!(
+        run("main :: Int -> Int\nmain = (\\x y. x + y) (2 * 3)"),
+        "\\y. 6 + y"
+    );
+}
+
+#[test]
+fn only_needed_arguments_are_evaluated() {
+    assert_eq!(run("main :: Int\nmain = (\\(a, b). b) (undefined, 1)"), "1");
+    assert_eq!(
+        run("main :: Int\nmain = length (take 3 (repeat undefined))"),
+        "3"
+    );
+}
+
+#[test]
+fn runtime_errors() {
+    assert_eq!(
+        run("main :: Int\nmain = 1 + (4 / (2 - 2))"),
+        "<error: division by zero>"
+    );
+    assert_eq!(
+        run("main :: Int\nmain = (\\(a, b). a) (undefined, 1)"),
+        "<error: undefined>"
+    );
+    assert_eq!(
+        run("x :: Int\nx = x + 1\nmain :: Int\nmain = x"),
+        "<error: infinite loop, a value depends on itself>"
+    );
+}
+
+// Both use the same pattern matcher, so only match once the scrutinee's head is a constructor
+#[test]
+fn matches_agree_with_the_stepper() {
+    for program in [
+        "main :: Int\nmain = match (\\l. l) (Cons 1 Nil) {\n  | Cons x xs -> x\n}",
+        "main :: Int\nmain = match (1, Cons 2 Nil) {\n  | (1, Nil) -> 0\n  | (_, Cons y _) -> y\n}",
+        "main :: Int\nmain = match (take 1 (repeat 3)) {\n  | Nil -> 0\n  | Cons x _ -> x\n}",
+    ] {
+        assert_eq!(run(program), step(program), "{}", program);
+    }
+    assert_eq!(
+        step("main :: Int\nmain = match (\\l. l) (Cons 1 Nil) {\n  | Cons x xs -> x\n}"),
+        "1"
+    );
+}
+
+#[test]
+fn deep_evaluation() {
+    assert_eq!(run("main :: Int\nmain = sum (range 1 10000)"), "49995000");
+}
+
+#[test]
+fn long_list_read_back() {
+    let (ast, lt) = parse_and_typecheck("main :: List Int\nmain = range 1 100000");
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let value = evaluate(&ast, ast.root, main_expr, &lt);
+
+    // Walk the spine of the list rather than printing it, which recurses
+    let mut length = 0;
+    let mut list = value.root;
+    while value.get(list).t == ASTNodeType::Application {
+        length += 1;
+        list = value.get_arg(list);
+    }
+    assert_eq!(value.get(list).get_value(), "Nil");
+    assert_eq!(length, 99999);
+}
+
+#[test]
+fn infinite_values_run_out_of_steps() {
+    let (ast, lt) = parse_and_typecheck("main :: List Int\nmain = repeat 1");
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    assert!(evaluate_within(&ast, ast.root, main_expr, &lt, 10_000).is_none());
+
+    let (ast, lt) = parse_and_typecheck("main :: Int\nmain = sum (take 3 (repeat 1))");
+    let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    let value = evaluate_within(&ast, ast.root, main_expr, &lt, 10_000).unwrap();
+    assert_eq!(value.to_string_sugar(value.root, false), "3");
+}
@@ -0,0 +1,407 @@
+use super::value::{Env, Thunk, Value};
+use crate::find_redexes::{match_pattern, Match};
+use crate::{ASTNodeType, KnownTypeLabelTable, AST};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A runtime error, and the node it happened at
+struct RuntimeError {
+    message: String,
+    node: usize,
+}
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+/// What the machine is doing: evaluating an expression, or returning a value to the frame on
+/// top of the stack
+enum Control {
+    Eval(usize, Env),
+    Return(Value),
+}
+
+/// What to do with a value once it has been evaluated. The stack of these replaces recursion, so
+/// deep evaluations, like a foldr over a long list, don't overflow the native stack
+enum Frame {
+    /// Store the value in the thunk that was forced
+    Update(Thunk),
+    /// Apply the value, a function, to the argument
+    Apply(Thunk),
+    /// Retry matching the scrutinee against the cases, now that part of it has been forced
+    Match(usize, Env, Thunk),
+    /// Retry binding an abstraction's argument to its pattern
+    Bind(usize, Env, Thunk),
+    /// Retry calling an inbuilt, now that one more of its arguments has been forced
+    Inbuilt(usize, Vec<Thunk>),
+}
+
+/// What is left to do while reading a value back
+enum ReadBack {
+    Thunk(Thunk),
+    Value(Value),
+    /// Apply the constructor or inbuilt to the last n expressions read back
+    Apply(usize, usize),
+    /// Pair the last two expressions read back
+    Pair,
+    /// Substitute the last expressions read back for the variables the abstraction closes over
+    Closure(usize, Vec<String>),
+}
+
+struct Machine<'a> {
+    ast: &'a AST,
+    lt: &'a KnownTypeLabelTable,
+    assigns: HashMap<String, usize>,
+    /// Labels are evaluated at most once, and shared by every use
+    globals: HashMap<String, Thunk>,
+    /// The expression being evaluated, which running out of steps is reported at
+    start: usize,
+    /// The steps evaluation and reading back may still take, if they are limited
+    steps_left: Option<usize>,
+    out_of_steps: bool,
+}
+
+impl<'a> Machine<'a> {
+    fn error(&self, message: &str, node: usize) -> RuntimeError {
+        RuntimeError {
+            message: message.to_string(),
+            node,
+        }
+    }
+
+    /// Count a step, failing once there are none left. Values can be infinite, as can reading
+    /// one back, so both take steps
+    fn tick(&mut self) -> Result<()> {
+        match self.steps_left {
+            Some(0) => {
+                self.out_of_steps = true;
+                Err(self.error("ran out of steps", self.start))
+            }
+            Some(steps) => {
+                self.steps_left = Some(steps - 1);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// A thunk for expr, reusing the thunk or value it already has where there is one
+    fn delay(&self, expr: usize, env: &Env) -> Thunk {
+        let n = self.ast.get(expr);
+        match n.t {
+            ASTNodeType::Literal => Thunk::done(Value::Literal(Rc::new(n.clone()))),
+            ASTNodeType::Abstraction => Thunk::done(Value::Closure(expr, env.clone())),
+            ASTNodeType::Identifier => match env.lookup(n.value_str()) {
+                Some(thunk) => thunk,
+                None => Thunk::delayed(expr, env.clone()),
+            },
+            _ => Thunk::delayed(expr, env.clone()),
+        }
+    }
+
+    fn enter(&self, thunk: &Thunk, stack: &mut Vec<Frame>) -> Result<Control> {
+        match thunk.start() {
+            Ok(Some((expr, env))) => {
+                stack.push(Frame::Update(thunk.clone()));
+                Ok(Control::Eval(expr, env))
+            }
+            Ok(None) => Ok(Control::Return(thunk.value().unwrap())),
+            Err(expr) => Err(self.error("infinite loop, a value depends on itself", expr)),
+        }
+    }
+
+    /// Evaluate the thunk to weak head normal form
+    fn force(&mut self, thunk: &Thunk) -> Result<Value> {
+        let mut stack = vec![];
+        let mut control = self.enter(thunk, &mut stack)?;
+        loop {
+            self.tick()?;
+            control = match control {
+                Control::Eval(expr, env) => self.eval(expr, env, &mut stack)?,
+                Control::Return(value) => match stack.pop() {
+                    None => return Ok(value),
+                    Some(frame) => self.resume(frame, value, &mut stack)?,
+                },
+            };
+        }
+    }
+
+    fn eval(&mut self, expr: usize, env: Env, stack: &mut Vec<Frame>) -> Result<Control> {
+        let n = self.ast.get(expr);
+        match n.t {
+            ASTNodeType::Literal => Ok(Control::Return(Value::Literal(Rc::new(n.clone())))),
+            ASTNodeType::Error => Err(self.error(&n.get_value(), expr)),
+            ASTNodeType::Identifier => {
+                let name = n.value_str();
+                if let Some(thunk) = env.lookup(name) {
+                    self.enter(&thunk, stack)
+                } else if n.is_uppercase() {
+                    Ok(Control::Return(Value::Constructor(expr, vec![])))
+                } else if let Some(thunk) = self.globals.get(name) {
+                    let thunk = thunk.clone();
+                    self.enter(&thunk, stack)
+                } else if let Some(assign) = self.assigns.get(name) {
+                    let exp = self.ast.get_assign_exp(*assign);
+                    let thunk = Thunk::delayed(exp, Env::default());
+                    self.globals.insert(name.to_string(), thunk.clone());
+                    self.enter(&thunk, stack)
+                } else if self
+                    .lt
+                    .func_map
+                    .get(name)
+                    .is_some_and(|label| label.is_inbuilt())
+                {
+                    self.call_inbuilt(expr, vec![], stack)
+                } else {
+                    Err(self.error(&format!("unknown label {}", name), expr))
+                }
+            }
+            ASTNodeType::Application => {
+                stack.push(Frame::Apply(self.delay(self.ast.get_arg(expr), &env)));
+                Ok(Control::Eval(self.ast.get_func(expr), env))
+            }
+            ASTNodeType::Abstraction => Ok(Control::Return(Value::Closure(expr, env))),
+            ASTNodeType::Pair => Ok(Control::Return(Value::Pair(
+                self.delay(self.ast.get_first(expr), &env),
+                self.delay(self.ast.get_second(expr), &env),
+            ))),
+            ASTNodeType::Match => {
+                let scrutinee = self.delay(self.ast.get_match_unpack_pattern(expr), &env);
+                self.match_cases(expr, env, scrutinee, stack)
+            }
+            ASTNodeType::Annotation => Ok(Control::Eval(self.ast.get_annotated_expr(expr), env)),
+            _ => unreachable!("Not an expression"),
+        }
+    }
+
+    fn resume(&mut self, frame: Frame, value: Value, stack: &mut Vec<Frame>) -> Result<Control> {
+        match frame {
+            Frame::Update(thunk) => {
+                thunk.update(value.clone());
+                Ok(Control::Return(value))
+            }
+            Frame::Apply(arg) => match value {
+                Value::Closure(abst, env) => self.bind(abst, env, arg, stack),
+                Value::Constructor(c, mut args) => {
+                    args.push(arg);
+                    Ok(Control::Return(Value::Constructor(c, args)))
+                }
+                Value::Inbuilt(f, mut args) => {
+                    args.push(arg);
+                    self.call_inbuilt(f, args, stack)
+                }
+                Value::Literal(_) | Value::Pair(_, _) => unreachable!("Applied a non function"),
+            },
+            Frame::Match(expr, env, scrutinee) => self.match_cases(expr, env, scrutinee, stack),
+            Frame::Bind(abst, env, arg) => self.bind(abst, env, arg, stack),
+            Frame::Inbuilt(f, args) => self.call_inbuilt(f, args, stack),
+        }
+    }
+
+    /// Bind an abstraction's argument to its variable, or its pattern, and evaluate its body
+    fn bind(
+        &mut self,
+        abst: usize,
+        env: Env,
+        arg: Thunk,
+        stack: &mut Vec<Frame>,
+    ) -> Result<Control> {
+        match match_pattern(self.ast, self.ast.get_abstr_var(abst), &arg) {
+            Match::Success(bindings) => Ok(Control::Eval(
+                self.ast.get_abstr_expr(abst),
+                extend(env, bindings),
+            )),
+            Match::Unknown(thunk) => {
+                stack.push(Frame::Bind(abst, env, arg));
+                self.enter(&thunk, stack)
+            }
+            Match::Refute => unreachable!("Abstraction patterns are irrefutable"),
+        }
+    }
+
+    /// Evaluate the first case whose pattern the scrutinee matches
+    fn match_cases(
+        &mut self,
+        expr: usize,
+        env: Env,
+        scrutinee: Thunk,
+        stack: &mut Vec<Frame>,
+    ) -> Result<Control> {
+        for (pattern, case_expr) in self.ast.get_match_cases(expr) {
+            match match_pattern(self.ast, pattern, &scrutinee) {
+                Match::Success(bindings) => {
+                    return Ok(Control::Eval(case_expr, extend(env, bindings)));
+                }
+                Match::Unknown(thunk) => {
+                    stack.push(Frame::Match(expr, env, scrutinee));
+                    return self.enter(&thunk, stack);
+                }
+                Match::Refute => {}
+            }
+        }
+        Err(self.error("no pattern matched", expr))
+    }
+
+    /// Call an inbuilt once it has all its arguments, forcing them first as inbuilts are strict
+    fn call_inbuilt(
+        &mut self,
+        f: usize,
+        args: Vec<Thunk>,
+        stack: &mut Vec<Frame>,
+    ) -> Result<Control> {
+        let f_n = self.ast.get(f);
+        let label = &self.lt.func_map[f_n.value_str()];
+        if args.len() < label.inbuilt_reduction_arity.unwrap() {
+            return Ok(Control::Return(Value::Inbuilt(f, args)));
+        }
+
+        let mut literals = vec![];
+        for arg in &args {
+            match arg.value() {
+                Some(Value::Literal(lit)) => literals.push(lit),
+                Some(_) => unreachable!("Inbuilts take literals"),
+                None => {
+                    let arg = arg.clone();
+                    stack.push(Frame::Inbuilt(f, args));
+                    return self.enter(&arg, stack);
+                }
+            }
+        }
+
+        // Inbuilts take their arguments last first, as check_for_valid_call finds them
+        let result = label
+            .call_inbuilt(f_n, literals.iter().rev().map(|lit| lit.as_ref()).collect())
+            .map_err(|e| self.error(&e, f))?;
+        let result_n = result.get(result.root);
+        assert_eq!(result_n.t, ASTNodeType::Literal, "Inbuilts give literals");
+        Ok(Control::Return(Value::Literal(Rc::new(result_n.clone()))))
+    }
+
+    /// Evaluate the value fully, and add it to out as an expression. The work left to do is kept
+    /// on a stack, as with evaluation, so long lists can be read back
+    fn read_back(&mut self, value: Value, out: &mut AST) -> Result<usize> {
+        let mut work = vec![ReadBack::Value(value)];
+        // The expressions read back so far, that are yet to be put together
+        let mut done: Vec<usize> = vec![];
+        while let Some(item) = work.pop() {
+            self.tick()?;
+            match item {
+                ReadBack::Thunk(thunk) => {
+                    let value = self.force(&thunk)?;
+                    work.push(ReadBack::Value(value));
+                }
+                ReadBack::Value(Value::Literal(lit)) => done.push(out.add(lit.as_ref().clone())),
+                ReadBack::Value(Value::Constructor(f, args) | Value::Inbuilt(f, args)) => {
+                    work.push(ReadBack::Apply(f, args.len()));
+                    work.extend(args.into_iter().rev().map(ReadBack::Thunk));
+                }
+                ReadBack::Value(Value::Pair(a, b)) => {
+                    work.push(ReadBack::Pair);
+                    work.push(ReadBack::Thunk(b));
+                    work.push(ReadBack::Thunk(a));
+                }
+                // The abstraction, with the variables it closes over substituted by their values
+                ReadBack::Value(Value::Closure(abst, env)) => {
+                    let closed: Vec<(String, Thunk)> = self
+                        .ast
+                        .get_free_identifiers(abst)
+                        .into_iter()
+                        .filter_map(|name| Some((name.clone(), env.lookup(&name)?)))
+                        .collect();
+                    let (names, thunks): (Vec<String>, Vec<Thunk>) = closed.into_iter().unzip();
+                    work.push(ReadBack::Closure(abst, names));
+                    work.extend(thunks.into_iter().rev().map(ReadBack::Thunk));
+                }
+                ReadBack::Apply(f, n) => {
+                    let f_n = self.ast.get(f);
+                    let args = done.split_off(done.len() - n);
+                    let mut head = out.append(self.ast, f);
+                    for arg in args {
+                        head = out.add_app(head, arg, f_n.line, f_n.col, false);
+                    }
+                    done.push(head);
+                }
+                ReadBack::Pair => {
+                    let b = done.pop().unwrap();
+                    let a = done.pop().unwrap();
+                    let (line, col) = (out.get(a).line, out.get(a).col);
+                    done.push(out.add_pair(a, b, line, col));
+                }
+                ReadBack::Closure(abst, names) => {
+                    let replacements = done.split_off(done.len() - names.len());
+                    let new = out.append(self.ast, abst);
+                    for (name, replacement) in names.iter().zip(replacements) {
+                        for usage in out.get_all_free_instances_of_var_in_exp(new, name) {
+                            out.replace_references_to_node(usage, replacement);
+                        }
+                    }
+                    done.push(new);
+                }
+            }
+        }
+        Ok(done.pop().unwrap())
+    }
+}
+
+fn extend(mut env: Env, bindings: Vec<(String, Thunk)>) -> Env {
+    for (name, thunk) in bindings {
+        env = env.bind(name, thunk);
+    }
+    env
+}
+
+/// The steps the interpreter takes before giving up when not told otherwise. Each is far
+/// smaller than a step of the stepper: summing a list of 100000 numbers takes about 7 million
+pub const DEFAULT_RUN_STEP_LIMIT: usize = 20_000_000;
+
+/// Evaluate expr, a closed expression in module, directly to a value, without the steps the
+/// stepper shows. Arguments are evaluated at most once, and only if needed, so this agrees with
+/// reducing to normal form lazily. The value is given as an expression, or an error term if
+/// evaluation fails at runtime, as a step to an error would
+pub fn evaluate(ast: &AST, module: usize, expr: usize, lt: &KnownTypeLabelTable) -> AST {
+    run(ast, module, expr, lt, None).unwrap()
+}
+
+/// evaluate, giving up with None if it takes more than max_steps steps, such as when the value
+/// is infinite
+pub fn evaluate_within(
+    ast: &AST,
+    module: usize,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+    max_steps: usize,
+) -> Option<AST> {
+    run(ast, module, expr, lt, Some(max_steps))
+}
+
+fn run(
+    ast: &AST,
+    module: usize,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+    steps_left: Option<usize>,
+) -> Option<AST> {
+    let mut machine = Machine {
+        ast,
+        lt,
+        assigns: ast.get_assigns_map(module),
+        globals: HashMap::new(),
+        start: expr,
+        steps_left,
+        out_of_steps: false,
+    };
+
+    let mut out = AST::new();
+    let thunk = Thunk::delayed(expr, Env::default());
+    let result = machine
+        .force(&thunk)
+        .and_then(|value| machine.read_back(value, &mut out));
+
+    out.root = match result {
+        Ok(root) => root,
+        Err(_) if machine.out_of_steps => return None,
+        Err(e) => {
+            let n = ast.get(e.node);
+            out.add_error(e.message, n.line, n.col)
+        }
+    };
+    Some(out)
+}
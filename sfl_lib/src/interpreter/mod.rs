@@ -0,0 +1,10 @@
+// A big-step interpreter, for running programs to a value rather than showing every step. It is
+// an environment machine with an explicit stack, so evaluation depth is only bounded by memory,
+// and it uses the same inbuilts as the stepper.
+mod machine;
+mod value;
+
+pub use machine::{evaluate, evaluate_within, DEFAULT_RUN_STEP_LIMIT};
+
+#[cfg(test)]
+mod interpreter_test;
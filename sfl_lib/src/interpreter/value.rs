@@ -0,0 +1,150 @@
+use crate::find_redexes::{Scrutinee, Shape};
+use crate::{ASTNode, AST};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A value in weak head normal form. Anything below the head is a thunk, so is only evaluated
+/// once it is needed
+#[derive(Clone)]
+pub(super) enum Value {
+    /// Shared, as values are copied out of thunks each time they are used
+    Literal(Rc<ASTNode>),
+    /// An abstraction, and the environment it was made in
+    Closure(usize, Env),
+    /// A constructor, by the identifier it was named by, applied to its arguments so far
+    Constructor(usize, Vec<Thunk>),
+    Pair(Thunk, Thunk),
+    /// An inbuilt, by the identifier it was named by, applied to fewer arguments than it needs
+    Inbuilt(usize, Vec<Thunk>),
+}
+
+enum ThunkState {
+    Delayed(usize, Env),
+    /// Being evaluated, so needing it again means it depends on itself
+    Evaluating(usize),
+    Done(Value),
+}
+
+/// An expression and its environment, replaced by its value the first time it is forced. Every
+/// use of a variable shares its thunk, which makes evaluation call-by-need
+#[derive(Clone)]
+pub(super) struct Thunk(Rc<RefCell<ThunkState>>);
+
+impl Thunk {
+    pub(super) fn delayed(expr: usize, env: Env) -> Self {
+        Self(Rc::new(RefCell::new(ThunkState::Delayed(expr, env))))
+    }
+
+    pub(super) fn done(value: Value) -> Self {
+        Self(Rc::new(RefCell::new(ThunkState::Done(value))))
+    }
+
+    pub(super) fn value(&self) -> Option<Value> {
+        match &*self.0.borrow() {
+            ThunkState::Done(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Mark the thunk as being evaluated, giving its expression and environment. Gives Err with
+    /// the expression if it already is, or None if it has a value
+    pub(super) fn start(&self) -> Result<Option<(usize, Env)>, usize> {
+        let mut state = self.0.borrow_mut();
+        match &*state {
+            ThunkState::Done(_) => Ok(None),
+            ThunkState::Evaluating(expr) => Err(*expr),
+            ThunkState::Delayed(expr, env) => {
+                let started = (*expr, env.clone());
+                *state = ThunkState::Evaluating(*expr);
+                Ok(Some(started))
+            }
+        }
+    }
+
+    pub(super) fn update(&self, value: Value) {
+        *self.0.borrow_mut() = ThunkState::Done(value);
+    }
+
+    /// Empty the thunk if nothing else shares it, adding the thunks it held to stack
+    fn take_parts(&mut self, stack: &mut Vec<Thunk>) {
+        let state = match Rc::get_mut(&mut self.0) {
+            Some(cell) => std::mem::replace(cell.get_mut(), ThunkState::Evaluating(0)),
+            None => return,
+        };
+        let env = match state {
+            ThunkState::Done(Value::Constructor(_, args) | Value::Inbuilt(_, args)) => {
+                stack.extend(args);
+                return;
+            }
+            ThunkState::Done(Value::Pair(a, b)) => {
+                stack.extend([a, b]);
+                return;
+            }
+            ThunkState::Done(Value::Closure(_, env)) | ThunkState::Delayed(_, env) => env,
+            ThunkState::Done(Value::Literal(_)) | ThunkState::Evaluating(_) => return,
+        };
+
+        let mut env = env.0;
+        while let Some(binding) = env.and_then(|binding| Rc::try_unwrap(binding).ok()) {
+            stack.push(binding.thunk);
+            env = binding.next.0;
+        }
+    }
+}
+
+// A long list is a long chain of thunks, so dropping it recursively would overflow the stack.
+// The thunks it holds are dropped one at a time instead
+impl Drop for Thunk {
+    fn drop(&mut self) {
+        let mut stack = vec![];
+        self.take_parts(&mut stack);
+        while let Some(mut thunk) = stack.pop() {
+            thunk.take_parts(&mut stack);
+        }
+    }
+}
+
+// Only the part of a thunk that has been evaluated can be matched, so patterns that need more
+// force it first
+impl Scrutinee for Thunk {
+    fn shape(&self, ast: &AST) -> Shape<Self> {
+        match self.value() {
+            None => Shape::Unevaluated,
+            Some(Value::Literal(lit)) => Shape::Literal(lit.get_value()),
+            Some(Value::Pair(a, b)) => Shape::Pair(a, b),
+            Some(Value::Constructor(c, args)) => Shape::Constructor(ast.get(c).get_value(), args),
+            Some(Value::Closure(_, _) | Value::Inbuilt(_, _)) => Shape::Function,
+        }
+    }
+}
+
+struct Binding {
+    name: String,
+    thunk: Thunk,
+    next: Env,
+}
+
+/// The variables in scope, innermost first
+#[derive(Clone, Default)]
+pub(super) struct Env(Option<Rc<Binding>>);
+
+impl Env {
+    pub(super) fn bind(&self, name: String, thunk: Thunk) -> Env {
+        Env(Some(Rc::new(Binding {
+            name,
+            thunk,
+            next: self.clone(),
+        })))
+    }
+
+    pub(super) fn lookup(&self, name: &str) -> Option<Thunk> {
+        let mut env = &self.0;
+        while let Some(binding) = env {
+            if binding.name == name {
+                return Some(binding.thunk.clone());
+            }
+            env = &binding.next.0;
+        }
+        None
+    }
+}
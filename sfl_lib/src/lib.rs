@@ -1,20 +1,42 @@
 mod ast;
 mod find_redexes;
 mod functions;
+mod interpreter;
 mod parsing;
 mod types;
 
 pub use ast::*;
 pub use find_redexes::{
-    find_all_redex_contraction_pairs, find_single_redex_contraction_pair, RCPair,
+    contract, contract_with, explore_reductions, explore_reductions_with,
+    find_all_redex_contraction_pairs, find_all_redex_contraction_pairs_with, find_all_redexes,
+    find_all_redexes_with, find_single_redex, find_single_redex_contraction_pair,
+    find_single_redex_contraction_pair_with, find_single_redex_with, reduce_within_budget,
+    strategy_from_name, ApplicativeOrder, BeforeStep, Budget, CallByNeed, EvaluationConfig,
+    EvaluationStrategy, GraphStep, GraphTerm, InnermostRightmost, ModuleContext, NonTermination,
+    NormalOrder, Outcome, RCPair, Redex, RedexKind, Reduction, ReductionGraph, ReductionRules,
+    ReductionSession, SeededRandom, TerminationChecker, TraceStep, DEFAULT_GRAPH_BOUND,
+    DEFAULT_SIZE_BUDGET, DEFAULT_STEP_LIMIT, GRAPH_FORMAT_VERSION, STRATEGY_NAMES,
+    TRACE_FORMAT_VERSION,
 };
 pub use functions::KnownTypeLabelTable;
+pub use interpreter::{evaluate, evaluate_within, DEFAULT_RUN_STEP_LIMIT};
 pub use parsing::{Parser, Token};
 pub static PRELUDE: &str = include_str!("../../prelude.sfl");
-pub use types::{typecheck, typecheck_tl_expr, Primitive, Type, TypeError};
+pub use types::{
+    lint_declared_types, typecheck, typecheck_hm, typecheck_reduced_expr, typecheck_tl_expr,
+    typecheck_with, typecheck_with_derivations, typecheck_with_types, Algorithm, Derivation,
+    PreservationChecker, PreservationError, Primitive, Type, TypeChecker, TypeError, TypeLint,
+    TypeTable, Unification, UnificationTrace,
+};
 
+#[cfg(test)]
+mod generator;
 #[cfg(test)]
 mod lib_test;
+#[cfg(test)]
+mod property_test;
+#[cfg(test)]
+mod test_utils;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm_utils;
@@ -1,4 +1,5 @@
 use super::*;
+use crate::test_utils::{parse_and_typecheck, parse_and_typecheck_with_types};
 
 fn full_run_test(program: &str, typechecked: bool) -> String {
     let pr = Parser::from_string(program.to_string())
@@ -24,6 +25,40 @@ fn full_run_test(program: &str, typechecked: bool) -> String {
     ast.to_string_sugar(main_expr, false)
 }
 
+fn lazy_run_test(program: &str) -> String {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let module = ast.root;
+
+    let mut main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    while let Some(rc) = find_single_redex_contraction_pair(&ast, Some(module), main_expr, &lt) {
+        ast.do_rc_subst(main_expr, &rc);
+        main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    }
+    ast.to_string_sugar(main_expr, false)
+}
+
+/// Run the program lazily for up to max_steps, checking after each step that main still has its
+/// type
+fn preservation_run_test(
+    program: &str,
+    max_steps: usize,
+) -> Result<String, Box<PreservationError>> {
+    let (mut ast, lt, tm) = parse_and_typecheck_with_types(program);
+    let module = ast.root;
+
+    let mut checker = PreservationChecker::new(&ast, &tm);
+    let mut main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    for _ in 0..max_steps {
+        let Some(rc) = find_single_redex_contraction_pair(&ast, Some(module), main_expr, &lt)
+        else {
+            break;
+        };
+        checker.do_rc_subst(&mut ast, &lt, &rc)?;
+        main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    }
+    Ok(ast.to_string_sugar(main_expr, false))
+}
+
 #[test]
 fn full_run_1() {
     let program = r#"
@@ -177,3 +212,155 @@ main = match (Diamonds, King) {
     let rc = find_single_redex_contraction_pair(&ast, Some(ast.root), main_expr, &lt).unwrap();
     println!("{:?}", rc.msg_before)
 }
+
+#[test]
+fn division_by_zero_is_runtime_error() {
+    let program = r#"
+    main :: Int
+    main = 1 + (10 / (5 - 5))
+    "#;
+
+    assert_eq!(full_run_test(program, true), "<error: division by zero>");
+}
+
+#[test]
+fn overflow_is_runtime_error() {
+    let program = r#"
+    main :: Int
+    main = 9223372036854775807 + 1
+    "#;
+
+    assert_eq!(full_run_test(program, true), "<error: integer overflow>");
+}
+
+/// Parse main, and find the first redex in it
+fn first_step(program: &str) -> (AST, usize, RCPair) {
+    let (ast, lt) = parse_and_typecheck(program);
+    let module = ast.root;
+    let main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    let rc = find_single_redex_contraction_pair(&ast, Some(module), main_expr, &lt).unwrap();
+    (ast, main_expr, rc)
+}
+
+#[test]
+fn step_contracts_only_its_redex() {
+    let program = r#"
+    main :: Int
+    main = (1 + 2) * (1 + 2)
+    "#;
+
+    let (mut ast, main_expr, rc) = first_step(program);
+    let main_expr = ast.do_rc_subst(main_expr, &rc);
+    assert_eq!(ast.to_string_sugar(main_expr, false), "3 * (1 + 2)");
+
+    let (mut ast, main_expr, rc) = first_step(program);
+    let main_expr = ast.do_rc_subst_replacing_equal(main_expr, &rc);
+    assert_eq!(ast.to_string_sugar(main_expr, false), "3 * 3");
+}
+
+#[test]
+fn persistent_step_leaves_term() {
+    let program = r#"
+    main :: Int
+    main = (1 + 2) * (1 + 2)
+    "#;
+
+    let (mut ast, main_expr, rc) = first_step(program);
+    let after = ast.do_rc_subst_persistent(main_expr, &rc);
+    assert_eq!(ast.to_string_sugar(main_expr, false), "(1 + 2) * (1 + 2)");
+    assert_eq!(ast.to_string_sugar(after, false), "3 * (1 + 2)");
+    // Only the path down to the redex is copied
+    assert_eq!(ast.get_arg(after), ast.get_arg(main_expr));
+}
+
+#[test]
+fn unused_runtime_error_is_not_evaluated() {
+    let program = r#"
+    main :: Int
+    main = (\x y. x) 1 (1 / 0)
+    "#;
+
+    assert_eq!(lazy_run_test(program), "1");
+}
+
+#[test]
+fn undefined_is_bottom() {
+    let program = r#"
+    fst :: (a, b) -> a
+    fst (x, _) = x
+
+    snd :: (a, b) -> b
+    snd (_, y) = y
+
+    main :: Int
+    main = fst (1, undefined)
+    "#;
+    assert_eq!(lazy_run_test(program), "1");
+
+    let program = r#"
+    main :: Int
+    main = length (Cons undefined (Cons undefined Nil))
+    "#;
+    assert_eq!(lazy_run_test(program), "2");
+
+    let program = r#"
+    snd :: (a, b) -> b
+    snd (_, y) = y
+
+    main :: Int
+    main = snd (1, undefined)
+    "#;
+    assert_eq!(lazy_run_test(program), "<error: undefined>");
+}
+
+#[test]
+fn preservation_examples() {
+    let path = format!("{}/../examples", env!("CARGO_MANIFEST_DIR"));
+    for entry in std::fs::read_dir(path).unwrap() {
+        let program = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        let pr = Parser::from_string(program.clone())
+            .parse_module(true)
+            .unwrap();
+        let mut ast = pr.ast;
+        let mut lt = pr.lt;
+        let module = ast.root;
+        if typecheck(&mut ast, module, &mut lt, &pr.tm).is_ok() {
+            // Without sharing, collatz takes thousands of steps, each checked in full
+            preservation_run_test(&program, 100).unwrap();
+        }
+    }
+}
+
+#[test]
+fn preservation_reports_ill_typed_step() {
+    let pr = Parser::from_string("main :: Int\nmain = 1 + 2".to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let module = ast.root;
+    typecheck(&mut ast, module, &mut lt, &pr.tm).unwrap();
+    let mut checker = PreservationChecker::new(&ast, &pr.tm);
+
+    // A broken step, which replaces 1 with true
+    let main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    let one = ast.get_arg(ast.get_func(main_expr));
+    let true_ast = Parser::from_string("main = true".to_string())
+        .parse_module(false)
+        .unwrap()
+        .ast;
+    let rc = RCPair {
+        from: one,
+        kind: RedexKind::Inbuilt,
+        to: true_ast.clone_node(true_ast.get_assign_exp(true_ast.get_main(true_ast.root).unwrap())),
+        msg_after: "Replaced 1".to_string(),
+        msg_before: "Replace 1".to_string(),
+        under_lambdas: false,
+    };
+
+    let e = checker.do_rc_subst(&mut ast, &lt, &rc).unwrap_err();
+    assert_eq!(e.step, 0);
+    assert_eq!(e.rc.from, one);
+    assert_eq!(e.before, "1 + 2");
+    assert_eq!(e.after, "true + 2");
+}
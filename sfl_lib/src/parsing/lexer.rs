@@ -27,6 +27,9 @@ pub struct Lexer {
     i: usize,
     pub line: usize,
     pub col: usize,
+    /// Where the last token returned started
+    pub token_line: usize,
+    pub token_col: usize,
 }
 
 impl Lexer {
@@ -39,6 +42,8 @@ impl Lexer {
             i: 0,
             line: 0,
             col: 0,
+            token_line: 0,
+            token_col: 0,
         }
     }
 
@@ -175,6 +180,31 @@ impl Lexer {
         }
     }
 
+    /// A pragma such as {-# OPAQUE map #-}, on one line, whose value is what is between the
+    /// braces
+    fn lex_pragma(&mut self) -> Result<Token, LexerError> {
+        let mut str = String::new();
+        for _ in 0..3 {
+            self.advance();
+        }
+
+        while !(self.c() == '#' && self.file[self.i + 1] == '-' && self.file[self.i + 2] == '}') {
+            if self.c() == '\n' || self.c() == '\0' {
+                return Err(self.error("Unterminated pragma".to_string()));
+            }
+            str.push(self.c());
+            self.advance();
+        }
+        for _ in 0..3 {
+            self.advance();
+        }
+
+        Ok(Token {
+            tt: TokenType::Pragma,
+            value: str.trim().to_string(),
+        })
+    }
+
     fn lex_char_lit(&mut self) -> Result<Token, LexerError> {
         let mut str = String::new();
 
@@ -243,6 +273,8 @@ impl Lexer {
                 self.advance();
             }
         }
+        self.token_line = self.line;
+        self.token_col = self.col;
         let c = self.c();
 
         match c {
@@ -356,6 +388,9 @@ impl Lexer {
                     value: ")".to_string(),
                 })
             }
+            '{' if self.file[self.i + 1] == '-' && self.file[self.i + 2] == '#' => {
+                self.lex_pragma()
+            }
             '{' => {
                 self.advance();
                 Ok(Token {
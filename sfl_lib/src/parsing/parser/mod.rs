@@ -12,10 +12,16 @@ mod parse_match;
 mod parse_types;
 
 pub struct Parser {
-    t_queue: VecDeque<Token>,
+    t_queue: VecDeque<(Token, usize, usize)>,
     lexer: Lexer,
     type_assignment_map: HashMap<String, Type>,
     bound: HashSet<String>,
+    /// Identifiers used before they are bound, with their positions. Labels may be defined
+    /// later in the module, so these are only errors if they are still unbound at the end
+    forward_references: Vec<(String, usize, usize)>,
+    /// Labels named by OPAQUE pragmas, with their positions, made opaque once every label in
+    /// the module is known
+    opaque: Vec<(String, usize, usize)>,
 }
 
 pub struct ParserError {
@@ -76,6 +82,8 @@ impl Parser {
             lexer: Lexer::new(contents, Some(filename)),
             bound: HashSet::new(),
             type_assignment_map: HashMap::new(),
+            forward_references: vec![],
+            opaque: vec![],
         })
     }
 
@@ -88,6 +96,8 @@ impl Parser {
                 .cloned()
                 .collect(),
             type_assignment_map: HashMap::new(),
+            forward_references: vec![],
+            opaque: vec![],
         }
     }
 
@@ -145,6 +155,19 @@ impl Parser {
         self.bound.remove(name);
     }
 
+    fn check_forward_references(&self) -> Result<(), ParserError> {
+        for (name, line, col) in &self.forward_references {
+            if !self.bound.contains(name) {
+                return Err(ParserError {
+                    e: format!("Unbound identifier: {}", name),
+                    line: *line,
+                    col: *col,
+                });
+            }
+        }
+        Ok(())
+    }
+
     fn parse_error(&self, msg: String) -> ParserError {
         ParserError {
             e: msg,
@@ -157,7 +180,8 @@ impl Parser {
     #[inline(always)]
     fn queue_tk(&mut self) -> Result<(), ParserError> {
         let t = self.lexer.get_token()?;
-        self.t_queue.push_back(t);
+        self.t_queue
+            .push_back((t, self.lexer.token_line, self.lexer.token_col));
 
         Ok(())
     }
@@ -173,7 +197,13 @@ impl Parser {
         while n >= self.t_queue.len() {
             self.queue_tk()?;
         }
-        Ok(self.t_queue[n].clone())
+        Ok(self.t_queue[n].0.clone())
+    }
+
+    // Get the line and column the nth token starts at, without advancing
+    fn peek_pos(&mut self, n: usize) -> Result<(usize, usize), ParserError> {
+        self.peek(n)?;
+        Ok((self.t_queue[n].1, self.t_queue[n].2))
     }
 
     // Get 0th token and advance
@@ -184,6 +214,28 @@ impl Parser {
         peek_result
     }
 
+    /// {-# OPAQUE label ... #-} makes each label opaque, so that its calls are evaluated in one
+    /// step
+    fn parse_pragma(&mut self, pragma: &str) -> Result<(), ParserError> {
+        let (line, col) = self.peek_pos(0)?;
+        let mut words = pragma.split(|c: char| c.is_whitespace() || c == ',');
+        match words.next() {
+            Some("OPAQUE") => {
+                let labels: Vec<&str> = words.filter(|word| !word.is_empty()).collect();
+                if labels.is_empty() {
+                    return Err(self.parse_error("OPAQUE pragma names no labels".to_string()));
+                }
+                self.opaque.extend(
+                    labels
+                        .into_iter()
+                        .map(|label| (label.to_string(), line, col)),
+                );
+                Ok(())
+            }
+            _ => Err(self.parse_error(format!("Unknown pragma: {}", pragma))),
+        }
+    }
+
     fn init_parser(&mut self, with_prelude: bool) -> (KnownTypeLabelTable, TypeMap, AST) {
         if with_prelude {
             let mut parser = Self::from_string(PRELUDE.to_string());
@@ -194,7 +246,9 @@ impl Parser {
             for binding in parser.bound {
                 self.bind(binding);
             }
-            (pr.lt, pr.tm, pr.ast)
+            let mut ast = pr.ast;
+            ast.mark_prelude();
+            (pr.lt, pr.tm, ast)
         } else {
             let mut ast = AST::new();
             let module = ast.add_module(Vec::new(), self.lexer.line, self.lexer.col);
@@ -210,6 +264,7 @@ impl Parser {
     ) -> Result<usize, ParserError> {
         let ass_tk = self.peek(0)?;
         assert!(ass_tk.tt == TokenType::Id);
+        let (ass_line, ass_col) = self.peek_pos(0)?;
 
         let name = ass_tk.value.clone();
 
@@ -239,7 +294,7 @@ impl Parser {
             }
         };
 
-        let id = ast.add_id(ass_tk, self.lexer.line, self.lexer.col);
+        let id = ast.add_id(ass_tk, ass_line, ass_col);
 
         // Ignore if type assignment is not found, so the typechecker will have to infer
         let type_assignment = match self.get_type_assignment(&name) {
@@ -307,6 +362,10 @@ impl Parser {
                         self.bind(constructor_name);
                     }
                 }
+                TokenType::Pragma => {
+                    self.parse_pragma(&t.value)?;
+                    self.advance();
+                }
                 TokenType::Newline => {
                     self.advance();
                 }
@@ -317,6 +376,15 @@ impl Parser {
             }
         }
 
+        self.check_forward_references()?;
+        for (name, line, col) in &self.opaque {
+            lt.set_opaque(name).map_err(|e| ParserError {
+                e,
+                line: *line,
+                col: *col,
+            })?;
+        }
+
         if with_prelude && !main_found {
             return Err(self.parse_error(
                 "Assignment to 'main' is missing. This is the programs entry point.".to_string(),
@@ -330,6 +398,7 @@ impl Parser {
     pub fn parse_tl_expression(&mut self, with_prelude: bool) -> Result<ParseResult, ParserError> {
         let (lt, tm, mut ast) = self.init_parser(with_prelude);
         ast.root = self.parse_expression(&mut ast, &tm.types)?;
+        self.check_forward_references()?;
         Ok(ParseResult { lt, tm, ast })
     }
 }
@@ -80,28 +80,59 @@ impl Parser {
         ast: &mut AST,
         type_table: &HashMap<String, Type>,
     ) -> Result<usize, ParserError> {
+        let (line, col) = self.peek_pos(0)?;
         let t = self.consume()?;
         match t.tt {
-            TokenType::Id => Ok(ast.add_id(t, self.lexer.line, self.lexer.col)),
+            TokenType::Id => Ok(ast.add_id(t, line, col)),
             TokenType::LParen => self.parse_abstr_var(ast, type_table),
             _ => Err(self.parse_error("Expected identifier (or '(') after lambda".to_string())),
         }
     }
 
+    // Parse the rest of an expression after an opening parenthesis, which may be annotated with a
+    // type: (e :: T)
+    fn parse_bracketed_expression(
+        &mut self,
+        ast: &mut AST,
+        type_table: &HashMap<String, Type>,
+    ) -> Result<usize, ParserError> {
+        let (line, col) = self.peek_pos(0)?;
+        let mut exp = self.parse_expression(ast, type_table)?;
+
+        if self.peek(0)?.tt == TokenType::DoubleColon {
+            self.advance();
+            let t = self.parse_type_expression(type_table, None)?;
+            exp = ast.add_annotation(exp, t, line, col);
+        }
+
+        match self.peek(0)?.tt {
+            TokenType::RParen => {
+                self.advance();
+                Ok(exp)
+            }
+            _ => {
+                let tk = self.peek(0)?;
+                Err(self.parse_error(format!("Expected closing parenthesis, got \"{:?}\"", tk)))
+            }
+        }
+    }
+
     // Parse a primary expression
     fn parse_expr_primary(
         &mut self,
         ast: &mut AST,
         type_table: &HashMap<String, Type>,
     ) -> Result<usize, ParserError> {
-        let line = self.lexer.line;
-        let col = self.lexer.col;
+        let (line, col) = self.peek_pos(0)?;
         let t = self.consume()?;
         match t.tt {
             TokenType::Id | TokenType::UppercaseId => {
                 let id_name = t.value.clone();
                 if !self.bound.contains(&id_name) {
-                    return Err(self.parse_error(format!("Unbound identifier: {}", id_name)));
+                    if t.tt != TokenType::Id || t.is_infix_id() {
+                        return Err(self.parse_error(format!("Unbound identifier: {}", id_name)));
+                    }
+                    self.forward_references.push((id_name, line, col));
                 }
                 Ok(ast.add_id(t, line, col))
             }
@@ -113,7 +144,8 @@ impl Parser {
                 self.advance();
                 Ok(self.parse_abstraction(ast, false, type_table)?.0)
             }
-            TokenType::LParen | TokenType::Dollar => {
+            TokenType::LParen => self.parse_bracketed_expression(ast, type_table),
+            TokenType::Dollar => {
                 let exp = self.parse_expression(ast, type_table)?;
                 self.advance();
                 Ok(exp)
@@ -122,10 +154,28 @@ impl Parser {
         }
     }
 
+    // Pairs bind loosest, so (1 + 2, 3) is a pair of a sum
     pub(super) fn parse_expression(
         &mut self,
         ast: &mut AST,
         type_table: &HashMap<String, Type>,
+    ) -> Result<usize, ParserError> {
+        let left = self.parse_application(ast, type_table)?;
+
+        let line = self.lexer.line;
+        let col = self.lexer.col;
+        if self.peek(0)?.tt == TokenType::Comma {
+            self.advance();
+            let right = self.parse_expression(ast, type_table)?;
+            return Ok(ast.add_pair(left, right, line, col));
+        }
+        Ok(left)
+    }
+
+    fn parse_application(
+        &mut self,
+        ast: &mut AST,
+        type_table: &HashMap<String, Type>,
     ) -> Result<usize, ParserError> {
         let mut left = self.parse_expr_primary(ast, type_table)?;
 
@@ -142,18 +192,7 @@ impl Parser {
                 // If paren, apply to paren
                 TokenType::LParen => {
                     self.advance();
-                    let right = self.parse_expression(ast, type_table)?;
-                    match self.peek(0)?.tt {
-                        TokenType::RParen => {
-                            self.advance();
-                        }
-                        _ => {
-                            return Err(self.parse_error(format!(
-                                "Expected closing parenthesis, got \"{:?}\"",
-                                tk
-                            )))
-                        }
-                    }
+                    let right = self.parse_bracketed_expression(ast, type_table)?;
                     left = ast.add_app(left, right, line, col, false);
                 }
 
@@ -173,16 +212,11 @@ impl Parser {
                 | TokenType::EOF
                 | TokenType::Newline
                 | TokenType::DoubleColon
-                | TokenType::LBrace => {
+                | TokenType::LBrace
+                | TokenType::Comma => {
                     return Ok(left);
                 }
 
-                TokenType::Comma => {
-                    self.advance();
-                    let right = self.parse_expression(ast, type_table)?;
-                    left = ast.add_pair(left, right, line, col);
-                }
-
                 TokenType::Lambda => {
                     self.advance();
                     self.parse_abstraction(ast, false, type_table)?.0;
@@ -205,7 +239,7 @@ impl Parser {
                 TokenType::Id | TokenType::UppercaseId => {
                     if self.peek(0)?.is_infix_id() {
                         let id_node = self.parse_expr_primary(ast, type_table)?;
-                        let right = self.parse_expression(ast, type_table)?;
+                        let right = self.parse_application(ast, type_table)?;
                         left = ast.add_app(id_node, left, line, col, false);
                         left = ast.add_app(left, right, line, col, false);
                     } else {
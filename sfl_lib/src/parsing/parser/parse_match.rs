@@ -75,8 +75,7 @@ impl Parser {
         unpack: bool,
         bound_set: &'a mut HashSet<String>,
     ) -> Result<(usize, &'a mut HashSet<String>), ParserError> {
-        let line = self.lexer.line;
-        let col = self.lexer.col;
+        let (line, col) = self.peek_pos(0)?;
         let t = self.consume()?;
         match t.tt {
             TokenType::Id | TokenType::UppercaseId => {
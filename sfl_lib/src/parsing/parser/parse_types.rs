@@ -209,6 +209,28 @@ impl Parser {
         &mut self,
         type_table: &HashMap<String, Type>,
         bound_type_vars: Option<&HashSet<String>>,
+    ) -> Result<Type, ParserError> {
+        let left = self.parse_function_type(type_table, bound_type_vars)?;
+
+        // Pairs bind loosest, so (a -> b, c) is a pair of a function
+        if self.peek(0)?.tt == TokenType::Comma {
+            self.advance();
+            match left.parser_error_if_incomplete() {
+                Ok(()) => {}
+                Err(s) => return Err(self.parse_error(s)),
+            }
+            return Ok(Type::pr(
+                left,
+                self.parse_type_expression(type_table, bound_type_vars)?,
+            ));
+        }
+        Ok(left)
+    }
+
+    fn parse_function_type(
+        &mut self,
+        type_table: &HashMap<String, Type>,
+        bound_type_vars: Option<&HashSet<String>>,
     ) -> Result<Type, ParserError> {
         let mut left = self.parse_type_expression_primary(type_table, bound_type_vars)?;
 
@@ -218,7 +240,7 @@ impl Parser {
             match next.tt {
                 TokenType::RArrow => {
                     self.advance();
-                    let right = self.parse_type_expression(type_table, bound_type_vars)?;
+                    let right = self.parse_function_type(type_table, bound_type_vars)?;
 
                     match left.parser_error_if_incomplete() {
                         Ok(()) => {}
@@ -228,18 +250,6 @@ impl Parser {
                     left = Type::Function(Box::new(left), Box::new(right));
                 }
 
-                TokenType::Comma => {
-                    self.advance();
-                    match left.parser_error_if_incomplete() {
-                        Ok(()) => {}
-                        Err(s) => return Err(self.parse_error(s)),
-                    }
-                    left = Type::pr(
-                        left,
-                        self.parse_type_expression(type_table, bound_type_vars)?,
-                    );
-                }
-
                 TokenType::UppercaseId | TokenType::Id | TokenType::LParen => {
                     match next.tt {
                         // If this is in an abstraction, and the next token is a double colon, then we're done because
@@ -260,6 +270,7 @@ impl Parser {
                 }
 
                 TokenType::RParen
+                | TokenType::Comma
                 | TokenType::Newline
                 | TokenType::EOF
                 | TokenType::Dot
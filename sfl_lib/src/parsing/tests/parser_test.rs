@@ -1,4 +1,5 @@
 use crate::parsing::*;
+use crate::{ASTNodeType, Type, AST};
 
 #[test]
 fn assign() -> Result<(), ParserError> {
@@ -100,6 +101,10 @@ fn bound() -> Result<(), ParserError> {
         .parse_module(false)
         .is_err());
 
+    // y is defined later in the module
+    let str = "x = add 2 y\ny = 3";
+    Parser::from_string(str.to_string()).parse_module(false)?;
+
     Ok(())
 }
 
@@ -236,6 +241,81 @@ fn pair() -> Result<(), ParserError> {
     Ok(())
 }
 
+fn parse_assign_exp(program_str: &str) -> Result<(AST, usize), ParserError> {
+    let ast = Parser::from_string(program_str.to_string())
+        .parse_module(false)?
+        .ast;
+    let x = ast.get_assign_exp(ast.get_assign_to(ast.root, "x".to_string()).unwrap());
+    Ok((ast, x))
+}
+
+#[test]
+fn pair_binds_loosest() -> Result<(), ParserError> {
+    // Each side of a pair is a whole application or infix expression
+    unchanged_parse_output_str_test("x = (1 + 2, 3 * 4)", false)?;
+    let (ast, x) = parse_assign_exp("f y = y\nx = (1 + 2, f 3)")?;
+    assert_eq!(ast.get(x).t, ASTNodeType::Pair);
+    assert_eq!(ast.to_string_sugar(ast.get_first(x), false), "1 + 2");
+    assert_eq!(ast.to_string_sugar(ast.get_second(x), false), "f 3");
+
+    // Pairs nest to the right
+    let (ast, x) = parse_assign_exp("x = (1, 2, 3)")?;
+    assert_eq!(ast.get(ast.get_second(x)).t, ASTNodeType::Pair);
+
+    // An abstraction still takes the rest of the expression, so one inside a pair is bracketed
+    let (ast, x) = parse_assign_exp("x = (\\y. y, 1)")?;
+    assert_eq!(ast.get(x).t, ASTNodeType::Abstraction);
+    unchanged_parse_output_str_test("x = ((\\y. y), 1)", false)?;
+    Ok(())
+}
+
+#[test]
+fn pair_type_binds_loosest() -> Result<(), ParserError> {
+    let str = "x :: (Int -> Int, Bool)\nx = ((\\y. y), true)";
+    let ast = Parser::from_string(str.to_string())
+        .parse_module(false)?
+        .ast;
+    assert_eq!(ast.to_string_sugar(ast.root, true), str);
+
+    let assign = ast.get_assign_to(ast.root, "x".to_string()).unwrap();
+    assert_eq!(
+        ast.get(assign).type_assignment,
+        Some(Type::pr(
+            Type::f(Type::int64(), Type::int64()),
+            Type::bool()
+        ))
+    );
+
+    let str = "x :: (Bool, Int -> Int)\nx = (true, (\\y. y))";
+    let ast = Parser::from_string(str.to_string())
+        .parse_module(false)?
+        .ast;
+    let assign = ast.get_assign_to(ast.root, "x".to_string()).unwrap();
+    assert_eq!(
+        ast.get(assign).type_assignment,
+        Some(Type::pr(
+            Type::bool(),
+            Type::f(Type::int64(), Type::int64())
+        ))
+    );
+    Ok(())
+}
+
+#[test]
+fn inline_annotation() -> Result<(), ParserError> {
+    unchanged_parse_output_str_test("x = (5 :: Int)", false)?;
+    unchanged_parse_output_str_test("f x = x\ny = f (5 :: Int)", false)?;
+    unchanged_parse_output_str_test("f = (\\x. x :: Int -> Int) 5", false)?;
+    unchanged_parse_output_str_test("f = (1 + 2 :: Int) * 3", false)?;
+
+    let str = "x = (5 :: Int :: Int)";
+    assert!(Parser::from_string(str.to_string())
+        .parse_module(false)
+        .is_err());
+
+    Ok(())
+}
+
 #[test]
 fn type_decl() -> Result<(), ParserError> {
     let str = "type Bingus = Int\nmain :: Bingus -> Int\nmain = \\x.x";
@@ -460,3 +540,25 @@ fn parse_match_fold() -> Result<(), ParserError> {
 
     Ok(())
 }
+
+#[test]
+fn opaque_pragma() -> Result<(), ParserError> {
+    let program = "{-# OPAQUE map, double #-}\ndouble :: Int -> Int\ndouble x = x + x\nmain :: Int\nmain = double 2";
+    let pr = Parser::from_string(program.to_string()).parse_module(true)?;
+    assert!(pr.lt.is_opaque("map"));
+    assert!(pr.lt.is_opaque("double"));
+    assert!(!pr.lt.is_opaque("main"));
+
+    for bad in [
+        "{-# OPAQUE nope #-}\nmain :: Int\nmain = 1",
+        "{-# OPAQUE #-}\nmain :: Int\nmain = 1",
+        "{-# INLINE map #-}\nmain :: Int\nmain = 1",
+        "{-# OPAQUE Cons #-}\nmain :: Int\nmain = 1",
+        "{-# OPAQUE map\nmain :: Int\nmain = 1",
+    ] {
+        assert!(Parser::from_string(bad.to_string())
+            .parse_module(true)
+            .is_err());
+    }
+    Ok(())
+}
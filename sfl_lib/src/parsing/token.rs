@@ -23,6 +23,7 @@ pub enum TokenType {
     Forall,
     KWType,
     KWData,
+    Pragma,
 
     LParen,
     RParen,
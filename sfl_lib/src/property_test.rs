@@ -0,0 +1,126 @@
+use super::generator::{GeneratedModule, Generator};
+use super::*;
+use crate::parsing::TypeMap;
+use crate::test_utils::{parse_and_typecheck, parse_and_typecheck_with_types};
+
+use std::ops::Range;
+
+/// The seeds checked by every test run. Typechecking each step is slow, so the rest are only
+/// checked by the ignored sweeps, run with cargo test -- --ignored
+const SEEDS: Range<u64> = 0..2;
+const SWEEP_SEEDS: Range<u64> = 2..40;
+
+/// Reduction stops after this many steps. Generated programs need fewer than 50
+const MAX_STEPS: usize = 200;
+
+/// The labels of the module that aren't from the prelude, printed with their types
+fn print_labels(ast: &AST) -> String {
+    ast.get_assignee_names(ast.root)
+        .into_iter()
+        .map(|name| ast.get_assign_to(ast.root, name).unwrap())
+        .filter(|assign| !ast.is_from_prelude(*assign))
+        .map(|assign| ast.to_string_sugar(assign, true) + "\n")
+        .collect()
+}
+
+/// Reduce main to normal form, picking each step with strategy, and checking each preserves main's
+/// type. Gives main's final value
+fn reduce(
+    module: &GeneratedModule,
+    typed: &(AST, KnownTypeLabelTable, TypeMap),
+    strategy: &mut dyn EvaluationStrategy,
+) -> String {
+    let src = module.source();
+    let (ast, lt, tm) = typed;
+    let (mut ast, lt) = (ast.clone(), lt.clone());
+    let mut checker = PreservationChecker::new(&ast, tm);
+
+    let mut main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    for _ in 0..MAX_STEPS {
+        let rc = match strategy.next_redex(&ast, Some(ast.root), main_expr, &lt) {
+            Some(rc) => rc,
+            None => {
+                // Progress: a well-typed Int or Bool can only be stuck once it is a literal
+                assert_eq!(
+                    ast.get(main_expr).t,
+                    ASTNodeType::Literal,
+                    "{} is stuck in\n{}",
+                    ast.to_string_sugar(main_expr, false),
+                    src
+                );
+                return ast.to_string_sugar(main_expr, false);
+            }
+        };
+        let step = if strategy.shares_arguments() {
+            checker.do_rc_subst_shared(&mut ast, &lt, &rc)
+        } else {
+            checker.do_rc_subst(&mut ast, &lt, &rc)
+        };
+        step.unwrap_or_else(|e| panic!("{:?} in\n{}", e, src));
+        ast.collect_garbage();
+        main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+    }
+    panic!("No normal form after {} steps in\n{}", MAX_STEPS, src)
+}
+
+fn check_round_trip(seeds: Range<u64>) {
+    for seed in seeds {
+        let module = Generator::new(seed).module();
+        let (ast, _) = parse_and_typecheck(&module.source());
+        let printed = print_labels(&ast);
+
+        // Printing is a fixed point of parsing then printing
+        let (reparsed, _) = parse_and_typecheck(&format!("{}\n{}", module.data, printed));
+        assert_eq!(print_labels(&reparsed), printed, "seed {}", seed);
+    }
+}
+
+// Each step is checked for preservation, and the normal form for progress, whichever redex is
+// picked. Confluence means every choice reaches the same value, and the interpreter must agree
+fn check_same_value(seeds: Range<u64>) {
+    for seed in seeds {
+        let module = Generator::new(seed).module();
+        let typed = parse_and_typecheck_with_types(&module.source());
+        let lazy_value = reduce(&module, &typed, &mut NormalOrder);
+
+        let (ast, lt, _) = &typed;
+        let main_expr = ast.get_assign_exp(ast.get_main(ast.root).unwrap());
+        let value = evaluate(ast, ast.root, main_expr, lt);
+        assert_eq!(
+            lazy_value,
+            value.to_string_sugar(value.root, false),
+            "interpreter {}\n{}",
+            seed,
+            module.source()
+        );
+
+        // Generated programs don't recurse, so even applicative order terminates
+        let random = format!("random:{}", seed);
+        for name in ["need", "applicative", "innermost-rightmost", &random] {
+            let value = reduce(&module, &typed, strategy_from_name(name).unwrap().as_mut());
+            assert_eq!(lazy_value, value, "{} {}\n{}", name, seed, module.source());
+        }
+    }
+}
+
+#[test]
+fn generated_modules_round_trip() {
+    check_round_trip(SEEDS);
+}
+
+#[test]
+fn generated_modules_reduce_to_the_same_value() {
+    check_same_value(SEEDS);
+}
+
+#[test]
+#[ignore]
+fn generated_modules_round_trip_sweep() {
+    check_round_trip(SWEEP_SEEDS);
+}
+
+#[test]
+#[ignore]
+fn generated_modules_reduce_to_the_same_value_sweep() {
+    check_same_value(SWEEP_SEEDS);
+}
@@ -0,0 +1,22 @@
+use crate::parsing::TypeMap;
+use crate::{typecheck, KnownTypeLabelTable, Parser, AST};
+
+/// Parse a program with the prelude and typecheck it, as every stepping test starts by doing.
+/// The module is the AST's root
+pub(crate) fn parse_and_typecheck(program: &str) -> (AST, KnownTypeLabelTable) {
+    let (ast, lt, _) = parse_and_typecheck_with_types(program);
+    (ast, lt)
+}
+
+/// Like parse_and_typecheck, also giving the types the program declares
+pub(crate) fn parse_and_typecheck_with_types(program: &str) -> (AST, KnownTypeLabelTable, TypeMap) {
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap_or_else(|e| panic!("{:?} in\n{}", e, program));
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let module = ast.root;
+    typecheck(&mut ast, module, &mut lt, &pr.tm)
+        .unwrap_or_else(|e| panic!("{}in\n{}", e.render(program), program));
+    (ast, lt, pr.tm)
+}
@@ -1,3 +1,5 @@
+use super::recorder::Recorder;
+use super::synthesis::{synthesize_type, synthesize_type_recorded};
 use super::*;
 use crate::parsing::TypeMap;
 use crate::{ASTNodeType, Type, AST};
@@ -10,6 +12,54 @@ pub fn check_type(
     expr: usize,
     type_map: &TypeMap,
     is_pattern: bool,
+    rec: &mut Recorder,
+) -> Result<Context, TypeError> {
+    check_type_recorded(c, expected, ast, expr, type_map, is_pattern, rec).map_err(|e| {
+        e.note(ast, expr, |expr_str| match expected {
+            Type::Existential(_) => format!("while checking `{}`", expr_str),
+            _ => format!(
+                "while checking `{}` against {}",
+                expr_str,
+                expected.tv_ify()
+            ),
+        })
+    })
+}
+
+// check_type, without noting the expression on an error
+fn check_type_recorded(
+    c: Context,
+    expected: &Type,
+    ast: &AST,
+    expr: usize,
+    type_map: &TypeMap,
+    is_pattern: bool,
+    rec: &mut Recorder,
+) -> Result<Context, TypeError> {
+    let result = rec.record(
+        c,
+        |c, rec| check_type_rule(c, expected, ast, expr, type_map, is_pattern, rec),
+        |result| {
+            (
+                format!("{} ⇐ {}", ast.to_string_sugar(expr, false), expected),
+                result.as_ref().ok(),
+            )
+        },
+    );
+    if result.is_ok() {
+        rec.typed(expr, expected);
+    }
+    result
+}
+
+fn check_type_rule(
+    c: Context,
+    expected: &Type,
+    ast: &AST,
+    expr: usize,
+    type_map: &TypeMap,
+    is_pattern: bool,
+    rec: &mut Recorder,
 ) -> Result<Context, TypeError> {
     let node = ast.get(expr);
 
@@ -24,13 +74,20 @@ pub fn check_type(
 
     match (expected, &node.t) {
         // Unit always checks
-        (Type::Unit, _) => Ok(c),
+        (Type::Unit, _) => {
+            rec.rule("1I");
+            Ok(c)
+        }
 
         // Follow Alias
-        (Type::Alias(_, type_), _) => check_type(c, &type_, ast, expr, type_map, is_pattern),
+        (Type::Alias(_, type_), _) => {
+            rec.rule("Alias");
+            check_type_recorded(c, &type_, ast, expr, type_map, is_pattern, rec)
+        }
 
         // Forall Introduction
         (Type::Forall(var, t), _) => {
+            rec.rule("∀I");
             let t = match t
                 .as_ref()
                 .clone()
@@ -42,30 +99,57 @@ pub fn check_type(
 
             let c = c.append(ContextItem::TypeVariable(var.clone()));
 
-            let pred = check_type(c, &t, ast, expr, type_map, is_pattern)?;
+            let pred = check_type_recorded(c, &t, ast, expr, type_map, is_pattern, rec)?;
+            rec.substitute_types(&pred);
             Ok(pred.get_before_item(ContextItem::TypeVariable(var.clone())))
         }
 
         // Arrow introduction
         (Type::Function(from, to), ASTNodeType::Abstraction) => {
+            rec.rule("→I");
             let var = ast.get_abstr_var(expr);
 
             let (c, before) = c.recurse_add_to_context(from, &ast, var)?;
-
-            let pred = check_type(c, to, ast, ast.get_abstr_expr(expr), type_map, is_pattern)?;
+            rec.typed(var, from);
+
+            let pred = check_type(
+                c,
+                to,
+                ast,
+                ast.get_abstr_expr(expr),
+                type_map,
+                is_pattern,
+                rec,
+            )?;
+            rec.substitute_types(&pred);
             Ok(pred.get_before_assignment(before))
         }
 
         (Type::Product(pt1, pt2), ASTNodeType::Pair) => {
-            let pair1 = check_type(c, pt1, ast, ast.get_first(expr), type_map, is_pattern)?;
-            let pair2 = check_type(pair1, pt2, ast, ast.get_second(expr), type_map, is_pattern)?;
+            rec.rule("×I");
+            let pair1 = check_type(c, pt1, ast, ast.get_first(expr), type_map, is_pattern, rec)?;
+            let pair2 = check_type(
+                pair1,
+                pt2,
+                ast,
+                ast.get_second(expr),
+                type_map,
+                is_pattern,
+                rec,
+            )?;
 
             Ok(pair2)
         }
 
+        (_, ASTNodeType::Match) => {
+            rec.rule("Match⇐");
+            check_match(c, expected, ast, expr, type_map, rec)
+        }
+
         // Sub
         _ => {
-            let (synth_t, c) = synthesize_type(c, ast, expr, type_map, is_pattern)?;
+            rec.rule("Sub");
+            let (synth_t, c) = synthesize_type_recorded(c, ast, expr, type_map, is_pattern, rec)?;
 
             #[cfg(debug_assertions)]
             let _c_str = format!("{:?}", &c);
@@ -76,7 +160,7 @@ pub fn check_type(
             let a = c.substitute(&synth_t);
             let b = c.substitute(&expected);
 
-            let st = subtype(c, &a, &b, type_map);
+            let st = subtype(c.blaming(expr), &a, &b, type_map, rec);
 
             match st {
                 Ok(new_c) => {
@@ -85,17 +169,74 @@ pub fn check_type(
 
                     Ok(new_c)
                 }
-                Err(e) => Err(type_error(
-                    format!(
-                        "Cannot figure out how {} could be subtype of {}: {}",
-                        a.tv_ify().to_string(),
-                        b.tv_ify().to_string(),
-                        e
-                    ),
-                    ast,
-                    expr,
-                )),
+                Err(e) => {
+                    let mut slice = c.explain(&synth_t);
+                    slice.extend(c.explain(expected));
+                    Err(TypeError::mismatch(b.tv_ify(), a.tv_ify(), e, ast, expr)
+                        .with_slice(ast, slice))
+                }
+            }
+        }
+    }
+}
+
+/// Check every case of a match against the expected type. The patterns are checked against the
+/// type of the matched expression which, if it is not known yet, is taken from the shape of the
+/// first pattern that has one
+pub(super) fn check_match(
+    c: Context,
+    expected: &Type,
+    ast: &AST,
+    expr: usize,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, TypeError> {
+    let unpack_expr = ast.get_match_unpack_pattern(expr);
+
+    let (unpack_type, mut c) = if let Some(t) = ast.get(unpack_expr).type_assignment.clone() {
+        let c = check_type(c, &t, ast, unpack_expr, type_map, false, rec)?;
+        (t, c)
+    } else {
+        synthesize_type(c, ast, unpack_expr, type_map, false, rec)?
+    };
+
+    // A polymorphic expression, such as Nil, is matched at an instance of its type
+    let mut unpack_type = unpack_type;
+    while let Type::Forall(var, t) = unpack_type {
+        let exst = c.get_next_existential_identifier();
+        c = c.append(ContextItem::Existential(exst, None));
+        unpack_type = t
+            .substitute_type_variable(&var, &Type::Existential(exst))
+            .unwrap();
+    }
+
+    let cases = ast.get_match_cases(expr);
+
+    if let Type::Existential(e) = c.substitute(&unpack_type) {
+        for (case_pat, _) in &cases {
+            c = c.blaming(*case_pat).articulate_pattern(e, ast, *case_pat);
+            if c.get_existential(e) != Some(None) {
+                break;
             }
         }
     }
+
+    for (case_pat, case_expr) in cases {
+        #[cfg(debug_assertions)]
+        let _pat_str = ast.to_string_sugar(case_pat, false);
+
+        // The pattern's bindings are only in scope for its case
+        let marker = c.get_next_existential_identifier();
+        let case_c = c
+            .append(ContextItem::Marker(marker))
+            .append(ContextItem::Existential(marker, None));
+
+        let case_c = check_type(case_c, &unpack_type, ast, case_pat, type_map, true, rec)?;
+        let case_c = check_type(case_c, expected, ast, case_expr, type_map, false, rec)?;
+
+        rec.substitute_types(&case_c);
+        c = case_c.get_before_item(ContextItem::Marker(marker));
+    }
+
+    Ok(c)
 }
@@ -1,6 +1,6 @@
 use crate::types::checker::type_error;
 use crate::{ASTNodeType, KnownTypeLabelTable, Type, TypeError, AST};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 #[derive(Clone, PartialEq, Eq)]
 pub enum ContextItem {
@@ -32,6 +32,14 @@ pub struct Context {
     vec: Vec<ContextItem>,
     next_exid: usize,
     next_placeholder_assignvar_i: usize,
+    /// The expressions whose checking solved each existential, or the existentials in its
+    /// solution. Kept after the existential leaves the context, so errors can still be explained
+    sources: HashMap<usize, BTreeSet<usize>>,
+    /// The expression that existentials solved now are blamed on
+    blame: Option<usize>,
+    /// Whether variables may be bound again, shadowing the outer binding. Programs can't do this,
+    /// but terms found by reducing them can
+    shadowing: bool,
 }
 
 impl std::fmt::Debug for Context {
@@ -69,23 +77,22 @@ impl Context {
             vec,
             next_exid: 0,
             next_placeholder_assignvar_i: 0,
+            sources: HashMap::new(),
+            blame: None,
+            shadowing: false,
         }
     }
 
-    pub fn assigns_only(&self) -> Self {
-        let mut vec = vec![];
-
-        for i in self.vec.iter() {
-            if let ContextItem::TypeAssignment(_, _) = i {
-                vec.push(i.clone());
-            }
-        }
-
-        Self {
-            vec,
-            next_exid: 0,
-            next_placeholder_assignvar_i: 0,
-        }
+    /// The items of the context as strings, leaving out the assignments of hidden labels
+    pub fn to_strings(&self, hidden: &HashSet<String>) -> Vec<String> {
+        self.vec
+            .iter()
+            .filter(|item| match item {
+                ContextItem::TypeAssignment(name, _) => !hidden.contains(name),
+                _ => true,
+            })
+            .map(|item| format!("{:?}", item))
+            .collect()
     }
 
     pub fn append(&self, item: ContextItem) -> Self {
@@ -127,30 +134,29 @@ impl Context {
             vec: new_v,
             next_exid: self.next_exid,
             next_placeholder_assignvar_i: self.next_placeholder_assignvar_i,
+            sources: self.sources.clone(),
+            blame: self.blame,
+            shadowing: self.shadowing,
         }
     }
     pub fn get_before_assignment(&self, str: String) -> Self {
         #[cfg(debug_assertions)]
         let _c_str = format!("{:?}", &self);
-        let mut new_v = vec![];
-
-        for i in &self.vec {
-            match i {
-                ContextItem::TypeAssignment(v, _) => {
-                    if v == &str {
-                        break;
-                    }
-                }
-                _ => {}
-            }
-
-            new_v.push(i.clone());
-        }
+        // The innermost assignment, in case str shadows another
+        let end = self
+            .vec
+            .iter()
+            .rposition(|i| matches!(i, ContextItem::TypeAssignment(v, _) if v == &str))
+            .unwrap_or(self.vec.len());
+        let new_v = self.vec[..end].to_vec();
 
         let new_s = Self {
             vec: new_v,
             next_exid: self.next_exid,
             next_placeholder_assignvar_i: self.next_placeholder_assignvar_i,
+            sources: self.sources.clone(),
+            blame: self.blame,
+            shadowing: self.shadowing,
         };
 
         #[cfg(debug_assertions)]
@@ -158,8 +164,9 @@ impl Context {
         new_s
     }
 
+    /// The type of the innermost assignment to var
     pub fn get_type_assignment(&self, var: &str) -> Option<Result<Type, TypeError>> {
-        for i in &self.vec {
+        for i in self.vec.iter().rev() {
             match i {
                 ContextItem::TypeAssignment(v, t) => {
                     if v == var {
@@ -207,6 +214,29 @@ impl Context {
             vec: new_v,
             next_exid,
             next_placeholder_assignvar_i,
+            sources: self.sources.clone(),
+            blame: self.blame,
+            shadowing: self.shadowing,
+        }
+    }
+
+    /// Allow variables to be bound again, shadowing their outer bindings
+    pub fn shadowing(&self) -> Self {
+        Self {
+            shadowing: true,
+            ..self.clone()
+        }
+    }
+
+    pub fn allows_shadowing(&self) -> bool {
+        self.shadowing
+    }
+
+    /// Blame the existentials solved from now on on expr
+    pub fn blaming(&self, expr: usize) -> Self {
+        Self {
+            blame: Some(expr),
+            ..self.clone()
         }
     }
 
@@ -216,6 +246,14 @@ impl Context {
         #[cfg(debug_assertions)]
         let _t_str = format!("{:?}", &t);
 
+        // The solution is explained by the expression being checked, and whatever explains the
+        // existentials it is made of
+        let mut sources = self.sources.clone();
+        let mut new_sources: BTreeSet<usize> = self.blame.into_iter().collect();
+        for e in t.ordered_existentials() {
+            new_sources.extend(self.sources.get(&e).into_iter().flatten());
+        }
+
         let mut new_v = vec![];
 
         for i in &self.vec {
@@ -225,6 +263,10 @@ impl Context {
                 ContextItem::Existential(e, Some(Type::Existential(e2))) => {
                     if *e2 == existential_being_set {
                         new_v.push(ContextItem::Existential(*e, Some(t.clone())));
+                        sources
+                            .entry(*e)
+                            .or_default()
+                            .extend(new_sources.iter().copied());
                         continue;
                     }
                 }
@@ -257,6 +299,10 @@ impl Context {
             }
             new_v.push(i.clone());
         }
+        sources
+            .entry(existential_being_set)
+            .or_default()
+            .extend(new_sources);
 
         #[cfg(debug_assertions)]
         let _new_v_str = format!("{:?}", new_v);
@@ -265,7 +311,41 @@ impl Context {
             vec: new_v,
             next_exid: self.next_exid,
             next_placeholder_assignvar_i: self.next_placeholder_assignvar_i,
+            sources,
+            blame: self.blame,
+            shadowing: self.shadowing,
+        }
+    }
+
+    /// The expressions that explain the existentials in t, following their solutions
+    pub fn explain(&self, t: &Type) -> BTreeSet<usize> {
+        let mut explained = BTreeSet::new();
+        let mut seen = HashSet::new();
+        let mut todo = t.ordered_existentials();
+
+        while let Some(e) = todo.pop() {
+            if !seen.insert(e) {
+                continue;
+            }
+            explained.extend(self.sources.get(&e).into_iter().flatten());
+            if let Some(Some(solution)) = self.get_existential(e) {
+                todo.extend(solution.ordered_existentials());
+            }
+        }
+
+        explained
+    }
+
+    /// Whether existential a is declared to the left of existential b
+    pub fn is_declared_before(&self, a: usize, b: usize) -> bool {
+        for i in &self.vec {
+            match i {
+                ContextItem::Existential(e, _) if *e == a => return true,
+                ContextItem::Existential(e, _) if *e == b => return false,
+                _ => {}
+            }
         }
+        false
     }
 
     pub fn get_next_existential_identifier(&self) -> usize {
@@ -347,7 +427,7 @@ impl Context {
             (Type::Alias(_, t), _) => self.recurse_add_to_context(t, ast, expr),
             (_, ASTNodeType::Identifier) => {
                 let mut var_name = ast.get(expr).get_value();
-                if self.get_type_assignment(var_name.as_str()).is_some() {
+                if !self.shadowing && self.get_type_assignment(var_name.as_str()).is_some() {
                     return Err(type_error(
                         format!(
                             "Type of {} is defined elsewhere, so cannot rebind",
@@ -388,7 +468,7 @@ impl Context {
                 #[cfg(debug_assertions)]
                 let _c_str2 = format!("{:?}", &c);
 
-                let c = c.set_existential_definition(
+                let c = c.blaming(expr).set_existential_definition(
                     *e,
                     Type::pr(Type::Existential(pt1), Type::Existential(pt2)),
                 );
@@ -397,4 +477,51 @@ impl Context {
             _ => Err(type_error("recurse add issue".to_string(), ast, expr)),
         }
     }
+
+    /// Solve an unsolved existential to the shape of a pattern it is matched against: the type a
+    /// constructor builds, or a pair. The existentials of the shape's parts are declared before e,
+    /// so they stay in scope once the pattern's own bindings are dropped. Anything else, such as a
+    /// variable or a literal, leaves e as it is
+    pub fn articulate_pattern(&self, e: usize, ast: &AST, pattern: usize) -> Context {
+        let head = ast.get_app_head(pattern);
+        let pn = ast.get(head);
+
+        match pn.t {
+            ASTNodeType::Identifier if pn.is_uppercase() => {
+                let constructor_type = match self.get_type_assignment(&pn.get_value()) {
+                    Some(Ok(t)) => t,
+                    _ => return self.clone(),
+                };
+
+                let mut c = self.clone();
+                let mut built = constructor_type;
+                while let Type::Forall(var, t) = built {
+                    let exst = c.get_next_existential_identifier();
+                    c = c.add_before_existential(e, ContextItem::Existential(exst, None));
+                    built = match t.substitute_type_variable(&var, &Type::Existential(exst)) {
+                        Ok(t) => t,
+                        Err(_) => return self.clone(),
+                    };
+                }
+                let built = built.flatten().pop().unwrap();
+
+                c.set_existential_definition(e, built)
+            }
+            ASTNodeType::Pair => {
+                let pt1 = self.get_next_existential_identifier();
+                let pt2 = self.get_next_existential_identifier() + 1;
+
+                let c = self.add_before_existential(e, ContextItem::Existential(pt1, None));
+                let c = c.add_before_existential(e, ContextItem::Existential(pt2, None));
+                let c = c.set_existential_definition(
+                    e,
+                    Type::pr(Type::Existential(pt1), Type::Existential(pt2)),
+                );
+
+                let c = c.articulate_pattern(pt1, ast, ast.get_first(pattern));
+                c.articulate_pattern(pt2, ast, ast.get_second(pattern))
+            }
+            _ => self.clone(),
+        }
+    }
 }
@@ -0,0 +1,87 @@
+use crate::AST;
+use std::collections::{HashMap, HashSet};
+
+struct Tarjan<'a> {
+    edges: &'a HashMap<String, Vec<String>>,
+    index: HashMap<String, usize>,
+    lowlink: HashMap<String, usize>,
+    stack: Vec<String>,
+    on_stack: HashSet<String>,
+    sccs: Vec<Vec<String>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, v: &String) {
+        let i = self.index.len();
+        self.index.insert(v.clone(), i);
+        self.lowlink.insert(v.clone(), i);
+        self.stack.push(v.clone());
+        self.on_stack.insert(v.clone());
+
+        for w in &self.edges[v] {
+            if !self.index.contains_key(w) {
+                self.visit(w);
+                let low = std::cmp::min(self.lowlink[v], self.lowlink[w]);
+                self.lowlink.insert(v.clone(), low);
+            } else if self.on_stack.contains(w) {
+                let low = std::cmp::min(self.lowlink[v], self.index[w]);
+                self.lowlink.insert(v.clone(), low);
+            }
+        }
+
+        if self.lowlink[v] == self.index[v] {
+            let mut scc = vec![];
+            loop {
+                let w = self.stack.pop().unwrap();
+                self.on_stack.remove(&w);
+                let done = &w == v;
+                scc.push(w);
+                if done {
+                    break;
+                }
+            }
+            scc.reverse();
+            self.sccs.push(scc);
+        }
+    }
+}
+
+/// Group the labels in the module without type assignments into strongly connected components
+/// of the "refers to" relation, so that mutually recursive labels can be inferred together.
+/// Labels with type assignments are left out, as their types are already known. Components
+/// are returned in dependency order, so each only refers to the components before it.
+pub fn unannotated_sccs(ast: &AST, module: usize) -> Vec<Vec<String>> {
+    let assigns = ast.get_assigns_map(module);
+    let unannotated: Vec<String> = ast
+        .get_assignee_names(module)
+        .into_iter()
+        .filter(|name| ast.get(assigns[name]).type_assignment.is_none())
+        .collect();
+
+    let mut edges = HashMap::new();
+    for name in &unannotated {
+        let free = ast.get_free_identifiers(ast.get_assign_exp(assigns[name]));
+        let deps = unannotated
+            .iter()
+            .filter(|other| free.contains(*other))
+            .cloned()
+            .collect::<Vec<_>>();
+        edges.insert(name.clone(), deps);
+    }
+
+    let mut tarjan = Tarjan {
+        edges: &edges,
+        index: HashMap::new(),
+        lowlink: HashMap::new(),
+        stack: vec![],
+        on_stack: HashSet::new(),
+        sccs: vec![],
+    };
+    for name in &unannotated {
+        if !tarjan.index.contains_key(name) {
+            tarjan.visit(name);
+        }
+    }
+
+    tarjan.sccs
+}
@@ -0,0 +1,107 @@
+use super::context::Context;
+use crate::KnownTypeLabelTable;
+use std::collections::HashSet;
+
+/// One rule application in a typing derivation, with the derivations of its premises
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Derivation {
+    /// The rule's name, as in the paper: "Var", "Sub", "→I", "InstLArr", ...
+    pub rule: String,
+    /// The judgement the rule concludes, without its contexts, such as "x ⇒ Int"
+    pub judgement: String,
+    pub input: Vec<String>,
+    /// The output context, or None if the rule failed
+    pub output: Option<Vec<String>>,
+    pub children: Vec<Derivation>,
+}
+
+impl Derivation {
+    /// The derivation in pre-order, with the depth of each rule application
+    pub fn flatten(&self) -> Vec<(usize, &Derivation)> {
+        let mut v = vec![];
+        self.flatten_into(0, &mut v);
+        v
+    }
+
+    fn flatten_into<'a>(&'a self, depth: usize, v: &mut Vec<(usize, &'a Derivation)>) {
+        v.push((depth, self));
+        for child in &self.children {
+            child.flatten_into(depth + 1, v);
+        }
+    }
+}
+
+/// Builds derivations as the checker applies its rules. A disabled recorder does nothing, so
+/// normal typechecking doesn't pay for it
+pub struct DerivationRecorder {
+    enabled: bool,
+    /// Rule applications that are still being derived, innermost last
+    stack: Vec<Derivation>,
+    /// The derivation of each label checked so far
+    pub derivations: Vec<(String, Derivation)>,
+    /// Inbuilts are in every context, so they are left out of the recorded ones
+    inbuilts: HashSet<String>,
+}
+
+impl DerivationRecorder {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            stack: vec![],
+            derivations: vec![],
+            inbuilts: HashSet::new(),
+        }
+    }
+
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            inbuilts: KnownTypeLabelTable::new().func_map.into_keys().collect(),
+            ..Self::disabled()
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Start recording a rule applied to c, as a premise of the rule currently being applied
+    pub(super) fn begin(&mut self, c: &Context) {
+        self.stack.push(Derivation {
+            rule: String::new(),
+            judgement: String::new(),
+            input: c.to_strings(&self.inbuilts),
+            output: None,
+            children: vec![],
+        });
+    }
+
+    /// Finish the rule started last, with the judgement it concludes and its output context
+    pub(super) fn end(&mut self, judgement: String, output: Option<&Context>) {
+        let mut d = self.stack.pop().unwrap();
+        d.judgement = judgement;
+        d.output = output.map(|c| c.to_strings(&self.inbuilts));
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(d),
+            None => self.derivations.push((String::new(), d)),
+        }
+    }
+
+    /// Name the rule currently being applied
+    pub(super) fn rule(&mut self, rule: &str) {
+        if let Some(d) = self.stack.last_mut() {
+            d.rule = rule.to_string();
+        }
+    }
+
+    /// Label the derivations recorded since the last label
+    pub(super) fn finish_label(&mut self, name: &str) {
+        for (label, _) in self.derivations.iter_mut().rev() {
+            if !label.is_empty() {
+                break;
+            }
+            *label = name.to_string();
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{Type, AST};
+
+pub(super) type Span = ((usize, usize), (usize, usize));
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub e: String,
+    pub line: usize,
+    pub col: usize,
+    /// Where the offending expression ends, just after its last token
+    pub end_line: usize,
+    pub end_col: usize,
+    /// The type the expression was checked against, if the error is a mismatch
+    pub expected: Option<Box<Type>>,
+    /// The type the expression was found to have, if the error is a mismatch
+    pub actual: Option<Box<Type>>,
+    /// The label whose definition the error is in
+    pub definition: Option<String>,
+    /// "while checking ..." notes, innermost first
+    pub notes: Vec<String>,
+    /// The spans of the expressions that decided the conflicting types
+    slice: Vec<Span>,
+}
+
+impl std::fmt::Debug for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "Type Error at [{}:{}]: {}",
+            self.line + 1,
+            self.col + 1,
+            self.e
+        )
+    }
+}
+
+/// Expressions in notes are cut short so that the chain stays readable
+const NOTE_EXPR_MAX_LEN: usize = 40;
+
+impl TypeError {
+    pub fn new(e: String, line: usize, col: usize) -> Self {
+        Self {
+            e,
+            line,
+            col,
+            end_line: line,
+            end_col: col,
+            expected: None,
+            actual: None,
+            definition: None,
+            notes: vec![],
+            slice: vec![],
+        }
+    }
+
+    pub(crate) fn at(e: String, ast: &AST, expr: usize) -> Self {
+        let ((line, col), (end_line, end_col)) = ast.get_span(expr);
+        Self {
+            end_line,
+            end_col,
+            ..Self::new(e, line, col)
+        }
+    }
+
+    /// A failure to show that actual is a subtype of expected, for the given reason
+    pub(crate) fn mismatch(
+        expected: Type,
+        actual: Type,
+        reason: String,
+        ast: &AST,
+        expr: usize,
+    ) -> Self {
+        let mut e = format!("Expected type {}, but found {}", expected, actual);
+        if reason != format!("{} is not a subtype of {}", actual, expected) {
+            e += &format!(": {}", reason);
+        }
+
+        Self {
+            expected: Some(Box::new(expected)),
+            actual: Some(Box::new(actual)),
+            ..Self::at(e, ast, expr)
+        }
+    }
+
+    pub(super) fn with_actual(self, actual: Type) -> Self {
+        Self {
+            actual: Some(Box::new(actual)),
+            ..self
+        }
+    }
+
+    /// Note what the checker was doing with expr when the error happened. The expression the
+    /// error is about is skipped, as the error already describes it
+    pub(super) fn note(
+        mut self,
+        ast: &AST,
+        expr: usize,
+        note: impl FnOnce(String) -> String,
+    ) -> Self {
+        let span = ((self.line, self.col), (self.end_line, self.end_col));
+        if self.notes.is_empty() && ast.get_span(expr) == span {
+            return self;
+        }
+
+        let mut expr_str = ast.to_string_sugar(expr, false);
+        if expr_str.chars().count() > NOTE_EXPR_MAX_LEN {
+            expr_str = expr_str
+                .chars()
+                .take(NOTE_EXPR_MAX_LEN - 3)
+                .collect::<String>()
+                + "...";
+        }
+
+        self.notes.push(note(expr_str));
+        self
+    }
+
+    pub(crate) fn in_definition(self, name: &str) -> Self {
+        Self {
+            definition: self.definition.or(Some(name.to_string())),
+            ..self
+        }
+    }
+
+    /// Blame the error on the expressions that decided the types it is about, as well as the
+    /// expression it was found at. Expressions inside others already in the slice are more
+    /// precise, so only the innermost are kept
+    pub(super) fn with_slice(self, ast: &AST, exprs: impl IntoIterator<Item = usize>) -> Self {
+        let mut spans = self.slice().to_vec();
+        spans.extend(exprs.into_iter().map(|expr| ast.get_span(expr)));
+        spans.push(((self.line, self.col), (self.end_line, self.end_col)));
+        spans.sort();
+        spans.dedup();
+
+        let contains =
+            |outer: &Span, inner: &Span| outer != inner && outer.0 <= inner.0 && inner.1 <= outer.1;
+        let slice: Vec<Span> = spans
+            .iter()
+            .filter(|span| !spans.iter().any(|other| contains(span, other)))
+            .copied()
+            .collect();
+
+        Self { slice, ..self }
+    }
+
+    /// The minimal set of locations that together explain the error: where it was found, and
+    /// where the types that conflict there were decided. Empty if the error has no slice
+    pub fn slice(&self) -> &[Span] {
+        &self.slice
+    }
+
+    /// Render the error with an excerpt of the source it came from, with the offending expression
+    /// underlined. The other expressions in its slice are underlined after it
+    pub fn render(&self, src: &str) -> String {
+        let mut s = format!("{:?}\n", self);
+        if let Some(name) = &self.definition {
+            s += &format!("  in the definition of {}\n", name);
+        }
+
+        let span = ((self.line, self.col), (self.end_line, self.end_col));
+        s += &excerpt(src, &[span], '^');
+
+        if let Some(expected) = &self.expected {
+            s += &format!("  = expected: {}\n", expected);
+        }
+        if let Some(actual) = &self.actual {
+            s += &format!("  = found:    {}\n", actual);
+        }
+        for note in &self.notes {
+            s += &format!("  = note: {}\n", note);
+        }
+
+        // The slice is sorted, so the spans on each line are together
+        let others: Vec<Span> = self
+            .slice()
+            .iter()
+            .filter(|other| **other != span)
+            .copied()
+            .collect();
+        if !others.is_empty() {
+            s += "  = note: the conflicting types were decided by\n";
+            for line in others.chunk_by(|a, b| a.0 .0 == b.0 .0) {
+                s += &excerpt(src, line, '-');
+            }
+        }
+
+        s
+    }
+}
+
+/// The line of src that the spans start on, with each span underlined by marker
+pub(super) fn excerpt(src: &str, spans: &[Span], marker: char) -> String {
+    let line = spans[0].0 .0;
+    let line_str = match src.lines().nth(line) {
+        Some(line_str) => line_str,
+        None => return String::new(),
+    };
+
+    let line_no = (line + 1).to_string();
+    let pad = " ".repeat(line_no.len());
+    let line_len = line_str.chars().count();
+
+    let mut underline = String::new();
+    for ((_, col), (end_line, end_col)) in spans {
+        let start = (*col).max(underline.chars().count()).min(line_len);
+        let end = if *end_line == line {
+            (*end_col).min(line_len)
+        } else {
+            line_len
+        };
+        // Spans that end where others have already been underlined add nothing
+        if !underline.is_empty() && end <= underline.chars().count() {
+            continue;
+        }
+
+        underline += &" ".repeat(start.saturating_sub(underline.chars().count()));
+        underline += &marker.to_string().repeat(end.saturating_sub(start).max(1));
+    }
+
+    let mut s = format!("{} |\n", pad);
+    s += &format!("{} | {}\n", line_no, line_str);
+    s += &format!("{} | {}\n", pad, underline);
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excerpt_spans_ending_at_line_end() {
+        let spans = [((0, 7), (0, 12)), ((0, 9), (0, 12)), ((0, 11), (0, 12))];
+        assert_eq!(
+            excerpt("main = f x y", &spans, '-'),
+            "  |\n1 | main = f x y\n  |        -----\n"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use super::error::excerpt;
+use super::*;
+
+/// An informational diagnostic for a label whose type assignment is less general than the type
+/// its definition has without it, such as Int -> Int for the identity function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeLint {
+    pub label: String,
+    pub declared: Type,
+    pub inferred: Type,
+    /// Where the label is defined
+    pub line: usize,
+    pub col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+impl std::fmt::Display for TypeLint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is declared as {}, but its definition has the more general type {}",
+            self.label, self.declared, self.inferred
+        )
+    }
+}
+
+impl TypeLint {
+    /// Render the lint with an excerpt of the source it came from, like a type error
+    pub fn render(&self, src: &str) -> String {
+        let span = ((self.line, self.col), (self.end_line, self.end_col));
+        format!(
+            "Note at [{}:{}]: {}\n{}",
+            self.line + 1,
+            self.col + 1,
+            self,
+            excerpt(src, &[span], '^')
+        )
+    }
+}
+
+/// Whether a is at least as general as b, so that b is an instance of it
+fn at_least_as_general(c: &Context, a: &Type, b: &Type, type_map: &TypeMap) -> bool {
+    subtype(c.clone(), a, b, type_map, &mut Recorder::disabled()).is_ok()
+}
+
+/// Infer the type of each label with a type assignment as if it had none, and find those where
+/// the inferred type is strictly more general. Types equal up to renaming their variables are not
+/// reported. The module should be as parsed, so that assigned types can be told apart from
+/// inferred ones. Labels that need their type assignment, or modules that don't typecheck, give
+/// no lints
+pub fn lint_declared_types(
+    ast: &AST,
+    module: usize,
+    lt: &KnownTypeLabelTable,
+    type_map: &TypeMap,
+) -> Vec<TypeLint> {
+    let mut typed = ast.clone();
+    let mut typed_lt = lt.clone();
+    if typecheck(&mut typed, module, &mut typed_lt, type_map).is_err() {
+        return vec![];
+    }
+
+    let assignee_names = ast.get_assignee_names(module);
+    let c = Context::from_labels(&typed_lt, &assignee_names.iter().cloned().collect());
+    let known: HashMap<String, Type> = assignee_names
+        .iter()
+        .map(|name| {
+            let assign = typed.get_assign_to(module, name.clone()).unwrap();
+            (
+                name.clone(),
+                typed.get(assign).type_assignment.clone().unwrap(),
+            )
+        })
+        .collect();
+
+    let mut lints = vec![];
+    for name in &assignee_names {
+        let assign = ast.get_assign_to(module, name.clone()).unwrap();
+        let declared = match &ast.get(assign).type_assignment {
+            Some(t) if !ast.is_from_prelude(assign) => t.clone(),
+            _ => continue,
+        };
+
+        // Recursive calls see the label's own inferred type, rather than its declared one
+        let mut others = known.clone();
+        others.remove(name);
+        let inferred = match infer_scc_types(
+            c.clone(),
+            &typed,
+            module,
+            &vec![name.clone()],
+            &others,
+            type_map,
+            &mut Recorder::disabled(),
+        ) {
+            Ok(mut types) => types.pop().unwrap(),
+            Err(_) => continue,
+        };
+
+        if at_least_as_general(&c, &inferred, &declared, type_map)
+            && !at_least_as_general(&c, &declared, &inferred, type_map)
+        {
+            let ((line, col), (end_line, end_col)) = ast.get_span(ast.get_assignee_id(assign));
+            lints.push(TypeLint {
+                label: name.clone(),
+                declared,
+                inferred,
+                line,
+                col,
+                end_line,
+                end_col,
+            });
+        }
+    }
+
+    lints
+}
@@ -1,5 +1,10 @@
 mod checking;
 mod context;
+mod dependency;
+mod derivation;
+mod error;
+mod lint;
+mod recorder;
 mod subtype;
 mod synthesis;
 
@@ -8,39 +13,21 @@ use crate::parsing::TypeMap;
 use crate::{KnownTypeLabelTable, AST};
 use checking::check_type;
 use context::*;
-use std::collections::HashSet;
+pub(super) use dependency::unannotated_sccs;
+pub use derivation::Derivation;
+pub use error::TypeError;
+pub use lint::{lint_declared_types, TypeLint};
+use recorder::Recorder;
+pub use recorder::TypeTable;
+use std::collections::{HashMap, HashSet};
+use std::iter::zip;
 use subtype::subtype;
 use synthesis::synthesize_type;
 
-#[derive(Clone, PartialEq, Eq)]
-pub struct TypeError {
-    pub e: String,
-    pub line: usize,
-    pub col: usize,
-}
-
-impl std::fmt::Debug for TypeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(
-            f,
-            "Type Error at [{}:{}]: {}",
-            self.line + 1,
-            self.col + 1,
-            self.e
-        )
-    }
-}
 fn type_error(msg: String, ast: &AST, expr: usize) -> TypeError {
-    let n = ast.get(expr);
-    TypeError {
-        e: msg,
-        col: n.col,
-        line: n.line,
-    }
+    TypeError::at(msg, ast, expr)
 }
 
-static MUST_ASSIGN: bool = true;
-
 pub fn typecheck_tl_expr(expected: &Type, ast: &AST, expr: usize) -> Result<(), TypeError> {
     match check_type(
         Context::from_labels(&KnownTypeLabelTable::new(), &HashSet::new()),
@@ -49,22 +36,70 @@ pub fn typecheck_tl_expr(expected: &Type, ast: &AST, expr: usize) -> Result<(),
         expr,
         &TypeMap::new(),
         false,
+        &mut Recorder::disabled(),
     ) {
         Ok(_) => Ok(()),
         Err(e) => Err(e),
     }
 }
 
-fn infer_type_with_context(
-    c: Context,
+/// Check an expression found by reducing a typechecked module against a type. The labels it
+/// refers to are taken from lt, so must all have types. Substituting an abstraction under one
+/// with the same variable makes it shadow that variable, so unlike in programs, variables may be
+/// bound again
+pub fn typecheck_reduced_expr(
+    expected: &Type,
+    ast: &AST,
+    expr: usize,
+    lt: &KnownTypeLabelTable,
+    type_map: &TypeMap,
+) -> Result<(), TypeError> {
+    let c = reduced_expr_context(ast, expr, lt);
+    check_type(
+        c,
+        expected,
+        ast,
+        expr,
+        type_map,
+        false,
+        &mut Recorder::disabled(),
+    )
+    .map(|_| ())
+}
+
+/// Check that contracting the redex expr to the contractum keeps any type the redex could have
+/// been used at: the redex's type is synthesized and generalised, and the contractum is checked
+/// against that. expr must not be under a binder, so that the identifiers free in it are labels
+pub(crate) fn typecheck_contraction(
     ast: &AST,
     expr: usize,
+    contractum: &AST,
+    lt: &KnownTypeLabelTable,
     type_map: &TypeMap,
-) -> Result<(Type, Context), TypeError> {
-    let (t, c) = synthesize_type(c, ast, expr, type_map, false)?;
+) -> Result<(), TypeError> {
+    let c = reduced_expr_context(ast, expr, lt);
+    let (t, c) = synthesize_type(c, ast, expr, type_map, false, &mut Recorder::disabled())?;
+    let t = c.substitute(&t).forall_ify();
+    typecheck_reduced_expr(&t, contractum, contractum.root, lt, type_map)
+}
+
+/// The context to check an expression found by reducing a typechecked module in
+fn reduced_expr_context(ast: &AST, expr: usize, lt: &KnownTypeLabelTable) -> Context {
+    let known: HashMap<String, Type> = lt
+        .get_type_map()
+        .into_iter()
+        .filter_map(|(name, t)| Some((name, t?)))
+        .collect();
 
-    let t = c.substitute(&t);
-    Ok((t, c))
+    // Constructors are always in scope, but other labels are only added if they are free, so
+    // that variables in expr can share their names
+    let labels = known
+        .keys()
+        .filter(|name| !name.starts_with(|ch: char| ch.is_uppercase()))
+        .cloned()
+        .collect();
+    let c = Context::from_labels(lt, &labels).shadowing();
+    with_free_labels(c, ast, &vec![expr], &known)
 }
 
 #[cfg(test)]
@@ -72,9 +107,84 @@ pub fn infer_type(ast: &AST, expr: usize, type_map: &TypeMap) -> Result<Type, Ty
     let lt = KnownTypeLabelTable::new();
     let c = Context::from_labels(&lt, &HashSet::new());
 
-    Ok(infer_type_with_context(c, ast, expr, type_map)?
-        .0
-        .forall_ify())
+    let (t, c) = synthesize_type(c, ast, expr, type_map, false, &mut Recorder::disabled())?;
+
+    Ok(c.substitute(&t).forall_ify())
+}
+
+/// Add the types of the labels that are free in the given expressions. Only these are added, so
+/// that pattern and abstraction variables can share names with labels defined elsewhere
+fn with_free_labels(
+    c: Context,
+    ast: &AST,
+    exprs: &Vec<usize>,
+    known: &HashMap<String, Type>,
+) -> Context {
+    let mut free = HashSet::new();
+    for expr in exprs {
+        free.extend(ast.get_free_identifiers(*expr));
+    }
+    let mut free: Vec<String> = free.into_iter().collect();
+    free.sort();
+
+    let mut c = c;
+    for name in free {
+        if let Some(t) = known.get(&name) {
+            c = c.append(ContextItem::TypeAssignment(name, Ok(t.clone())));
+        }
+    }
+    c
+}
+
+/// Infer the types of a strongly connected component of unannotated labels. Each label gets an
+/// existential while the bodies are checked, so recursive calls are monomorphic. The results are
+/// then generalised: at the top level, every unsolved existential can be quantified over
+fn infer_scc_types(
+    c: Context,
+    ast: &AST,
+    module: usize,
+    scc: &Vec<String>,
+    known: &HashMap<String, Type>,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Vec<Type>, TypeError> {
+    let assign_exprs: Vec<usize> = scc
+        .iter()
+        .map(|name| ast.get_assign_exp(ast.get_assign_to(module, name.clone()).unwrap()))
+        .collect();
+
+    let mut c = with_free_labels(c, ast, &assign_exprs, known);
+    let mut exsts = vec![];
+    for name in scc {
+        let exst = c.get_next_existential_identifier();
+        c = c
+            .append(ContextItem::Existential(exst, None))
+            .append(ContextItem::TypeAssignment(
+                name.clone(),
+                Ok(Type::Existential(exst)),
+            ));
+        exsts.push(exst);
+    }
+
+    for ((assign_expr, exst), name) in zip(zip(assign_exprs, &exsts), scc) {
+        let result = check_type(
+            c,
+            &Type::Existential(*exst),
+            ast,
+            assign_expr,
+            type_map,
+            false,
+            rec,
+        );
+        rec.finish_label(name);
+        c = result.map_err(|e| e.in_definition(name))?;
+    }
+    rec.finish_types(Some(&c));
+
+    Ok(exsts
+        .iter()
+        .map(|exst| c.substitute(&Type::Existential(*exst)).forall_ify())
+        .collect())
 }
 
 pub fn typecheck(
@@ -83,57 +193,81 @@ pub fn typecheck(
     lt: &mut KnownTypeLabelTable,
     type_map: &TypeMap,
 ) -> Result<(), TypeError> {
-    let mut c = Context::from_labels(
-        &lt,
-        &ast.get_assignee_names(module)
-            .iter()
-            .map(|s| s.clone())
-            .collect(),
-    );
-
-    for assign_var in &ast.get_assignee_names(module) {
-        #[cfg(debug_assertions)]
-        let _c_str = format!("{:?}", &c);
+    typecheck_recorded(ast, module, lt, type_map, &mut Recorder::disabled())
+}
+
+/// Typecheck a module, also giving the derivation of each label's type in the order they were
+/// checked. If a label fails to typecheck, its derivation is the last, and shows where it failed
+pub fn typecheck_with_derivations(
+    ast: &mut AST,
+    module: usize,
+    lt: &mut KnownTypeLabelTable,
+    type_map: &TypeMap,
+) -> (Result<(), TypeError>, Vec<(String, Derivation)>) {
+    let mut rec = Recorder::new(true, false);
+    let result = typecheck_recorded(ast, module, lt, type_map, &mut rec);
+    (result, rec.derivation.derivations)
+}
 
+/// Typecheck a module, also giving the types found for its nodes. If a label fails to typecheck,
+/// the table still has the types found before it failed
+pub fn typecheck_with_types(
+    ast: &mut AST,
+    module: usize,
+    lt: &mut KnownTypeLabelTable,
+    type_map: &TypeMap,
+) -> (Result<(), TypeError>, TypeTable) {
+    let mut rec = Recorder::new(false, true);
+    let result = typecheck_recorded(ast, module, lt, type_map, &mut rec);
+    rec.finish_types(None);
+    (result, rec.types)
+}
+
+fn typecheck_recorded(
+    ast: &mut AST,
+    module: usize,
+    lt: &mut KnownTypeLabelTable,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<(), TypeError> {
+    let assignee_names = ast.get_assignee_names(module);
+    let c = Context::from_labels(&lt, &assignee_names.iter().cloned().collect());
+
+    // Types of labels in the module, from their type assignments or once they are inferred
+    let mut known = HashMap::new();
+    let mut annotated = vec![];
+    for assign_var in &assignee_names {
         let assign = ast.get_assign_to(module, assign_var.clone()).unwrap();
+        if let Some(type_assignment) = &ast.get(assign).type_assignment {
+            known.insert(assign_var.clone(), type_assignment.clone());
+            annotated.push((assign_var, assign, type_assignment.clone()));
+        }
+    }
 
+    // Infer the rest in dependency order, writing the inferred types back
+    for scc in unannotated_sccs(ast, module) {
+        let types = infer_scc_types(c.clone(), ast, module, &scc, &known, type_map, rec)?;
+        for (assign_var, t) in zip(scc, types) {
+            known.insert(assign_var.clone(), t.clone());
+            lt.add(assign_var.clone(), t.clone());
+            let assign = ast.get_assign_to(module, assign_var).unwrap();
+            rec.types.insert(ast.get_assignee_id(assign), t.clone());
+            ast.set_assignment_type(assign, t);
+        }
+    }
+
+    for (assign_var, assign, type_assignment) in annotated {
         #[cfg(debug_assertions)]
         let _assign_str = format!("{}", ast.to_string_sugar(assign, false));
 
         let assign_expr = ast.get_assign_exp(assign);
-
-        match &ast.get(assign).type_assignment {
-            Some(type_assignment) => {
-                c = c.append(ContextItem::TypeAssignment(
-                    assign_var.clone(),
-                    Ok(type_assignment.clone()),
-                ));
-                c = check_type(c, &type_assignment, ast, assign_expr, type_map, false)?
-                    .assigns_only();
-            }
-            None => {
-                if MUST_ASSIGN {
-                    return Err(type_error(
-                        format!("Cannot find type assignment for:  {}", &assign_var),
-                        ast,
-                        assign_expr,
-                    ));
-                }
-
-                let c2 = c.append(ContextItem::TypeAssignment(
-                    assign_var.clone(),
-                    Err(type_error(format!("Cannot infer type of expression containing recursive call. Assign a type to label '{}'", &assign_var), ast, assign_expr)),
-                ));
-                let (t, _) = infer_type_with_context(c2, &ast, assign_expr, type_map)?;
-
-                let t = t.forall_ify();
-                c = c.assigns_only().append(ContextItem::TypeAssignment(
-                    assign_var.clone(),
-                    Ok(t.clone()),
-                ));
-                ast.set_assignment_type(assign, t);
-            }
-        };
+        let c = with_free_labels(c.clone(), ast, &vec![assign_expr], &known);
+        let result = check_type(c, &type_assignment, ast, assign_expr, type_map, false, rec);
+        rec.finish_label(assign_var);
+        rec.finish_types(result.as_ref().ok());
+        rec.types
+            .insert(ast.get_assignee_id(assign), type_assignment);
+        result.map_err(|e| e.in_definition(assign_var))?;
     }
 
     Ok(())
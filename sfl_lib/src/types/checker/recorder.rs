@@ -0,0 +1,126 @@
+use super::context::Context;
+use super::derivation::DerivationRecorder;
+use crate::{Type, AST};
+use std::collections::HashMap;
+
+/// The types the typechecker found for the nodes of a module, fully substituted
+#[derive(Clone, Debug, Default)]
+pub struct TypeTable {
+    types: HashMap<usize, Type>,
+}
+
+impl TypeTable {
+    pub fn get(&self, node: usize) -> Option<&Type> {
+        self.types.get(&node)
+    }
+
+    pub fn insert(&mut self, node: usize, t: Type) {
+        self.types.insert(node, t);
+    }
+
+    /// The innermost typed node at a (0 indexed) position in the source, with its type. Nodes from
+    /// the prelude are not in the source, so are never found
+    pub fn type_at(&self, ast: &AST, line: usize, col: usize) -> Option<(usize, &Type)> {
+        let pos = (line, col);
+        self.types
+            .iter()
+            .filter(|(node, _)| !ast.is_from_prelude(**node))
+            .filter_map(|(node, t)| {
+                let (start, end) = ast.get_span(*node);
+                if start <= pos && pos < end {
+                    Some((start, end, *node, t))
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|(start, end, node, _)| (*start, std::cmp::Reverse(*end), *node))
+            .map(|(_, _, node, t)| (node, t))
+    }
+}
+
+/// Builds derivations and type tables as the checker applies its rules. A recorder that records
+/// neither does nothing, so normal typechecking doesn't pay for it
+pub struct Recorder {
+    pub derivation: DerivationRecorder,
+
+    types_enabled: bool,
+    /// Types found in the definition being checked, which may still contain existentials
+    pending_types: Vec<(usize, Type)>,
+    pub types: TypeTable,
+}
+
+impl Recorder {
+    pub fn disabled() -> Self {
+        Self::new(false, false)
+    }
+
+    pub fn new(derivations: bool, types: bool) -> Self {
+        Self {
+            derivation: if derivations {
+                DerivationRecorder::enabled()
+            } else {
+                DerivationRecorder::disabled()
+            },
+            types_enabled: types,
+            pending_types: vec![],
+            types: TypeTable::default(),
+        }
+    }
+
+    /// Apply a rule to c, recording it as a premise of the rule currently being applied.
+    /// conclude gives the judgement and output context once the rule is done
+    pub(super) fn record<R>(
+        &mut self,
+        c: Context,
+        apply: impl FnOnce(Context, &mut Self) -> R,
+        conclude: impl FnOnce(&R) -> (String, Option<&Context>),
+    ) -> R {
+        if !self.derivation.is_enabled() {
+            return apply(c, self);
+        }
+
+        self.derivation.begin(&c);
+        let result = apply(c, self);
+        let (judgement, output) = conclude(&result);
+        self.derivation.end(judgement, output);
+
+        result
+    }
+
+    /// Name the rule currently being applied
+    pub(super) fn rule(&mut self, rule: &str) {
+        self.derivation.rule(rule);
+    }
+
+    /// Label the derivations recorded since the last label
+    pub(super) fn finish_label(&mut self, name: &str) {
+        self.derivation.finish_label(name);
+    }
+
+    /// Record the type of a node, as found under the current context
+    pub(super) fn typed(&mut self, node: usize, t: &Type) {
+        if self.types_enabled {
+            self.pending_types.push((node, t.clone()));
+        }
+    }
+
+    /// Substitute what c knows into the recorded types. Existentials are dropped from contexts as
+    /// they go out of scope, so this has to happen before then
+    pub(super) fn substitute_types(&mut self, c: &Context) {
+        for (_, t) in self.pending_types.iter_mut() {
+            *t = c.substitute(t);
+        }
+    }
+
+    /// Add the types recorded for a definition to the table, substituting the context it was
+    /// checked in, if it typechecked. Existentials that are left unsolved become type variables,
+    /// named separately in each type
+    pub(super) fn finish_types(&mut self, c: Option<&Context>) {
+        if let Some(c) = c {
+            self.substitute_types(c);
+        }
+        for (node, t) in self.pending_types.drain(..) {
+            self.types.insert(node, t.tv_ify());
+        }
+    }
+}
@@ -1,10 +1,31 @@
+use super::recorder::Recorder;
 use super::*;
 use crate::parsing::TypeMap;
 use crate::Type;
 use std::iter::zip;
 
 /// A is subtype of B
-pub fn subtype(c: Context, a: &Type, b: &Type, type_map: &TypeMap) -> Result<Context, String> {
+pub fn subtype(
+    c: Context,
+    a: &Type,
+    b: &Type,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, String> {
+    rec.record(
+        c,
+        |c, rec| subtype_rule(c, a, b, type_map, rec),
+        |result| (format!("{} <: {}", a, b), result.as_ref().ok()),
+    )
+}
+
+fn subtype_rule(
+    c: Context,
+    a: &Type,
+    b: &Type,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, String> {
     #[cfg(debug_assertions)]
     let _c_str = format!("{:?}", &c);
     #[cfg(debug_assertions)]
@@ -13,14 +34,21 @@ pub fn subtype(c: Context, a: &Type, b: &Type, type_map: &TypeMap) -> Result<Con
     let _b_str = b.to_string();
 
     match (a, b) {
-        (Type::Alias(_, a_type), _) => subtype(c, a_type, b, type_map),
-        (_, Type::Alias(_, a_type)) => subtype(c, a, a_type, type_map),
+        (Type::Alias(_, a_type), _) => {
+            rec.rule("<:Alias");
+            subtype(c, a_type, b, type_map, rec)
+        }
+        (_, Type::Alias(_, a_type)) => {
+            rec.rule("<:Alias");
+            subtype(c, a, a_type, type_map, rec)
+        }
 
         // <:InstantiateL
         (Type::Existential(ex), _) => {
             match b {
                 Type::Existential(ex2) => {
                     if ex == ex2 {
+                        rec.rule("<:Exvar");
                         return Ok(c);
                     }
                 }
@@ -36,7 +64,8 @@ pub fn subtype(c: Context, a: &Type, b: &Type, type_map: &TypeMap) -> Result<Con
                 ));
             }
 
-            instantiate_l(c, *ex, b, type_map)
+            rec.rule("<:InstantiateL");
+            instantiate_l(c, *ex, b, type_map, rec)
         }
 
         // <:InstantiateR
@@ -50,11 +79,13 @@ pub fn subtype(c: Context, a: &Type, b: &Type, type_map: &TypeMap) -> Result<Con
                 ));
             }
 
-            instantiate_r(c, *ex, a, type_map)
+            rec.rule("<:InstantiateR");
+            instantiate_r(c, *ex, a, type_map, rec)
         }
 
         // <:Var
         (Type::TypeVariable(a), Type::TypeVariable(b)) => {
+            rec.rule("<:Var");
             if a == b {
                 Ok(c)
             } else {
@@ -63,6 +94,7 @@ pub fn subtype(c: Context, a: &Type, b: &Type, type_map: &TypeMap) -> Result<Con
         }
 
         (Type::Primitive(a), Type::Primitive(b)) => {
+            rec.rule("<:Prim");
             if a == b {
                 Ok(c)
             } else {
@@ -71,10 +103,14 @@ pub fn subtype(c: Context, a: &Type, b: &Type, type_map: &TypeMap) -> Result<Con
         }
 
         // <:Unit
-        (Type::Unit, Type::Unit) => Ok(c),
+        (Type::Unit, Type::Unit) => {
+            rec.rule("<:Unit");
+            Ok(c)
+        }
 
         // <:ForallL
         (Type::Forall(var, t), _) => {
+            rec.rule("<:∀L");
             let exst = c.get_next_existential_identifier();
             let c = c
                 .append(ContextItem::Marker(exst))
@@ -84,42 +120,40 @@ pub fn subtype(c: Context, a: &Type, b: &Type, type_map: &TypeMap) -> Result<Con
             let _c_str = format!("{:?}", &c);
 
             let new_body = t.substitute_type_variable(var, &Type::Existential(exst))?;
-            let pred = subtype(c, &new_body, b, type_map)?;
+            let pred = subtype(c, &new_body, b, type_map, rec)?;
             Ok(pred.get_before_item(ContextItem::Marker(exst)))
         }
 
         // <:ForallR
         (_, Type::Forall(var, t)) => {
+            rec.rule("<:∀R");
             let c = c.append(ContextItem::TypeVariable(var.clone()));
-            let pred = subtype(c, a, t.as_ref(), type_map)?;
+            let pred = subtype(c, a, t.as_ref(), type_map, rec)?;
             Ok(pred.get_before_item(ContextItem::TypeVariable(var.clone())))
         }
 
         // <:->
         (Type::Function(a1, a2), Type::Function(b1, b2)) => {
-            let pred1 = subtype(c, b1.as_ref(), a1, type_map)?;
+            rec.rule("<:→");
+            let pred1 = subtype(c, b1.as_ref(), a1, type_map, rec)?;
             let a2 = &pred1.substitute(a2);
             let b2 = &pred1.substitute(b2);
-            let pred2 = subtype(pred1, a2, b2, type_map)?;
+            let pred2 = subtype(pred1, a2, b2, type_map, rec)?;
             Ok(pred2)
         }
 
-        (Type::Product(pt1_1, pt1_2), a) | (a, Type::Product(pt1_1, pt1_2)) => {
-            let (ut2_1, ut2_2) = match a {
+        (Type::Product(pt1_1, pt1_2), other) | (other, Type::Product(pt1_1, pt1_2)) => {
+            rec.rule("<:×");
+            let (ut2_1, ut2_2) = match other {
                 Type::Product(a, b) => (a, b),
-                _ => {
-                    return Err(format!(
-                        "Type {} is not a subtype of product {}",
-                        a.tv_ify(),
-                        Type::Product(pt1_1.clone(), pt1_2.clone()).tv_ify()
-                    ))
-                }
+                _ => return Err(format!("{} is not a subtype of {}", a.tv_ify(), b.tv_ify())),
             };
-            let ut_1_st = subtype(c, pt1_1, ut2_1, type_map)?;
-            subtype(ut_1_st, pt1_2, ut2_2, type_map)
+            let ut_1_st = subtype(c, pt1_1, ut2_1, type_map, rec)?;
+            subtype(ut_1_st, pt1_2, ut2_2, type_map, rec)
         }
 
         (Type::Union(name1, uargs1), Type::Union(name2, uargs2)) => {
+            rec.rule("<:Union");
             if uargs1.len() != uargs2.len() || name1 != name2 {
                 return Err(format!(
                     "Type {} is not a subtype of union {}",
@@ -129,15 +163,67 @@ pub fn subtype(c: Context, a: &Type, b: &Type, type_map: &TypeMap) -> Result<Con
             }
             let mut c = c;
             for (t1, t2) in zip(uargs1, uargs2) {
-                c = subtype(c, t1, t2, type_map)?;
+                c = subtype(c, t1, t2, type_map, rec)?;
             }
             Ok(c)
         }
-        _ => Err("Subtype failiure".to_string()),
+        _ => Err(format!("{} is not a subtype of {}", a.tv_ify(), b.tv_ify())),
+    }
+}
+
+/// Solve exst to the type constructor of t applied to fresh existentials, declared before exst, and
+/// give the pairs of fresh existentials and the arguments of t they stand for. Like the arrow
+/// rules, this keeps exst's solution well formed when t mentions existentials declared after it
+fn articulate(c: Context, exst: usize, t: &Type) -> (Context, Vec<(usize, Type)>) {
+    let args = match t {
+        Type::Union(_, args) => args.clone(),
+        Type::Product(t1, t2) => vec![t1.as_ref().clone(), t2.as_ref().clone()],
+        _ => unreachable!("Only unions and products are articulated"),
+    };
+
+    let mut c = c;
+    let mut pairs = vec![];
+    for arg in args {
+        let a = c.get_next_existential_identifier();
+        c = c.add_before_existential(exst, ContextItem::Existential(a, None));
+        pairs.push((a, arg));
     }
+
+    let fresh: Vec<Type> = pairs.iter().map(|(a, _)| Type::Existential(*a)).collect();
+    let solution = match t {
+        Type::Union(name, _) => Type::Union(name.clone(), fresh),
+        _ => Type::pr(fresh[0].clone(), fresh[1].clone()),
+    };
+
+    (c.set_existential_definition(exst, solution), pairs)
+}
+
+fn instantiate_l(
+    c: Context,
+    exst: usize,
+    b: &Type,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, String> {
+    rec.record(
+        c,
+        |c, rec| instantiate_l_rule(c, exst, b, type_map, rec),
+        |result| {
+            (
+                format!("{} :≤ {}", Type::Existential(exst), b),
+                result.as_ref().ok(),
+            )
+        },
+    )
 }
 
-fn instantiate_l(c: Context, exst: usize, b: &Type, type_map: &TypeMap) -> Result<Context, String> {
+fn instantiate_l_rule(
+    c: Context,
+    exst: usize,
+    b: &Type,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, String> {
     #[cfg(debug_assertions)]
     let _c_str = format!("{:?}", &c);
     #[cfg(debug_assertions)]
@@ -145,13 +231,19 @@ fn instantiate_l(c: Context, exst: usize, b: &Type, type_map: &TypeMap) -> Resul
     #[cfg(debug_assertions)]
     let _b_str = format!("{}", &b.to_string());
     match b {
-        // InstLReach
+        // InstLReach, solving whichever existential is declared later to the other
         Type::Existential(exst2) => {
-            Ok(c.set_existential_definition(*exst2, Type::Existential(exst)))
+            rec.rule("InstLReach");
+            if c.is_declared_before(*exst2, exst) {
+                Ok(c.set_existential_definition(exst, Type::Existential(*exst2)))
+            } else {
+                Ok(c.set_existential_definition(*exst2, Type::Existential(exst)))
+            }
         }
 
         // InstLArr
         Type::Function(from, to) => {
+            rec.rule("InstLArr");
             let a1n = c.get_next_existential_identifier();
             let a2n = c.get_next_existential_identifier() + 1;
             let a1 = ContextItem::Existential(a1n, None);
@@ -167,21 +259,32 @@ fn instantiate_l(c: Context, exst: usize, b: &Type, type_map: &TypeMap) -> Resul
             #[cfg(debug_assertions)]
             let _c_str = format!("{:?}", &c);
 
-            let pred1_c = instantiate_r(c, a1n, from.as_ref(), type_map)?;
+            let pred1_c = instantiate_r(c, a1n, from.as_ref(), type_map, rec)?;
             let to_subst = pred1_c.substitute(to);
 
             #[cfg(debug_assertions)]
             let _to_subst_str = format!("{:?}", &to_subst);
 
-            let pred2 = instantiate_l(pred1_c, a2n, &to_subst, type_map)?;
+            let pred2 = instantiate_l(pred1_c, a2n, &to_subst, type_map, rec)?;
 
             Ok(pred2)
         }
 
+        // InstLUnion and InstL×
+        Type::Union(_, args) if !args.is_empty() => {
+            rec.rule("InstLUnion");
+            instantiate_l_articulated(c, exst, b, type_map, rec)
+        }
+        Type::Product(_, _) => {
+            rec.rule("InstL×");
+            instantiate_l_articulated(c, exst, b, type_map, rec)
+        }
+
         // InstLAllR
         Type::Forall(var, t) => {
+            rec.rule("InstLAllR");
             let new_c = c.append(ContextItem::TypeVariable(var.clone()));
-            let pred = instantiate_l(new_c, exst, t.as_ref(), type_map)?;
+            let pred = instantiate_l(new_c, exst, t.as_ref(), type_map, rec)?;
             Ok(pred.get_before_item(ContextItem::TypeVariable(var.clone())))
         }
 
@@ -197,17 +300,64 @@ fn instantiate_l(c: Context, exst: usize, b: &Type, type_map: &TypeMap) -> Resul
                         &existential_type_assignment,
                         &b.clone(),
                         type_map,
+                        rec,
                     )?;
                 }
             }
 
             // InstLSolve
+            rec.rule("InstLSolve");
             Ok(c.set_existential_definition(exst, b.clone()))
         }
     }
 }
 
-fn instantiate_r(c: Context, exst: usize, a: &Type, type_map: &TypeMap) -> Result<Context, String> {
+// Unions and products are covariant, so each of their arguments is instantiated the same way
+fn instantiate_l_articulated(
+    c: Context,
+    exst: usize,
+    b: &Type,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, String> {
+    if let Some(Some(solution)) = c.get_existential(exst) {
+        return subtype(c, &solution, b, type_map, rec);
+    }
+
+    let (mut c, pairs) = articulate(c, exst, b);
+    for (a, arg) in pairs {
+        let arg = c.substitute(&arg);
+        c = instantiate_l(c, a, &arg, type_map, rec)?;
+    }
+    Ok(c)
+}
+
+fn instantiate_r(
+    c: Context,
+    exst: usize,
+    a: &Type,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, String> {
+    rec.record(
+        c,
+        |c, rec| instantiate_r_rule(c, exst, a, type_map, rec),
+        |result| {
+            (
+                format!("{} ≤: {}", a, Type::Existential(exst)),
+                result.as_ref().ok(),
+            )
+        },
+    )
+}
+
+fn instantiate_r_rule(
+    c: Context,
+    exst: usize,
+    a: &Type,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, String> {
     #[cfg(debug_assertions)]
     let _c_str = format!("{:?}", &c);
     #[cfg(debug_assertions)]
@@ -215,13 +365,19 @@ fn instantiate_r(c: Context, exst: usize, a: &Type, type_map: &TypeMap) -> Resul
     #[cfg(debug_assertions)]
     let _a_str = format!("{}", &a.to_string());
     match a {
-        // InstRReach
+        // InstRReach, solving whichever existential is declared later to the other
         Type::Existential(exst2) => {
-            Ok(c.set_existential_definition(*exst2, Type::Existential(exst)))
+            rec.rule("InstRReach");
+            if c.is_declared_before(*exst2, exst) {
+                Ok(c.set_existential_definition(exst, Type::Existential(*exst2)))
+            } else {
+                Ok(c.set_existential_definition(*exst2, Type::Existential(exst)))
+            }
         }
 
         // InstRArr
         Type::Function(from, to) => {
+            rec.rule("InstRArr");
             #[cfg(debug_assertions)]
             let _from_str = format!("{}", &from.to_string());
             #[cfg(debug_assertions)]
@@ -245,18 +401,29 @@ fn instantiate_r(c: Context, exst: usize, a: &Type, type_map: &TypeMap) -> Resul
             #[cfg(debug_assertions)]
             let _c_str2 = format!("{:?}", &c);
 
-            let pred1_c = instantiate_l(c, a1n, from.as_ref(), type_map)?;
+            let pred1_c = instantiate_l(c, a1n, from.as_ref(), type_map, rec)?;
             let to_subst = pred1_c.substitute(to.as_ref());
 
             #[cfg(debug_assertions)]
             let _to_subst_str = to_subst.to_string();
 
-            let pred2 = instantiate_r(pred1_c, a2n, &to_subst, type_map)?;
+            let pred2 = instantiate_r(pred1_c, a2n, &to_subst, type_map, rec)?;
             Ok(pred2)
         }
 
+        // InstRUnion and InstR×
+        Type::Union(_, args) if !args.is_empty() => {
+            rec.rule("InstRUnion");
+            instantiate_r_articulated(c, exst, a, type_map, rec)
+        }
+        Type::Product(_, _) => {
+            rec.rule("InstR×");
+            instantiate_r_articulated(c, exst, a, type_map, rec)
+        }
+
         // InstRAllL
         Type::Forall(var, t) => {
+            rec.rule("InstRAllL");
             let next_ext = c.get_next_existential_identifier();
             let c = c
                 .append(ContextItem::Marker(next_ext))
@@ -266,7 +433,7 @@ fn instantiate_r(c: Context, exst: usize, a: &Type, type_map: &TypeMap) -> Resul
             let _c_str = format!("{:?}", &c);
 
             let t = t.substitute_type_variable(var, &Type::Existential(next_ext))?;
-            let pred1 = instantiate_r(c, exst, &t, type_map)?;
+            let pred1 = instantiate_r(c, exst, &t, type_map, rec)?;
             Ok(pred1.get_before_item(ContextItem::Marker(next_ext)))
         }
 
@@ -282,12 +449,33 @@ fn instantiate_r(c: Context, exst: usize, a: &Type, type_map: &TypeMap) -> Resul
                         &existential_type_assignment,
                         &a.clone(),
                         type_map,
+                        rec,
                     )?;
                 }
             }
 
             // InstRSolve
+            rec.rule("InstRSolve");
             Ok(c.set_existential_definition(exst, a.clone()))
         }
     }
 }
+
+fn instantiate_r_articulated(
+    c: Context,
+    exst: usize,
+    a: &Type,
+    type_map: &TypeMap,
+    rec: &mut Recorder,
+) -> Result<Context, String> {
+    if let Some(Some(solution)) = c.get_existential(exst) {
+        return subtype(c, a, &solution, type_map, rec);
+    }
+
+    let (mut c, pairs) = articulate(c, exst, a);
+    for (a, arg) in pairs {
+        let arg = c.substitute(&arg);
+        c = instantiate_r(c, a, &arg, type_map, rec)?;
+    }
+    Ok(c)
+}
@@ -1,4 +1,5 @@
-use super::checking::check_type;
+use super::checking::{check_match, check_type};
+use super::recorder::Recorder;
 use super::*;
 use crate::parsing::TypeMap;
 use crate::*;
@@ -10,6 +11,48 @@ pub(crate) fn synthesize_type(
     expr: usize,
     type_map: &TypeMap,
     is_pattern: bool,
+    rec: &mut Recorder,
+) -> Result<(Type, Context), TypeError> {
+    synthesize_type_recorded(c, ast, expr, type_map, is_pattern, rec).map_err(|e| {
+        e.note(ast, expr, |expr_str| {
+            format!("while inferring the type of `{}`", expr_str)
+        })
+    })
+}
+
+// synthesize_type, without noting the expression on an error
+pub(super) fn synthesize_type_recorded(
+    c: Context,
+    ast: &AST,
+    expr: usize,
+    type_map: &TypeMap,
+    is_pattern: bool,
+    rec: &mut Recorder,
+) -> Result<(Type, Context), TypeError> {
+    let result = rec.record(
+        c,
+        |c, rec| synthesize_type_rule(c, ast, expr, type_map, is_pattern, rec),
+        |result| {
+            let expr_str = ast.to_string_sugar(expr, false);
+            match result {
+                Ok((t, c)) => (format!("{} ⇒ {}", expr_str, t), Some(c)),
+                Err(_) => (format!("{} ⇒ ?", expr_str), None),
+            }
+        },
+    );
+    if let Ok((t, _)) = &result {
+        rec.typed(expr, t);
+    }
+    result
+}
+
+fn synthesize_type_rule(
+    c: Context,
+    ast: &AST,
+    expr: usize,
+    type_map: &TypeMap,
+    is_pattern: bool,
+    rec: &mut Recorder,
 ) -> Result<(Type, Context), TypeError> {
     #[cfg(debug_assertions)]
     let _expr_str = ast.to_string_sugar(expr, false);
@@ -26,10 +69,17 @@ pub(crate) fn synthesize_type(
 
             #[cfg(debug_assertions)]
             let _var_str = var.clone();
-            match c.get_type_assignment(&var) {
-                Some(t) => Ok((t?, c)),
+            let pattern_var = is_pattern && var.chars().next().unwrap().is_lowercase();
+            // Where shadowing is allowed, variables in patterns are always new bindings
+            let shadows = pattern_var && c.allows_shadowing();
+            match c.get_type_assignment(&var).filter(|_| !shadows) {
+                Some(t) => {
+                    rec.rule("Var");
+                    Ok((t?, c))
+                }
                 None => {
-                    if is_pattern && var.chars().next().unwrap().is_lowercase() {
+                    if pattern_var {
+                        rec.rule("PatVar");
                         let next_exist = Type::Existential(c.get_next_existential_identifier());
                         Ok((
                             next_exist.clone(),
@@ -41,6 +91,7 @@ pub(crate) fn synthesize_type(
                         ))
                     } else {
                         if var == "_" {
+                            rec.rule("Wildcard");
                             let next_exist = Type::Existential(c.get_next_existential_identifier());
                             return Ok((
                                 next_exist.clone(),
@@ -57,10 +108,11 @@ pub(crate) fn synthesize_type(
         }
 
         ASTNodeType::Pair => {
+            rec.rule("×I⇒");
             let expr1 = ast.get_first(expr);
             let expr2 = ast.get_second(expr);
-            let (expr1t, c) = synthesize_type(c, ast, expr1, type_map, is_pattern)?;
-            let (expr2t, c) = synthesize_type(c, ast, expr2, type_map, is_pattern)?;
+            let (expr1t, c) = synthesize_type(c, ast, expr1, type_map, is_pattern, rec)?;
+            let (expr2t, c) = synthesize_type(c, ast, expr2, type_map, is_pattern, rec)?;
 
             // lift foralls
             let expr1fas = expr1t.get_foralls();
@@ -81,66 +133,44 @@ pub(crate) fn synthesize_type(
             ))
         }
 
-        ASTNodeType::Literal => Ok((node.get_lit_type(), c)),
+        ASTNodeType::Literal => {
+            rec.rule("Lit");
+            Ok((node.get_lit_type(), c))
+        }
+
+        // Errors are bottom, so can take any type
+        ASTNodeType::Error => {
+            rec.rule("Error");
+            let next_exist = c.get_next_existential_identifier();
+            Ok((
+                Type::Existential(next_exist),
+                c.append(ContextItem::Existential(next_exist, None)),
+            ))
+        }
 
         ASTNodeType::Match => {
+            rec.rule("Match⇒");
             assert_eq!(is_pattern, false);
 
-            let unpack_expr = ast.get_match_unpack_pattern(expr);
-
-            let (unpack_type, c) = if let Some(t) = ast.get(unpack_expr).type_assignment.clone() {
-                let c = check_type(c, &t, ast, unpack_expr, type_map, false)?;
-                (t.clone(), c)
-            } else {
-                synthesize_type(c, ast, unpack_expr, type_map, false)?
-            };
-
-            #[cfg(debug_assertions)]
-            let _c_str = format!("{:?}", &c);
-            let _unpack_type_str = format!("{}", &unpack_type);
-
-            let cases = ast.get_match_cases(expr);
-
             let expr_exist = c.get_next_existential_identifier();
+            let c = c.append(ContextItem::Existential(expr_exist, None));
             let expr_type = Type::Existential(expr_exist);
 
-            let mut c = c.append(ContextItem::Existential(expr_exist, None));
-
-            for (case_pat, case_expr) in cases {
-                #[cfg(debug_assertions)]
-                let _pat_str = format!("{}", &ast.to_string_sugar(case_pat, false));
-                #[cfg(debug_assertions)]
-                let _expr_str = format!("{}", &ast.to_string_sugar(case_expr, false));
-
-                let pattern_context = check_type(c, &unpack_type, ast, case_pat, type_map, true)?;
-                #[cfg(debug_assertions)]
-                let _pat_c_str = format!("{:?}", &pattern_context);
-
-                let expr_context =
-                    check_type(pattern_context, &expr_type, ast, case_expr, type_map, false)?;
-                #[cfg(debug_assertions)]
-                let _expr_c_str = format!("{:?}", &expr_context);
-
-                c = expr_context;
-            }
-
-            #[cfg(debug_assertions)]
-            let _c_str = format!("{:?}", &c);
-
-            c.get_existential(expr_exist)
-                .map(|t| (t.unwrap(), c))
-                .ok_or_else(|| type_error("Match failed".to_string(), ast, expr))
+            let c = check_match(c, &expr_type, ast, expr, type_map, rec)?;
+            Ok((expr_type, c))
         }
 
         // ->I=>
         ASTNodeType::Abstraction => {
+            rec.rule("→I⇒");
             let next_exst = c.get_next_existential_identifier();
             let c = c
                 .append(ContextItem::Existential(next_exst, None))
                 .append(ContextItem::Existential(next_exst + 1, None));
 
             let c = if let Some(t) = &ast.get(ast.get_abstr_var(expr)).type_assignment {
-                c.set_existential_definition(next_exst, t.clone())
+                c.blaming(ast.get_abstr_var(expr))
+                    .set_existential_definition(next_exst, t.clone())
             } else {
                 c
             };
@@ -150,6 +180,7 @@ pub(crate) fn synthesize_type(
                 ast,
                 ast.get_abstr_var(expr),
             )?;
+            rec.typed(ast.get_abstr_var(expr), &Type::Existential(next_exst));
 
             #[cfg(debug_assertions)]
             let _c_str = format!("{:?}", &c);
@@ -161,6 +192,7 @@ pub(crate) fn synthesize_type(
                 ast.get_abstr_expr(expr),
                 type_map,
                 false,
+                rec,
             )?;
 
             #[cfg(debug_assertions)]
@@ -172,6 +204,7 @@ pub(crate) fn synthesize_type(
                 Type::Existential(next_exst),
                 Type::Existential(next_exst + 1),
             );
+            rec.substitute_types(&c);
             let c = c.get_before_assignment(before);
 
             #[cfg(debug_assertions)]
@@ -185,10 +218,10 @@ pub(crate) fn synthesize_type(
 
         // ->E
         ASTNodeType::Application => {
+            rec.rule("→E");
             let lhs = ast.get_func(expr);
-            let rhs = ast.get_arg(expr);
 
-            let (f_type, f_c) = synthesize_type(c, ast, lhs, type_map, is_pattern)?;
+            let (f_type, f_c) = synthesize_type(c, ast, lhs, type_map, is_pattern, rec)?;
 
             #[cfg(debug_assertions)]
             let _f_c_str = format!("{:?}", &f_c);
@@ -196,11 +229,39 @@ pub(crate) fn synthesize_type(
             #[cfg(debug_assertions)]
             let _f_type_str = f_type.to_string();
 
+            // An argument that doesn't fit is as much the function's fault, and that of whatever
+            // decided its type
+            let mut f_sources = f_c.explain(&f_type);
+            f_sources.insert(ast.get_app_head(lhs));
+            let arg_span = ast.get_span(ast.get_arg(expr));
+
             let f_type = f_c.substitute(&f_type);
 
             #[cfg(debug_assertions)]
             let _f_type_str = f_type.to_string();
-            synthesize_app_type(f_c, &f_type, ast, lhs, rhs, type_map, is_pattern)
+            synthesize_app_type(f_c, &f_type, ast, expr, type_map, is_pattern, rec).map_err(|e| {
+                if ((e.line, e.col), (e.end_line, e.end_col)) == arg_span {
+                    e.with_slice(ast, f_sources)
+                } else {
+                    e
+                }
+            })
+        }
+
+        // Anno
+        ASTNodeType::Annotation => {
+            rec.rule("Anno");
+            let t = node.type_assignment.clone().unwrap();
+            let c = check_type(
+                c,
+                &t,
+                ast,
+                ast.get_annotated_expr(expr),
+                type_map,
+                is_pattern,
+                rec,
+            )?;
+            Ok((t, c))
         }
 
         _ => unreachable!("Non expression"),
@@ -212,11 +273,36 @@ fn synthesize_app_type(
     c: Context,
     applied_type: &Type,
     ast: &AST,
-    f: usize,
-    expr: usize,
+    app: usize,
     type_map: &TypeMap,
     is_pattern: bool,
+    rec: &mut Recorder,
 ) -> Result<(Type, Context), TypeError> {
+    rec.record(
+        c,
+        |c, rec| synthesize_app_type_rule(c, applied_type, ast, app, type_map, is_pattern, rec),
+        |result| {
+            let expr_str = ast.to_string_sugar(ast.get_arg(app), false);
+            match result {
+                Ok((t, c)) => (format!("{} • {} ⇒⇒ {}", applied_type, expr_str, t), Some(c)),
+                Err(_) => (format!("{} • {} ⇒⇒ ?", applied_type, expr_str), None),
+            }
+        },
+    )
+}
+
+fn synthesize_app_type_rule(
+    c: Context,
+    applied_type: &Type,
+    ast: &AST,
+    app: usize,
+    type_map: &TypeMap,
+    is_pattern: bool,
+    rec: &mut Recorder,
+) -> Result<(Type, Context), TypeError> {
+    let f = ast.get_func(app);
+    let expr = ast.get_arg(app);
+
     #[cfg(debug_assertions)]
     let _expr_str = ast.to_string_sugar(expr, false);
 
@@ -229,6 +315,7 @@ fn synthesize_app_type(
     match applied_type {
         // Forall App
         Type::Forall(var, t) => {
+            rec.rule("∀App");
             let new_c = c.append(ContextItem::Existential(
                 c.get_next_existential_identifier(),
                 None,
@@ -246,17 +333,19 @@ fn synthesize_app_type(
                     panic!("Failed to substitute in forall app: {}", s)
                 }
             };
-            synthesize_app_type(new_c, &a_subst, ast, f, expr, type_map, is_pattern)
+            synthesize_app_type(new_c, &a_subst, ast, app, type_map, is_pattern, rec)
         }
 
         // -> App
         Type::Function(from, to) => {
-            let pred = check_type(c, &from, ast, expr, type_map, is_pattern)?;
+            rec.rule("→App");
+            let pred = check_type(c, &from, ast, expr, type_map, is_pattern, rec)?;
 
             Ok((to.as_ref().clone(), pred))
         }
 
         Type::Existential(var) => {
+            rec.rule("α̂App");
             let a1n = c.get_next_existential_identifier();
             let a2n = c.get_next_existential_identifier() + 1;
             let a1 = ContextItem::Existential(a1n, None);
@@ -266,12 +355,14 @@ fn synthesize_app_type(
                 .add_before_existential(*var, a1);
             let a1t = Type::Existential(a1n);
             let a2t = Type::Existential(a2n);
-            let c = c.set_existential_definition(*var, Type::f(a1t.clone(), a2t.clone()));
+            let c = c
+                .blaming(app)
+                .set_existential_definition(*var, Type::f(a1t.clone(), a2t.clone()));
 
             #[cfg(debug_assertions)]
             let _c_str = format!("{:?}", &c);
 
-            let c = check_type(c, &a1t, ast, expr, type_map, is_pattern)?;
+            let c = check_type(c, &a1t, ast, expr, type_map, is_pattern, rec)?;
 
             Ok((a2t.clone(), c))
         }
@@ -285,7 +376,8 @@ fn synthesize_app_type(
             ),
             ast,
             expr,
-        )),
+        )
+        .with_actual(applied_type.tv_ify())),
 
         _ => Err(type_error(format!("App synthesis error. Failed to understand the application of type {} to expression {}", applied_type, ast.to_string_sugar(expr, false)), ast, expr)),
     }
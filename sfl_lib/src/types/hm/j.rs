@@ -0,0 +1,82 @@
+use super::unify::Substitution;
+use super::{Infer, Locals};
+use crate::{ASTNodeType, Type, TypeError};
+
+/// Algorithm J: the type of expr, extending the substitution in place. Types are left as they
+/// are found, and only have the substitution applied when they are unified
+pub(super) fn infer(
+    st: &mut Infer,
+    s: &mut Substitution,
+    locals: &Locals,
+    expr: usize,
+) -> Result<Type, TypeError> {
+    let ast = st.ast;
+    let node = ast.get(expr);
+
+    match node.t {
+        ASTNodeType::Identifier => st.lookup(locals, expr),
+
+        ASTNodeType::Literal => Ok(node.get_lit_type()),
+
+        // Errors are bottom, so can take any type
+        ASTNodeType::Error => Ok(st.fresh()),
+
+        ASTNodeType::Pair => {
+            let t1 = infer(st, s, locals, ast.get_first(expr))?;
+            let t2 = infer(st, s, locals, ast.get_second(expr))?;
+            Ok(Type::pr(t1, t2))
+        }
+
+        ASTNodeType::Application => {
+            let f_type = infer(st, s, locals, ast.get_func(expr))?;
+            let arg_type = infer(st, s, locals, ast.get_arg(expr))?;
+            let result = st.fresh();
+            st.unify(s, &f_type, &Type::f(arg_type, result.clone()), expr)?;
+            Ok(result)
+        }
+
+        ASTNodeType::Abstraction => {
+            let var = ast.get_abstr_var(expr);
+            let inner = st.bind_pattern(locals, var);
+            let var_type = infer(st, s, &inner, var)?;
+            if let Some(t) = &ast.get(var).type_assignment {
+                let t = st.skolemise(t, var)?;
+                st.unify(s, &t, &var_type, var)?;
+            }
+
+            let body_type = infer(st, s, &inner, ast.get_abstr_expr(expr))?;
+            Ok(Type::f(var_type, body_type))
+        }
+
+        ASTNodeType::Match => {
+            let unpack_expr = ast.get_match_unpack_pattern(expr);
+            let unpack_type = infer(st, s, locals, unpack_expr)?;
+            if let Some(t) = &ast.get(unpack_expr).type_assignment {
+                let t = st.skolemise(t, unpack_expr)?;
+                st.unify(s, &t, &unpack_type, unpack_expr)?;
+            }
+
+            let result = st.fresh();
+            for (case_pat, case_expr) in ast.get_match_cases(expr) {
+                let inner = st.bind_pattern(locals, case_pat);
+                let pat_type = infer(st, s, &inner, case_pat)?;
+                st.unify(s, &unpack_type, &pat_type, case_pat)?;
+
+                let case_type = infer(st, s, &inner, case_expr)?;
+                st.unify(s, &result, &case_type, case_expr)?;
+            }
+
+            Ok(result)
+        }
+
+        ASTNodeType::Annotation => {
+            let t = node.type_assignment.clone().unwrap();
+            let inner_type = infer(st, s, locals, ast.get_annotated_expr(expr))?;
+            let skolemised = st.skolemise(&t, expr)?;
+            st.unify(s, &skolemised, &inner_type, expr)?;
+            st.instantiate(&t, expr)
+        }
+
+        _ => unreachable!("Non expression"),
+    }
+}
@@ -0,0 +1,391 @@
+mod j;
+mod unify;
+mod w;
+
+use super::{Type, TypeError};
+use crate::parsing::TypeMap;
+use crate::{ASTNodeType, KnownTypeLabelTable, AST};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+use unify::Substitution;
+
+/// The classic Hindley–Milner inference algorithms. Both find the same types, and differ in how
+/// they keep track of the substitution found by unification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Algorithm W, which returns a substitution from each step and composes them
+    W,
+    /// Algorithm J, which extends a single substitution in place
+    J,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "w" => Ok(Algorithm::W),
+            "j" => Ok(Algorithm::J),
+            _ => Err(format!("Unknown algorithm {}, expected w or j", s)),
+        }
+    }
+}
+
+/// One unification made while inferring a label's type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unification {
+    /// The node whose typing needed the types to be equal
+    pub node: usize,
+    /// Both types have the substitution found so far applied
+    pub expected: Type,
+    pub actual: Type,
+    /// The variables bound by the unification, or why the types don't unify
+    pub result: Result<Vec<(usize, Type)>, String>,
+}
+
+impl Display for Unification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ~ {}", self.expected, self.actual)?;
+        match &self.result {
+            Ok(bindings) => {
+                let bindings: Vec<String> = bindings
+                    .iter()
+                    .map(|(v, t)| format!("{} := {}", Type::Existential(*v), t))
+                    .collect();
+                write!(f, "  [{}]", bindings.join(", "))
+            }
+            Err(e) => write!(f, "  failed: {}", e),
+        }
+    }
+}
+
+/// The unifications made for each label, in the order the labels were checked
+pub type UnificationTrace = Vec<(String, Vec<Unification>)>;
+
+/// The variables a pattern binds, each with its type, innermost scope last
+type Locals = Vec<(String, Type)>;
+
+/// State shared by both algorithms while inferring a label's type
+struct Infer<'a> {
+    ast: &'a AST,
+    labels: &'a HashMap<String, Type>,
+    /// The module's declared types, which types from type assignments and annotations must use
+    type_map: &'a TypeMap,
+    next_var: usize,
+    /// Type variables in scope from type assignments and annotations, which can't be unified
+    /// with anything but themselves
+    skolems: HashSet<String>,
+    trace: Vec<Unification>,
+}
+
+impl Infer<'_> {
+    fn fresh(&mut self) -> Type {
+        self.next_var += 1;
+        Type::Existential(self.next_var - 1)
+    }
+
+    /// Unify the types under the substitution, extending it, and record the unification
+    fn unify(
+        &mut self,
+        s: &mut Substitution,
+        expected: &Type,
+        actual: &Type,
+        expr: usize,
+    ) -> Result<(), TypeError> {
+        let expected = s.apply(expected);
+        let actual = s.apply(actual);
+        let result = s.unify(&expected, &actual);
+        self.trace.push(Unification {
+            node: expr,
+            expected: expected.clone(),
+            actual: actual.clone(),
+            result: result.clone().map_err(|e| e.to_string()),
+        });
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(reason) => {
+                // Unification variables are named as type variables, the same way in the types
+                // and the reason, as the bidirectional checker's errors name them
+                let (a, b) = reason.types();
+                let named = Type::tv_ify_together(&[expected, actual, a.clone(), b.clone()]);
+                let reason = reason.with_types(named[2].clone(), named[3].clone());
+                Err(TypeError::mismatch(
+                    named[0].clone(),
+                    named[1].clone(),
+                    reason.to_string(),
+                    self.ast,
+                    expr,
+                ))
+            }
+        }
+    }
+
+    /// Replace the outer foralls of a rank-1 type with fresh unification variables
+    fn instantiate(&mut self, t: &Type, expr: usize) -> Result<Type, TypeError> {
+        let (vars, mut t) = split_foralls(t, self.ast, expr)?;
+        for var in vars {
+            t = t.substitute_type_variable(&var, &self.fresh()).unwrap();
+        }
+        Ok(t)
+    }
+
+    /// Remove the outer foralls of a rank-1 type, leaving its type variables rigid. Variables
+    /// that are already in scope are renamed, so that they stay distinct
+    fn skolemise(&mut self, t: &Type, expr: usize) -> Result<Type, TypeError> {
+        let (vars, mut t) = split_foralls(t, self.ast, expr)?;
+        self.check_declared(&t, expr)?;
+        for var in vars {
+            let mut name = var.clone();
+            let mut i = 1;
+            while self.skolems.contains(&name) {
+                name = format!("{}{}", var, i);
+                i += 1;
+            }
+            if name != var {
+                t = t
+                    .substitute_type_variable(&var, &Type::TypeVariable(name.clone()))
+                    .unwrap();
+            }
+            self.skolems.insert(name);
+        }
+        Ok(t)
+    }
+
+    /// Check that every data type named in a declared type is in the type map, with as many
+    /// arguments as it has parameters
+    fn check_declared(&self, t: &Type, expr: usize) -> Result<(), TypeError> {
+        match t {
+            Type::Union(name, args) => {
+                let params = match self.type_map.types.get(name) {
+                    Some(declared) => declared.count_foralls(),
+                    None => {
+                        return Err(TypeError::at(
+                            format!("Unknown type {}", name),
+                            self.ast,
+                            expr,
+                        ))
+                    }
+                };
+                if params != args.len() {
+                    return Err(TypeError::at(
+                        format!(
+                            "Type {} takes {} arguments, but was given {}",
+                            name,
+                            params,
+                            args.len()
+                        ),
+                        self.ast,
+                        expr,
+                    ));
+                }
+                args.iter().try_for_each(|a| self.check_declared(a, expr))
+            }
+            Type::Function(t1, t2) | Type::Product(t1, t2) => {
+                self.check_declared(t1, expr)?;
+                self.check_declared(t2, expr)
+            }
+            Type::Alias(_, t) | Type::Forall(_, t) => self.check_declared(t, expr),
+            _ => Ok(()),
+        }
+    }
+
+    /// The type of an identifier: its binding's if it's bound by a pattern, or else an instance
+    /// of its label's type. Wildcards are fresh each time
+    fn lookup(&mut self, locals: &Locals, expr: usize) -> Result<Type, TypeError> {
+        let name = self.ast.get(expr).get_value();
+        if name.starts_with('_') {
+            return Ok(self.fresh());
+        }
+        if let Some((_, t)) = locals.iter().rev().find(|(n, _)| *n == name) {
+            return Ok(t.clone());
+        }
+        match self.labels.get(&name) {
+            Some(t) => self.instantiate(t, expr),
+            None => Err(TypeError::at(
+                format!("Unbound identifier {}", name),
+                self.ast,
+                expr,
+            )),
+        }
+    }
+
+    /// Bind the variables of a pattern to fresh unification variables. The pattern itself can
+    /// then be typed like an expression
+    fn bind_pattern(&mut self, locals: &Locals, pattern: usize) -> Locals {
+        let mut locals = locals.clone();
+        let mut stack = vec![pattern];
+        while let Some(p) = stack.pop() {
+            let node = self.ast.get(p);
+            match node.t {
+                ASTNodeType::Identifier => {
+                    let name = node.get_value();
+                    let first = name.chars().next().unwrap();
+                    if first.is_lowercase() {
+                        let t = self.fresh();
+                        locals.push((name, t));
+                    }
+                }
+                ASTNodeType::Application => {
+                    stack.push(self.ast.get_arg(p));
+                    stack.push(self.ast.get_func(p));
+                }
+                ASTNodeType::Pair => {
+                    stack.push(self.ast.get_second(p));
+                    stack.push(self.ast.get_first(p));
+                }
+                _ => {}
+            }
+        }
+        locals
+    }
+}
+
+/// Split a type into its outer forall variables and its body, which must be a monotype
+fn split_foralls(t: &Type, ast: &AST, expr: usize) -> Result<(Vec<String>, Type), TypeError> {
+    let vars = t.get_foralls();
+    let mut body = t;
+    while let Type::Forall(_, inner) = body {
+        body = inner;
+    }
+
+    if body.is_monotype() {
+        Ok((vars, body.clone()))
+    } else {
+        Err(TypeError::at(
+            format!(
+                "{} is not a rank-1 type, so cannot be used with Hindley–Milner inference",
+                t
+            ),
+            ast,
+            expr,
+        ))
+    }
+}
+
+/// Infer the type of an expression, extending the substitution found so far
+fn infer(
+    st: &mut Infer,
+    algorithm: Algorithm,
+    s: &mut Substitution,
+    locals: &Locals,
+    expr: usize,
+) -> Result<Type, TypeError> {
+    match algorithm {
+        Algorithm::W => {
+            let locals = locals
+                .iter()
+                .map(|(n, t)| (n.clone(), s.apply(t)))
+                .collect();
+            let (s1, t) = w::infer(st, &locals, expr)?;
+            *s = s1.compose(s);
+            Ok(t)
+        }
+        Algorithm::J => j::infer(st, s, locals, expr),
+    }
+}
+
+/// Typecheck a module with Hindley–Milner inference instead of the bidirectional checker, giving
+/// the unifications made for each label in the order they were checked. Only rank-1 types are
+/// supported. As with typecheck, the types of labels without type assignments are inferred and
+/// written back to the module and the label table, and declared types must be in the type map
+pub fn typecheck_hm(
+    ast: &mut AST,
+    module: usize,
+    lt: &mut KnownTypeLabelTable,
+    type_map: &TypeMap,
+    algorithm: Algorithm,
+) -> (Result<(), TypeError>, UnificationTrace) {
+    let mut traces = vec![];
+    let result = typecheck_hm_traced(ast, module, lt, type_map, algorithm, &mut traces);
+    (result, traces)
+}
+
+fn typecheck_hm_traced(
+    ast: &mut AST,
+    module: usize,
+    lt: &mut KnownTypeLabelTable,
+    type_map: &TypeMap,
+    algorithm: Algorithm,
+    traces: &mut UnificationTrace,
+) -> Result<(), TypeError> {
+    let assignee_names = ast.get_assignee_names(module);
+
+    let mut labels = HashMap::new();
+    for (name, t) in lt.get_type_map() {
+        if let (false, Some(t)) = (assignee_names.contains(&name), t) {
+            labels.insert(name, t);
+        }
+    }
+
+    let mut annotated = vec![];
+    for assign_var in &assignee_names {
+        let assign = ast.get_assign_to(module, assign_var.clone()).unwrap();
+        if let Some(type_assignment) = &ast.get(assign).type_assignment {
+            labels.insert(assign_var.clone(), type_assignment.clone());
+            annotated.push((assign_var.clone(), assign, type_assignment.clone()));
+        }
+    }
+
+    // Infer the rest in dependency order. Recursive uses within a component are monomorphic
+    for scc in super::checker::unannotated_sccs(ast, module) {
+        let mut st = Infer {
+            ast,
+            labels: &labels,
+            type_map,
+            next_var: 0,
+            skolems: HashSet::new(),
+            trace: vec![],
+        };
+        let mut locals = vec![];
+        for name in &scc {
+            let t = st.fresh();
+            locals.push((name.clone(), t));
+        }
+
+        let mut s = Substitution::default();
+        let mut result = Ok(());
+        for (name, label_var) in &locals {
+            let assign_expr = ast.get_assign_exp(ast.get_assign_to(module, name.clone()).unwrap());
+            result = infer(&mut st, algorithm, &mut s, &locals, assign_expr)
+                .and_then(|t| st.unify(&mut s, label_var, &t, assign_expr))
+                .map_err(|e| e.in_definition(name));
+            if result.is_err() {
+                break;
+            }
+        }
+        traces.push((scc.join(", "), st.trace));
+        result?;
+
+        for (name, label_var) in locals {
+            let t = s.apply(&label_var).forall_ify();
+            labels.insert(name.clone(), t.clone());
+            lt.add(name.clone(), t.clone());
+            let assign = ast.get_assign_to(module, name).unwrap();
+            ast.set_assignment_type(assign, t);
+        }
+    }
+
+    // A label with a type assignment must have exactly that type, with its type variables rigid
+    for (assign_var, assign, type_assignment) in annotated {
+        let assign_expr = ast.get_assign_exp(assign);
+        let mut st = Infer {
+            ast,
+            labels: &labels,
+            type_map,
+            next_var: 0,
+            skolems: HashSet::new(),
+            trace: vec![],
+        };
+        let result = st.skolemise(&type_assignment, assign).and_then(|declared| {
+            let mut s = Substitution::default();
+            let t = infer(&mut st, algorithm, &mut s, &vec![], assign_expr)?;
+            st.unify(&mut s, &declared, &t, assign_expr)
+        });
+        traces.push((assign_var.clone(), st.trace));
+        result.map_err(|e| e.in_definition(&assign_var))?;
+    }
+
+    Ok(())
+}
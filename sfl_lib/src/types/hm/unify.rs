@@ -0,0 +1,146 @@
+use crate::Type;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// Why two types don't unify, in terms of the two types it is about
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnifyError {
+    /// The variable occurs in the type, so binding it would make an infinite type
+    Occurs(Type, Type),
+    /// One of the types has a forall inside it
+    NotRank1(Type, Type),
+    Mismatch(Type, Type),
+}
+
+impl UnifyError {
+    pub fn types(&self) -> (&Type, &Type) {
+        match self {
+            UnifyError::Occurs(a, b) | UnifyError::NotRank1(a, b) | UnifyError::Mismatch(a, b) => {
+                (a, b)
+            }
+        }
+    }
+
+    /// The same error about two other types
+    pub fn with_types(&self, a: Type, b: Type) -> Self {
+        match self {
+            UnifyError::Occurs(_, _) => UnifyError::Occurs(a, b),
+            UnifyError::NotRank1(_, _) => UnifyError::NotRank1(a, b),
+            UnifyError::Mismatch(_, _) => UnifyError::Mismatch(a, b),
+        }
+    }
+}
+
+impl Display for UnifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnifyError::Occurs(v, t) => write!(
+                f,
+                "{} occurs in {}, so they would make an infinite type",
+                v, t
+            ),
+            UnifyError::NotRank1(t1, t2) => write!(
+                f,
+                "cannot unify {} with {}, as only rank-1 types are inferred",
+                t1, t2
+            ),
+            UnifyError::Mismatch(t1, t2) => write!(f, "cannot unify {} with {}", t1, t2),
+        }
+    }
+}
+
+/// A substitution of types for unification variables. Like the bidirectional checker, unification
+/// variables are existentials. Bindings are triangular: a bound type may mention variables that
+/// are bound later, so applying a substitution follows them through
+#[derive(Clone, Debug, Default)]
+pub struct Substitution {
+    map: HashMap<usize, Type>,
+}
+
+impl Substitution {
+    pub fn apply(&self, t: &Type) -> Type {
+        match t {
+            Type::Existential(v) => match self.map.get(v) {
+                Some(bound) => self.apply(bound),
+                None => t.clone(),
+            },
+            Type::Function(t1, t2) => Type::f(self.apply(t1), self.apply(t2)),
+            Type::Product(t1, t2) => Type::pr(self.apply(t1), self.apply(t2)),
+            Type::Union(name, args) => {
+                Type::Union(name.clone(), args.iter().map(|t| self.apply(t)).collect())
+            }
+            Type::Forall(var, t) => Type::Forall(var.clone(), Box::new(self.apply(t))),
+            Type::Alias(name, t) => Type::Alias(name.clone(), Box::new(self.apply(t))),
+            _ => t.clone(),
+        }
+    }
+
+    /// The substitution that applies other, then self
+    pub fn compose(&self, other: &Substitution) -> Substitution {
+        let mut map: HashMap<usize, Type> =
+            other.map.iter().map(|(v, t)| (*v, self.apply(t))).collect();
+        for (v, t) in &self.map {
+            map.entry(*v).or_insert_with(|| t.clone());
+        }
+        Substitution { map }
+    }
+
+    /// Extend the substitution with a most general unifier of t1 and t2, giving the new bindings
+    /// in the order they were made
+    pub fn unify(&mut self, t1: &Type, t2: &Type) -> Result<Vec<(usize, Type)>, UnifyError> {
+        let mut bindings = vec![];
+        self.unify_into(t1, t2, &mut bindings)?;
+        Ok(bindings)
+    }
+
+    fn unify_into(
+        &mut self,
+        t1: &Type,
+        t2: &Type,
+        bindings: &mut Vec<(usize, Type)>,
+    ) -> Result<(), UnifyError> {
+        let t1 = self.apply(t1);
+        let t2 = self.apply(t2);
+
+        match (&t1, &t2) {
+            (Type::Alias(_, t1), _) => self.unify_into(t1, &t2, bindings),
+            (_, Type::Alias(_, t2)) => self.unify_into(&t1, t2, bindings),
+
+            (Type::Existential(v1), Type::Existential(v2)) if v1 == v2 => Ok(()),
+            (Type::Existential(v), t) | (t, Type::Existential(v)) => {
+                if t.contains_existential(*v) {
+                    return Err(UnifyError::Occurs(Type::Existential(*v), t.clone()));
+                }
+                self.map.insert(*v, t.clone());
+                bindings.push((*v, t.clone()));
+                Ok(())
+            }
+
+            (Type::Unit, Type::Unit) => Ok(()),
+            (Type::Primitive(p1), Type::Primitive(p2)) if p1 == p2 => Ok(()),
+            // Type variables come from type assignments, and are rigid
+            (Type::TypeVariable(n1), Type::TypeVariable(n2)) if n1 == n2 => Ok(()),
+
+            (Type::Function(a1, b1), Type::Function(a2, b2))
+            | (Type::Product(a1, b1), Type::Product(a2, b2)) => {
+                self.unify_into(a1, a2, bindings)?;
+                self.unify_into(b1, b2, bindings)
+            }
+
+            (Type::Union(n1, args1), Type::Union(n2, args2))
+                if n1 == n2 && args1.len() == args2.len() =>
+            {
+                for (a1, a2) in args1.iter().zip(args2) {
+                    self.unify_into(a1, a2, bindings)?;
+                }
+                Ok(())
+            }
+
+            (Type::Forall(_, _), _) | (_, Type::Forall(_, _)) => {
+                Err(UnifyError::NotRank1(t1.clone(), t2.clone()))
+            }
+
+            _ => Err(UnifyError::Mismatch(t1.clone(), t2.clone())),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use super::unify::Substitution;
+use super::{Infer, Locals};
+use crate::{ASTNodeType, Type, TypeError};
+
+fn apply_locals(s: &Substitution, locals: &Locals) -> Locals {
+    locals
+        .iter()
+        .map(|(name, t)| (name.clone(), s.apply(t)))
+        .collect()
+}
+
+/// A most general unifier of the types
+fn unify(
+    st: &mut Infer,
+    expected: &Type,
+    actual: &Type,
+    expr: usize,
+) -> Result<Substitution, TypeError> {
+    let mut s = Substitution::default();
+    st.unify(&mut s, expected, actual, expr)?;
+    Ok(s)
+}
+
+/// Algorithm W: the type of expr, with the substitution that must be applied to locals for it
+pub(super) fn infer(
+    st: &mut Infer,
+    locals: &Locals,
+    expr: usize,
+) -> Result<(Substitution, Type), TypeError> {
+    let ast = st.ast;
+    let node = ast.get(expr);
+
+    match node.t {
+        ASTNodeType::Identifier => Ok((Substitution::default(), st.lookup(locals, expr)?)),
+
+        ASTNodeType::Literal => Ok((Substitution::default(), node.get_lit_type())),
+
+        // Errors are bottom, so can take any type
+        ASTNodeType::Error => Ok((Substitution::default(), st.fresh())),
+
+        ASTNodeType::Pair => {
+            let (s1, t1) = infer(st, locals, ast.get_first(expr))?;
+            let (s2, t2) = infer(st, &apply_locals(&s1, locals), ast.get_second(expr))?;
+            Ok((s2.compose(&s1), Type::pr(s2.apply(&t1), t2)))
+        }
+
+        ASTNodeType::Application => {
+            let (s1, f_type) = infer(st, locals, ast.get_func(expr))?;
+            let (s2, arg_type) = infer(st, &apply_locals(&s1, locals), ast.get_arg(expr))?;
+            let result = st.fresh();
+
+            let s3 = unify(
+                st,
+                &s2.apply(&f_type),
+                &Type::f(arg_type, result.clone()),
+                expr,
+            )?;
+            Ok((s3.compose(&s2.compose(&s1)), s3.apply(&result)))
+        }
+
+        ASTNodeType::Abstraction => {
+            let var = ast.get_abstr_var(expr);
+            let inner = st.bind_pattern(locals, var);
+            let (mut s1, var_type) = infer(st, &inner, var)?;
+            if let Some(t) = &ast.get(var).type_assignment {
+                let t = st.skolemise(t, var)?;
+                s1 = unify(st, &t, &s1.apply(&var_type), var)?.compose(&s1);
+            }
+
+            let (s2, body_type) = infer(st, &apply_locals(&s1, &inner), ast.get_abstr_expr(expr))?;
+            let s = s2.compose(&s1);
+            Ok((s.clone(), Type::f(s.apply(&var_type), body_type)))
+        }
+
+        ASTNodeType::Match => {
+            let unpack_expr = ast.get_match_unpack_pattern(expr);
+            let (mut s, unpack_type) = infer(st, locals, unpack_expr)?;
+            if let Some(t) = &ast.get(unpack_expr).type_assignment {
+                let t = st.skolemise(t, unpack_expr)?;
+                s = unify(st, &t, &s.apply(&unpack_type), unpack_expr)?.compose(&s);
+            }
+
+            let result = st.fresh();
+            for (case_pat, case_expr) in ast.get_match_cases(expr) {
+                let inner = st.bind_pattern(&apply_locals(&s, locals), case_pat);
+                let (s1, pat_type) = infer(st, &inner, case_pat)?;
+                s = s1.compose(&s);
+                s = unify(st, &s.apply(&unpack_type), &s.apply(&pat_type), case_pat)?.compose(&s);
+
+                let (s2, case_type) = infer(st, &apply_locals(&s, &inner), case_expr)?;
+                s = s2.compose(&s);
+                s = unify(st, &s.apply(&result), &case_type, case_expr)?.compose(&s);
+            }
+
+            Ok((s.clone(), s.apply(&result)))
+        }
+
+        ASTNodeType::Annotation => {
+            let t = node.type_assignment.clone().unwrap();
+            let (s1, inner_type) = infer(st, locals, ast.get_annotated_expr(expr))?;
+            let skolemised = st.skolemise(&t, expr)?;
+            let s2 = unify(st, &skolemised, &inner_type, expr)?;
+            Ok((s2.compose(&s1), st.instantiate(&t, expr)?))
+        }
+
+        _ => unreachable!("Non expression"),
+    }
+}
@@ -0,0 +1,214 @@
+use super::*;
+use crate::Parser;
+use std::collections::BTreeMap;
+use std::fs;
+
+const CHECKERS: [TypeChecker; 3] = [
+    TypeChecker::Bidirectional,
+    TypeChecker::HindleyMilner(Algorithm::W),
+    TypeChecker::HindleyMilner(Algorithm::J),
+];
+
+/// The type of every label in the program after typechecking it with checker
+fn label_types(program: &str, checker: TypeChecker) -> Result<BTreeMap<String, String>, TypeError> {
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let module = ast.root;
+    typecheck_with(checker, &mut ast, module, &mut lt, &pr.tm)?;
+
+    Ok(ast
+        .get_assigns_map(module)
+        .into_iter()
+        .map(|(name, assign)| {
+            let t = ast.get(assign).type_assignment.as_ref().unwrap();
+            (name, t.to_string())
+        })
+        .collect())
+}
+
+/// Every checker should accept the program with the same types, or reject it
+fn differential_test(program: &str) {
+    let expected = label_types(program, TypeChecker::Bidirectional);
+    for checker in &CHECKERS[1..] {
+        let actual = label_types(program, *checker);
+        match (&expected, &actual) {
+            (Ok(expected), Ok(actual)) => {
+                assert_eq!(expected, actual, "{:?} on\n{}", checker, program)
+            }
+            (Err(_), Err(_)) => {}
+            _ => panic!(
+                "Bidirectional gave {:?}, but {:?} gave {:?} on\n{}",
+                expected.as_ref().map(|_| ()),
+                checker,
+                actual.as_ref().map(|_| ()),
+                program
+            ),
+        }
+    }
+}
+
+fn hm_main_type(program: &str, algorithm: Algorithm) -> String {
+    label_types(program, TypeChecker::HindleyMilner(algorithm)).unwrap()["main"].clone()
+}
+
+#[test]
+fn differential_programs() {
+    let programs = [
+        "main = 1",
+        "main = \\x. x",
+        "main = \\f x. f (f x)",
+        "id x = x\nmain = (id 1, id true)",
+        "compose f g x = f (g x)\nmain = compose",
+        "main :: Int\nmain = if true 1 2",
+        "main :: Bool\nmain = 1",
+        "main = \\x. x x",
+        "main :: a -> Int\nmain = \\x. x",
+        "main :: Int -> Int\nmain = \\x :: Int. x + 1",
+        "main = (\\x. x :: Int -> Int)",
+        "main = (1 :: Bool)",
+        "swap (x, y) = (y, x)\nmain = swap",
+        "main = map (\\x. x + 1) (Cons 1 Nil)",
+        "main = foldr (\\x acc. if x acc false) true",
+        "len xs = match xs {\n  | Nil -> 0\n  | Cons _ rest -> 1 + len rest\n}\nmain = len",
+        "f x = match x {\n  | Nil -> 0\n  | (a, b) -> a\n}\nmain = f",
+        "isEven n = if (n == 0) true (isOdd (n - 1))\nisOdd n = if (n == 0) false (isEven (n - 1))\nmain = isEven",
+        "loop x = loop x\nmain = loop",
+        "main :: List Int\nmain = Cons true Nil",
+        "fromMaybe d m = match m {\n  | Nothing -> d\n  | Just x -> x\n}\nmain = fromMaybe 0",
+    ];
+
+    for program in programs {
+        differential_test(program);
+    }
+}
+
+// Type assignments in source can't be higher-rank, so every program that parses is rank-1
+#[test]
+fn differential_example_files() {
+    for dir in ["examples", "test_programs"] {
+        let path = format!("{}/../{}", env!("CARGO_MANIFEST_DIR"), dir);
+        for entry in fs::read_dir(path).unwrap() {
+            let program = fs::read_to_string(entry.unwrap().path()).unwrap();
+            if Parser::from_string(program.clone())
+                .parse_module(true)
+                .is_ok()
+            {
+                differential_test(&program);
+            }
+        }
+    }
+}
+
+#[test]
+fn hm_infers_principal_types() {
+    for algorithm in [Algorithm::W, Algorithm::J] {
+        assert_eq!(hm_main_type("main = \\x. x", algorithm), "∀a. a -> a");
+        assert_eq!(
+            hm_main_type("main = \\f g x. f (g x)", algorithm),
+            "∀a. ∀b. ∀c. (a -> b) -> (c -> a) -> c -> b"
+        );
+        assert_eq!(
+            hm_main_type("id x = x\nmain = (id 1, id true)", algorithm),
+            "(Int, Bool)"
+        );
+    }
+}
+
+#[test]
+fn hm_rejects_higher_rank_types() {
+    // (∀a. a -> a) -> Int
+    let id_type = Type::fa(
+        vec!["a".to_string()],
+        Type::f(Type::tv("a".to_string()), Type::tv("a".to_string())),
+    );
+    let f_type = Type::f(id_type, Type::int64());
+
+    // Type assignments in source are rank-1, so give main its type after parsing
+    for checker in CHECKERS {
+        let pr = Parser::from_string("main = \\g. g 1".to_string())
+            .parse_module(true)
+            .unwrap();
+        let mut ast = pr.ast;
+        let mut lt = pr.lt;
+        let module = ast.root;
+        ast.set_assignment_type(ast.get_main(module).unwrap(), f_type.clone());
+
+        let result = typecheck_with(checker, &mut ast, module, &mut lt, &pr.tm);
+        match checker {
+            TypeChecker::Bidirectional => result.unwrap(),
+            TypeChecker::HindleyMilner(_) => {
+                let e = result.unwrap_err();
+                assert!(e.e.contains("rank-1"), "{}", e.e);
+            }
+        }
+    }
+}
+
+#[test]
+fn hm_unification_trace() {
+    let pr = Parser::from_string("main = (\\x. x) 1".to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let module = ast.root;
+    let (result, traces) = typecheck_hm(&mut ast, module, &mut lt, &pr.tm, Algorithm::W);
+    result.unwrap();
+
+    let (_, trace) = traces.iter().find(|(label, _)| label == "main").unwrap();
+    let trace: Vec<String> = trace.iter().map(|u| u.to_string()).collect();
+    assert_eq!(
+        trace,
+        vec![
+            "Eb -> Eb ~ Int -> Ec  [Eb := Int, Ec := Int]",
+            "Ea ~ Int  [Ea := Int]"
+        ]
+    );
+}
+
+#[test]
+fn hm_occurs_check() {
+    for algorithm in [Algorithm::W, Algorithm::J] {
+        let pr = Parser::from_string("main = \\x. x x".to_string())
+            .parse_module(true)
+            .unwrap();
+        let mut ast = pr.ast;
+        let mut lt = pr.lt;
+        let module = ast.root;
+        let (result, traces) = typecheck_hm(&mut ast, module, &mut lt, &pr.tm, algorithm);
+        let e = result.unwrap_err();
+        assert_eq!(e.definition.as_deref(), Some("main"));
+        // Unification variables are named as type variables, consistently with the reason
+        assert_eq!(
+            e.e,
+            "Expected type a, but found a -> b: a occurs in a -> b, so they would make an infinite type"
+        );
+
+        let last = traces.last().unwrap().1.last().unwrap();
+        assert!(last.result.as_ref().unwrap_err().contains("infinite type"));
+    }
+}
+
+#[test]
+fn hm_declared_types_must_be_in_the_type_map() {
+    for algorithm in [Algorithm::W, Algorithm::J] {
+        let program = "data Box a = Box a\nunbox :: Box a -> a\nunbox b = match b {\n  | Box x -> x\n}\nmain = unbox (Box 1)";
+        let pr = Parser::from_string(program.to_string())
+            .parse_module(false)
+            .unwrap();
+        let mut ast = pr.ast;
+        let mut lt = pr.lt;
+        let module = ast.root;
+        let (result, _) = typecheck_hm(&mut ast, module, &mut lt, &pr.tm, algorithm);
+        result.unwrap();
+
+        // A type map without Box, as if the module had been parsed without its declaration
+        let (result, _) = typecheck_hm(&mut ast, module, &mut lt, &TypeMap::new(), algorithm);
+        let e = result.unwrap_err();
+        assert_eq!(e.definition.as_deref(), Some("unbox"));
+        assert_eq!(e.e, "Unknown type Box");
+    }
+}
@@ -1,11 +1,64 @@
+use crate::parsing::TypeMap;
+use crate::{KnownTypeLabelTable, AST};
 use std::collections::HashSet;
 use std::fmt::Display;
 use std::hash::Hash;
+use std::str::FromStr;
 
 mod checker;
+mod hm;
+#[cfg(test)]
+mod hm_test;
+mod preservation;
 #[cfg(test)]
 mod type_checker_test;
 pub use checker::*;
+pub use hm::{typecheck_hm, Algorithm, Unification, UnificationTrace};
+pub use preservation::{PreservationChecker, PreservationError};
+
+/// Which typechecker to use on a module
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TypeChecker {
+    /// The bidirectional checker, which supports higher-rank types
+    #[default]
+    Bidirectional,
+    /// Hindley–Milner inference, for rank-1 programs
+    HindleyMilner(Algorithm),
+}
+
+impl FromStr for TypeChecker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bidirectional" => Ok(TypeChecker::Bidirectional),
+            _ => match s.parse() {
+                Ok(algorithm) => Ok(TypeChecker::HindleyMilner(algorithm)),
+                Err(_) => Err(format!(
+                    "Unknown typechecker {}, expected bidirectional, w or j",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// Typecheck a module with the given typechecker. Both write the inferred types of labels without
+/// type assignments back to the module and the label table
+pub fn typecheck_with(
+    checker: TypeChecker,
+    ast: &mut AST,
+    module: usize,
+    lt: &mut KnownTypeLabelTable,
+    type_map: &TypeMap,
+) -> Result<(), TypeError> {
+    match checker {
+        TypeChecker::Bidirectional => typecheck(ast, module, lt, type_map),
+        TypeChecker::HindleyMilner(algorithm) => {
+            typecheck_hm(ast, module, lt, type_map, algorithm).0
+        }
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Primitive {
@@ -270,6 +323,31 @@ impl Type {
         self.all_exists_to_tvs().1
     }
 
+    /// tv_ify each of the types, giving an existential the same name in all of them
+    pub fn tv_ify_together(types: &[Type]) -> Vec<Self> {
+        let (last, rest) = match types.split_last() {
+            Some(split) => split,
+            None => return vec![],
+        };
+        let mut joined = rest
+            .iter()
+            .rev()
+            .fold(last.clone(), |acc, t| Type::pr(t.clone(), acc))
+            .tv_ify();
+        let mut named = vec![];
+        for _ in rest {
+            match joined {
+                Type::Product(t, others) => {
+                    named.push(*t);
+                    joined = *others;
+                }
+                _ => unreachable!("tv_ify keeps the products"),
+            }
+        }
+        named.push(joined);
+        named
+    }
+
     pub fn is_monotype(&self) -> bool {
         match self {
             Type::Function(t1, t2) => t1.is_monotype() && t2.is_monotype(),
@@ -1,5 +1,6 @@
 use super::*;
-use crate::Parser;
+use crate::test_utils::parse_and_typecheck;
+use crate::{find_single_redex_contraction_pair, KnownTypeLabelTable, Parser, AST};
 
 fn tc_test_should_pass(program: &str) -> Result<(), TypeError> {
     let pr = Parser::from_string(program.to_string())
@@ -361,3 +362,501 @@ fn tests_for_diss() -> Result<(), TypeError> {
     "#,
     )
 }
+
+#[test]
+fn infer_unannotated_labels() {
+    mod_main_inference_test(
+        r#"
+    plus a b = a + b
+    main = plus 1 2"#,
+        "Int",
+    );
+
+    mod_main_inference_test(
+        r#"
+    twice f x = f (f x)
+    main = twice"#,
+        "∀a. (a -> a) -> a -> a",
+    );
+}
+
+#[test]
+fn type_check_annotation() -> Result<(), TypeError> {
+    tc_test_should_pass("main :: Int\nmain = (5 :: Int)")?;
+    tc_test_should_pass("id2 :: a -> a\nid2 x = x\nmain :: Int\nmain = (id2 :: Int -> Int) 5")?;
+    tc_test_should_pass("main :: Int -> Int\nmain = (\\x. x :: Int -> Int)")?;
+    tc_test_should_fail("main :: Int\nmain = (true :: Int)");
+    tc_test_should_fail("main :: Float\nmain = (5 :: Int)");
+    tc_test_should_fail("id2 :: a -> a\nid2 x = x\nmain :: Int\nmain = (id2 :: Int -> Int) true");
+
+    Ok(())
+}
+
+#[test]
+fn infer_with_annotation() {
+    // The annotation pins down the type that would otherwise be generalised
+    mod_main_inference_test(
+        r#"
+    main = (\x. x :: Int -> Int)"#,
+        "Int -> Int",
+    );
+
+    mod_main_inference_test(
+        r#"
+    pair x = (x, (x :: Bool))
+    main = pair"#,
+        "Bool -> (Bool, Bool)",
+    );
+}
+
+#[test]
+fn infer_mutually_recursive_labels() {
+    mod_main_inference_test(
+        r#"
+    isEven n = if (n == 0) true (isOdd (n - 1))
+    isOdd n = if (n == 0) false (isEven (n - 1))
+    main = isEven 10"#,
+        "Bool",
+    );
+}
+
+#[test]
+fn infer_match_without_annotation() {
+    mod_main_inference_test(
+        r#"
+    snd2 p = match p {
+      | (_, y) -> y
+    }
+    main = snd2"#,
+        "∀a. ∀b. (a, b) -> b",
+    );
+
+    mod_main_inference_test(
+        r#"
+    g xs = match xs {
+      | Nil -> true
+      | Cons x _ -> x
+    }
+    main = g"#,
+        "List Bool -> Bool",
+    );
+
+    mod_main_inference_test(
+        r#"
+    last2 xs = match xs {
+      | Nil -> Nothing
+      | Cons x Nil -> Just x
+      | Cons _ rest -> last2 rest
+    }
+    main = last2"#,
+        "∀a. List a -> Maybe a",
+    );
+}
+
+#[test]
+fn match_polymorphic_expression() -> Result<(), TypeError> {
+    tc_test_should_pass(
+        r#"
+    main :: Int
+    main = match Nil {
+      | Nil -> 0
+      | Cons x xs -> x
+    }"#,
+    )
+}
+
+#[test]
+fn infer_match_without_annotation_should_fail() {
+    tc_test_should_fail(
+        r#"
+    snd2 p = match p {
+      | (_, y) -> y
+    }
+    main :: Bool
+    main = snd2 (1, 2)"#,
+    );
+
+    tc_test_should_fail(
+        r#"
+    f x = match x {
+      | Nil -> 0
+      | (a, b) -> a
+    }
+    main = f"#,
+    );
+}
+
+#[test]
+fn check_forward_reference() -> Result<(), TypeError> {
+    tc_test_should_pass(
+        r#"
+    main :: Int
+    main = double 2
+
+    double :: Int -> Int
+    double x = x * 2"#,
+    )
+}
+
+#[test]
+fn infer_mismatched_recursion_should_fail() {
+    mod_inference_should_fail(
+        r#"
+    f x = if x 1 (g x)
+    g y = f (y + 1)
+    main = f true"#,
+    );
+}
+
+#[test]
+fn structured_type_error() {
+    let program = "x :: Int\nx = 1\n\nmain :: Int\nmain = if true x false";
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let tm = pr.tm;
+    let module = ast.root;
+    let e = typecheck(&mut ast, module, &mut lt, &tm).unwrap_err();
+
+    assert_eq!(e.expected, Some(Box::new(Type::int64())));
+    assert_eq!(e.actual, Some(Box::new(Type::bool())));
+    assert_eq!((e.line, e.col), (4, 17));
+    assert_eq!((e.end_line, e.end_col), (4, 22));
+    assert_eq!(e.definition.as_deref(), Some("main"));
+    assert_eq!(
+        e.notes,
+        vec!["while checking `if true x false` against Int".to_string()]
+    );
+
+    assert_eq!(
+        e.render(program),
+        r#"Type Error at [5:18]: Expected type Int, but found Bool
+  in the definition of main
+  |
+5 | main = if true x false
+  |                  ^^^^^
+  = expected: Int
+  = found:    Bool
+  = note: while checking `if true x false` against Int
+  = note: the conflicting types were decided by
+  |
+5 | main = if true x false
+  |        --      -
+"#
+    );
+}
+
+fn type_error(program: &str) -> TypeError {
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let module = ast.root;
+    typecheck(&mut ast, module, &mut lt, &pr.tm).unwrap_err()
+}
+
+#[test]
+fn type_error_slice() {
+    // f is used at Int first, which only conflicts with the later use at Bool
+    let e = type_error("pick f = (f 1, f true)\nmain = pick");
+    assert_eq!((e.line, e.col), (0, 17));
+    assert_eq!(
+        e.slice(),
+        &[((0, 12), (0, 13)), ((0, 15), (0, 16)), ((0, 17), (0, 21))]
+    );
+
+    // x gets its type from where it is first used, far from where it is used again
+    let e = type_error("main = \\x. (x + 1, if x 1 2)");
+    assert_eq!((e.line, e.col), (0, 22));
+    assert_eq!(
+        e.slice(),
+        &[((0, 12), (0, 13)), ((0, 19), (0, 21)), ((0, 22), (0, 23))]
+    );
+
+    // The annotation of a distant label
+    let program = "inc :: Int -> Int\ninc n = n + 1\n\nmain :: Int\nmain = inc true";
+    let e = type_error(program);
+    assert_eq!(e.slice(), &[((4, 7), (4, 10)), ((4, 11), (4, 15))]);
+    assert!(e.render(program).ends_with(
+        r#"  = note: the conflicting types were decided by
+  |
+5 | main = inc true
+  |        ---
+"#
+    ));
+}
+
+#[test]
+fn type_error_slice_through_annotation() {
+    // The lambda's annotation decides the type of its variable
+    let e = type_error("main = (\\x :: Bool. x) 1");
+    assert_eq!((e.line, e.col), (0, 23));
+    assert_eq!(
+        e.slice(),
+        &[((0, 9), (0, 10)), ((0, 20), (0, 21)), ((0, 23), (0, 24))]
+    );
+}
+
+#[test]
+fn type_error_notes() {
+    let program = r#"
+    main :: Int -> (Int, Bool)
+    main x = (x, x + 1)"#;
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let tm = pr.tm;
+    let module = ast.root;
+    let e = typecheck(&mut ast, module, &mut lt, &tm).unwrap_err();
+
+    assert_eq!(e.expected, Some(Box::new(Type::bool())));
+    assert_eq!(e.actual, Some(Box::new(Type::int64())));
+    assert_eq!(
+        e.notes,
+        vec![
+            "while checking `(x, x + 1)` against (Int, Bool)".to_string(),
+            "while checking `\\x. (x, x + 1)` against Int -> (Int, Bool)".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn derivation_records_rules() {
+    let program = "id :: a -> a\nid x = x\n\nmain :: Int\nmain = id 1";
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(false)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let module = ast.root;
+    let (result, derivations) = typecheck_with_derivations(&mut ast, module, &mut lt, &pr.tm);
+    result.unwrap();
+
+    let names: Vec<&String> = derivations.iter().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["id", "main"]);
+
+    let main = &derivations[1].1;
+    assert_eq!(main.judgement, "id 1 ⇐ Int");
+    let rules: Vec<(usize, &str)> = main
+        .flatten()
+        .iter()
+        .map(|(depth, d)| (*depth, d.rule.as_str()))
+        .collect();
+    assert_eq!(
+        rules,
+        vec![
+            (0, "Sub"),
+            (1, "→E"),
+            (2, "Var"),
+            (2, "∀App"),
+            (3, "→App"),
+            (4, "Sub"),
+            (5, "Lit"),
+            (5, "<:InstantiateR"),
+            (6, "InstRSolve"),
+            (1, "<:Prim"),
+        ]
+    );
+
+    let (_, solve) = main.flatten()[8];
+    assert_eq!(solve.input, vec!["id:∀a. a -> a", "Eb"]);
+    assert_eq!(
+        solve.output,
+        Some(vec!["id:∀a. a -> a".to_string(), "Eb:Int".to_string()])
+    );
+}
+
+#[test]
+fn derivation_shows_failure() {
+    let pr = Parser::from_string("main :: Int\nmain = true".to_string())
+        .parse_module(false)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let module = ast.root;
+    let (result, derivations) = typecheck_with_derivations(&mut ast, module, &mut lt, &pr.tm);
+    result.unwrap_err();
+
+    let (name, main) = derivations.last().unwrap();
+    assert_eq!(name, "main");
+    assert_eq!(main.output, None);
+    let (_, failed) = *main.flatten().last().unwrap();
+    assert_eq!(failed.rule, "<:Prim");
+    assert_eq!(failed.judgement, "Bool <: Int");
+    assert_eq!(failed.output, None);
+}
+
+fn type_table(program: &str) -> (AST, Result<(), TypeError>, TypeTable) {
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap();
+    let mut ast = pr.ast;
+    let mut lt = pr.lt;
+    let module = ast.root;
+    let (result, types) = typecheck_with_types(&mut ast, module, &mut lt, &pr.tm);
+    (ast, result, types)
+}
+
+fn type_at(ast: &AST, types: &TypeTable, line: usize, col: usize) -> Option<String> {
+    types.type_at(ast, line, col).map(|(_, t)| t.to_string())
+}
+
+#[test]
+fn type_at_position() {
+    let program = "double :: Int -> Int\ndouble x = x * 2\n\nsnd2 p = match p {\n  | (a, b) -> b\n}\n\nmain = double (snd2 (true, 3))";
+    let (ast, result, types) = type_table(program);
+    result.unwrap();
+
+    // Labels and their parameters
+    assert_eq!(type_at(&ast, &types, 1, 0), Some("Int -> Int".to_string()));
+    assert_eq!(type_at(&ast, &types, 1, 7), Some("Int".to_string()));
+    assert_eq!(type_at(&ast, &types, 1, 11), Some("Int".to_string()));
+    assert_eq!(type_at(&ast, &types, 7, 0), Some("Int".to_string()));
+
+    // Pattern variables, in an inferred definition. Each type's variables are named on their own
+    assert_eq!(type_at(&ast, &types, 4, 6), Some("(a, b)".to_string()));
+    assert_eq!(type_at(&ast, &types, 4, 8), Some("a".to_string()));
+
+    // Applications and literals
+    assert_eq!(type_at(&ast, &types, 7, 7), Some("Int -> Int".to_string()));
+    assert_eq!(type_at(&ast, &types, 7, 21), Some("Bool".to_string()));
+    assert_eq!(
+        type_at(&ast, &types, 7, 25),
+        Some("(Bool, Int)".to_string())
+    );
+
+    // Whitespace between definitions
+    assert_eq!(type_at(&ast, &types, 2, 0), None);
+}
+
+#[test]
+fn type_at_ignores_prelude() {
+    let (ast, result, types) = type_table("main :: Int\nmain = 1");
+    result.unwrap();
+
+    // Prelude labels are typed, but their positions are in the prelude's source
+    let map = ast.get_assign_to(ast.root, "map".to_string()).unwrap();
+    assert!(ast.is_from_prelude(map));
+    assert!(types.get(ast.get_assignee_id(map)).is_some());
+    assert_eq!(type_at(&ast, &types, 0, 0), None);
+    assert_eq!(type_at(&ast, &types, 1, 7), Some("Int".to_string()));
+}
+
+#[test]
+fn type_at_after_error() {
+    let (ast, result, types) = type_table("f :: Int -> Int\nf x = x\n\nmain :: Int\nmain = true");
+    result.unwrap_err();
+
+    assert_eq!(type_at(&ast, &types, 1, 6), Some("Int".to_string()));
+}
+
+fn lints(program: &str) -> Vec<TypeLint> {
+    let pr = Parser::from_string(program.to_string())
+        .parse_module(true)
+        .unwrap();
+    lint_declared_types(&pr.ast, pr.ast.root, &pr.lt, &pr.tm)
+}
+
+#[test]
+fn lint_less_general_declared_type() {
+    let program = "id :: Int -> Int\nid x = x\n\nmain :: Int\nmain = id 1";
+    let found = lints(program);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].label, "id");
+    assert_eq!(found[0].inferred.to_string(), "∀a. a -> a");
+    assert_eq!(
+        found[0].render(program),
+        r#"Note at [2:1]: id is declared as Int -> Int, but its definition has the more general type ∀a. a -> a
+  |
+2 | id x = x
+  | ^^
+"#
+    );
+
+    // Only partly less general
+    let found = lints("const :: a -> Int -> a\nconst x y = x\nmain = const 1 2");
+    assert_eq!(found[0].inferred.to_string(), "∀a. ∀b. a -> b -> a");
+}
+
+#[test]
+fn lint_equally_general_declared_types() {
+    for program in [
+        // The same type, up to renaming its variables
+        "id :: b -> b\nid x = x\nmain = id 1",
+        "inc :: Int -> Int\ninc x = x + 1\nmain = inc 1",
+        "main :: Int\nmain = 1",
+        // Recursion sees the inferred type
+        "len :: List a -> Int\nlen xs = match xs {\n  | Nil -> 0\n  | Cons _ rest -> 1 + len rest\n}\nmain = len Nil",
+        // Doesn't typecheck
+        "f :: Int -> Int\nf x = x\nmain = f true",
+    ] {
+        assert_eq!(lints(program), vec![], "{}", program);
+    }
+}
+
+/// Typecheck the program, then take steps reduction steps of main, giving the AST, the label table
+/// and main's expression
+fn reduce_main(program: &str, steps: usize) -> (AST, KnownTypeLabelTable, usize) {
+    let (mut ast, lt) = parse_and_typecheck(program);
+    let module = ast.root;
+
+    for _ in 0..steps {
+        let main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+        let rc = find_single_redex_contraction_pair(&ast, Some(module), main_expr, &lt).unwrap();
+        ast.do_rc_subst(main_expr, &rc);
+    }
+    let main_expr = ast.get_assign_exp(ast.get_main(module).unwrap());
+    (ast, lt, main_expr)
+}
+
+#[test]
+fn rebinding_only_allowed_in_reduced_terms() {
+    let (ast, lt, main_expr) =
+        reduce_main("main :: Int -> Int\nmain = (\\f. \\x. f x) (\\x. x)", 1);
+    assert_eq!(ast.to_string_sugar(main_expr, false), "\\x. (\\x. x) x");
+
+    let t = Type::f(Type::int64(), Type::int64());
+    typecheck_tl_expr(&t, &ast, main_expr).unwrap_err();
+    typecheck_reduced_expr(&t, &ast, main_expr, &lt, &TypeMap::new()).unwrap();
+}
+
+#[test]
+fn reduced_terms_use_innermost_binding() {
+    let (ast, lt, main_expr) = reduce_main(
+        "main :: Int -> Bool -> Bool\nmain = (\\g. \\x. g) (\\x. x)",
+        1,
+    );
+    assert_eq!(ast.to_string_sugar(main_expr, false), "\\x. \\x. x");
+
+    let t = Type::f(Type::int64(), Type::f(Type::bool(), Type::bool()));
+    typecheck_reduced_expr(&t, &ast, main_expr, &lt, &TypeMap::new()).unwrap();
+    let t = Type::f(Type::int64(), Type::f(Type::bool(), Type::int64()));
+    typecheck_reduced_expr(&t, &ast, main_expr, &lt, &TypeMap::new()).unwrap_err();
+}
+
+#[test]
+fn pattern_variables_shadow_in_reduced_terms() {
+    let (ast, lt, main_expr) = reduce_main(
+        r#"
+    main :: Int -> List Bool -> Bool
+    main = (\g. \x. g) (\l. match l {
+      | Cons x xs -> x
+      | Nil -> false
+    })"#,
+        1,
+    );
+
+    let t = Type::f(
+        Type::int64(),
+        Type::f(
+            Type::Union("List".to_string(), vec![Type::bool()]),
+            Type::bool(),
+        ),
+    );
+    typecheck_reduced_expr(&t, &ast, main_expr, &lt, &TypeMap::new()).unwrap();
+}
@@ -5,28 +5,72 @@ mod wasm_only {
     use super::utils::*;
     use sfl_lib::*;
 
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, HashSet};
     use wasm_bindgen::prelude::*;
 
     #[wasm_bindgen]
     pub struct ParseOptions {
         typed: bool,
         prelude: bool,
+        checker: TypeChecker,
     }
 
     #[wasm_bindgen]
     impl ParseOptions {
         #[wasm_bindgen(constructor)]
         pub fn new(typed: bool, prelude: bool) -> Self {
-            ParseOptions { typed, prelude }
+            ParseOptions {
+                typed,
+                prelude,
+                checker: TypeChecker::Bidirectional,
+            }
+        }
+
+        /// Choose the typechecker by name: "bidirectional", "w" or "j"
+        #[wasm_bindgen]
+        pub fn set_typecheck(&mut self, checker: &str) -> Result<(), String> {
+            self.checker = checker.parse()?;
+            Ok(())
         }
     }
 
+    // A state of the program to pick redexes from. Picking one steps the AST in place, and
+    // leaves the info picked from with only a copy of main and no label table, for the
+    // history. Such a state can only be printed and diffed until it is restored
     #[wasm_bindgen]
     pub struct RawASTInfo {
         pub ast: *mut AST,
         pub lt: *mut KnownTypeLabelTable,
         pub main_expr: usize,
+        // Found again whenever the AST is compacted, which renumbers the module's assignments
+        ctx: ModuleContext,
+        // The steps taken to get here by picking redexes, and the limits to stop picking them at
+        steps: usize,
+        max_steps: usize,
+        max_nodes: usize,
+    }
+
+    impl RawASTInfo {
+        fn new(ast: AST, lt: KnownTypeLabelTable, main_expr: usize) -> Self {
+            Self {
+                ctx: ModuleContext::new(&ast, Some(ast.root)),
+                ast: Box::into_raw(Box::new(ast)),
+                lt: Box::into_raw(Box::new(lt)),
+                main_expr,
+                steps: 0,
+                max_steps: DEFAULT_STEP_LIMIT,
+                max_nodes: DEFAULT_SIZE_BUDGET,
+            }
+        }
+
+        unsafe fn budget_exceeded(&self) -> Option<Outcome> {
+            let config = EvaluationConfig {
+                max_steps: self.max_steps,
+                max_nodes: self.max_nodes,
+                ..Default::default()
+            };
+            config.exceeded(&*self.ast, self.main_expr, self.steps)
+        }
     }
 
     #[wasm_bindgen]
@@ -116,43 +160,140 @@ mod wasm_only {
         info: &mut RawASTInfo,
         rcs: *mut Vec<RawRC>,
         to_subst: usize,
+    ) -> RawASTInfo {
+        pick_rc(info, rcs, to_subst, false)
+    }
+
+    // Like pick_rc_and_free, but keeping arguments shared, for strategies that share them
+    #[wasm_bindgen]
+    pub unsafe fn pick_rc_shared_and_free(
+        info: &mut RawASTInfo,
+        rcs: *mut Vec<RawRC>,
+        to_subst: usize,
+    ) -> RawASTInfo {
+        pick_rc(info, rcs, to_subst, true)
+    }
+
+    unsafe fn pick_rc(
+        info: &mut RawASTInfo,
+        rcs: *mut Vec<RawRC>,
+        to_subst: usize,
+        shared: bool,
     ) -> RawASTInfo {
         let rcs = &*rcs;
 
+        // The step is made in place, and info is left with a copy of main as it was, for the
+        // history. The new info takes the AST over
         let ast = &mut *info.ast;
-        let lt = &*info.lt;
-        let main_expr = info.main_expr;
-        let mut rust_rcs = vec![];
+        let mut before = AST::new();
+        before.root = before.append_shared(ast, info.main_expr);
 
-        for rc in rcs {
-            rust_rcs.push(&*rc.redex);
+        if shared {
+            ast.do_rc_subst_shared(info.main_expr, &*rcs[to_subst].redex);
+        } else {
+            // Equal redexes are offered once, so picking one contracts them all
+            ast.do_rc_subst_replacing_equal(info.main_expr, &*rcs[to_subst].redex);
         }
 
-        let mut ast2 = ast.clone();
-        ast2.do_rc_subst(main_expr, &*rcs[to_subst].redex);
-
         for rc in rcs {
             rc.free();
         }
 
-        // clone to cleanup and remove orphan nodes
-        let ast2 = ast2.clone_node(ast2.root);
+        // Remove the nodes the steps orphaned once there are as many as live ones, keeping
+        // shared nodes shared. Compacting renumbers the module's assignments
+        let mut ctx = std::mem::replace(&mut info.ctx, ModuleContext::new(&before, None));
+        if ast.collect_garbage() {
+            ctx = ModuleContext::new(ast, Some(ast.root));
+        }
 
-        let main_expr = ast2.get_assign_exp(if let Some(main) = ast2.get_main(ast2.root) {
+        let main_expr = ast.get_assign_exp(if let Some(main) = ast.get_main(ast.root) {
             main
         } else {
             panic!("no main, should have been caught by parser")
         });
 
-        RawASTInfo {
-            ast: Box::into_raw(Box::new(ast2)),
-            lt: Box::into_raw(Box::new(lt.clone())),
+        let next = RawASTInfo {
+            ast: info.ast,
+            lt: info.lt,
             main_expr,
+            ctx,
+            steps: info.steps + 1,
+            max_steps: info.max_steps,
+            max_nodes: info.max_nodes,
+        };
+        info.main_expr = before.root;
+        info.ast = Box::into_raw(Box::new(before));
+        info.lt = std::ptr::null_mut();
+        next
+    }
+
+    // Make info, a state picked from before that only keeps main for the history, the state to
+    // pick redexes from again. It takes over the AST of current, the state picked to last, and
+    // current is left with a copy of its main for the history in turn
+    #[wasm_bindgen]
+    pub unsafe fn restore(current: &mut RawASTInfo, info: &mut RawASTInfo) {
+        if !info.lt.is_null() {
+            return;
+        }
+
+        let ast = &mut *current.ast;
+        let mut after = AST::new();
+        after.root = after.append_shared(ast, current.main_expr);
+
+        let snapshot = Box::from_raw(info.ast);
+        ast.replace_with_shared(current.main_expr, &snapshot);
+        let mut ctx = std::mem::replace(&mut current.ctx, ModuleContext::new(&after, None));
+        if ast.collect_garbage() {
+            ctx = ModuleContext::new(ast, Some(ast.root));
         }
+
+        info.main_expr = ast.get_assign_exp(
+            ast.get_main(ast.root)
+                .expect("no main, should have been caught by parser"),
+        );
+        info.ast = current.ast;
+        info.lt = current.lt;
+        info.ctx = ctx;
+        info.max_steps = current.max_steps;
+        info.max_nodes = current.max_nodes;
+
+        current.main_expr = after.root;
+        current.ast = Box::into_raw(Box::new(after));
+        current.lt = std::ptr::null_mut();
+    }
+
+    // Stop offering redexes once the steps picked from info on have reached max_steps, or main
+    // has grown past max_nodes nodes, so stepping through a reduction that never ends stops
+    // like reduce_main_within_budget does. The limits are default_step_limit and
+    // default_size_budget until set
+    #[wasm_bindgen]
+    pub fn set_step_budget(info: &mut RawASTInfo, max_steps: usize, max_nodes: usize) {
+        info.max_steps = max_steps;
+        info.max_nodes = max_nodes;
+    }
+
+    // Why no redexes are offered for info even though main may not be in normal form, such as
+    // "budget exceeded: main grew past 1000 nodes"
+    #[wasm_bindgen]
+    pub unsafe fn step_budget_exceeded(info: &RawASTInfo) -> Option<String> {
+        info.budget_exceeded().map(|outcome| outcome.to_string())
     }
 
     #[wasm_bindgen]
     pub unsafe fn get_all_redexes(info: &RawASTInfo) -> *mut Vec<RawRC> {
+        get_all_redexes_with_rules(info, false, false)
+    }
+
+    // Like get_all_redexes, also finding redexes under binders and eta-redexes if asked
+    #[wasm_bindgen]
+    pub unsafe fn get_all_redexes_with_rules(
+        info: &RawASTInfo,
+        under_lambdas: bool,
+        eta: bool,
+    ) -> *mut Vec<RawRC> {
+        if info.budget_exceeded().is_some() {
+            return Box::into_raw(Box::new(vec![]));
+        }
         let info = info;
         let ast = &mut *info.ast;
         let lt = &*info.lt;
@@ -160,8 +301,16 @@ mod wasm_only {
         let main_assign = ast.get_assign_to(module, "main".to_string()).unwrap();
         let main_expr = ast.get_assign_exp(main_assign);
         let mut rcs_output: Vec<RawRC> = vec![];
-        let rcs = find_all_redex_contraction_pairs(&ast, Some(ast.root), main_expr, &lt);
-        for rc in ast.filter_identical_rcs(&rcs) {
+        let rules = ReductionRules { under_lambdas, eta };
+        // Equal redexes are offered once, and only that one is contracted, as an opaque redex is
+        // evaluated to contract it
+        let mut seen = HashSet::new();
+        let redexes = info.ctx.find_all_redexes(ast, main_expr, lt, rules);
+        for redex in redexes {
+            if !seen.insert(ast.to_string_sugar(redex.from, false)) {
+                continue;
+            }
+            let rc = info.ctx.contract(ast, redex, lt, rules);
             let from_str = Box::into_raw(Box::new(ast.to_string_sugar(rc.from, false).clone()));
             let to_str = Box::into_raw(Box::new(rc.to.to_string_sugar(rc.to.root, false).clone()));
             let msg1 = Box::into_raw(Box::new(rc.msg_before.to_string()));
@@ -179,6 +328,9 @@ mod wasm_only {
 
     #[wasm_bindgen]
     pub unsafe fn get_one_redex(info: &RawASTInfo) -> *mut Vec<RawRC> {
+        if info.budget_exceeded().is_some() {
+            return Box::into_raw(Box::new(vec![]));
+        }
         let info = info;
         let ast = &mut *info.ast;
         let lt = &*info.lt;
@@ -193,9 +345,12 @@ mod wasm_only {
         let main_expr = ast.get_assign_exp(main_assign);
 
         Box::into_raw(Box::new(
-            if let Some(rc) =
-                find_single_redex_contraction_pair(&ast, Some(ast.root), main_expr, &lt)
-            {
+            if let Some(rc) = info.ctx.find_single_redex_contraction_pair(
+                ast,
+                main_expr,
+                lt,
+                ReductionRules::default(),
+            ) {
                 let from_str = Box::into_raw(Box::new(ast.to_string_sugar(rc.from, false).clone()));
                 let to_str =
                     Box::into_raw(Box::new(rc.to.to_string_sugar(rc.to.root, false).clone()));
@@ -214,6 +369,136 @@ mod wasm_only {
         ))
     }
 
+    /// An evaluation strategy, kept between steps so a seeded random order carries on
+    #[wasm_bindgen]
+    pub struct RawStrategy {
+        strategy: Box<dyn EvaluationStrategy>,
+        rules: ReductionRules,
+    }
+
+    /// The strategy with the given name from STRATEGY_NAMES: normal, need, applicative,
+    /// innermost-rightmost or random[:seed]
+    #[wasm_bindgen]
+    pub fn new_strategy(name: &str) -> Result<RawStrategy, String> {
+        Ok(RawStrategy {
+            strategy: strategy_from_name(name)?,
+            rules: ReductionRules::default(),
+        })
+    }
+
+    // Whether the strategy reduces under binders and eta-reduces, which is off by default.
+    // Strategies that share arguments can't reduce under binders
+    #[wasm_bindgen]
+    pub fn strategy_set_rules(
+        strategy: &mut RawStrategy,
+        under_lambdas: bool,
+        eta: bool,
+    ) -> Result<(), String> {
+        if under_lambdas && strategy.strategy.shares_arguments() {
+            return Err(format!(
+                "The {} strategy can't reduce under lambdas",
+                strategy.strategy.name()
+            ));
+        }
+        strategy.rules = ReductionRules { under_lambdas, eta };
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn strategy_name(strategy: &RawStrategy) -> String {
+        strategy.strategy.name().to_string()
+    }
+
+    // The redex the strategy picks next, like get_one_redex
+    #[wasm_bindgen]
+    pub unsafe fn get_strategy_redex(
+        info: &RawASTInfo,
+        strategy: &mut RawStrategy,
+    ) -> *mut Vec<RawRC> {
+        if info.budget_exceeded().is_some() {
+            return Box::into_raw(Box::new(vec![]));
+        }
+        let ast = &mut *info.ast;
+        let lt = &*info.lt;
+        let module = ast.root;
+
+        let main_assign = if let Some(main) = ast.get_assign_to(module, "main".to_string()) {
+            main
+        } else {
+            return Box::into_raw(Box::new(vec![]));
+        };
+
+        let main_expr = ast.get_assign_exp(main_assign);
+
+        Box::into_raw(Box::new(
+            if let Some(rc) =
+                strategy
+                    .strategy
+                    .next_redex_in(ast, &info.ctx, main_expr, lt, strategy.rules)
+            {
+                let from_str = Box::into_raw(Box::new(ast.to_string_sugar(rc.from, false)));
+                let to_str = Box::into_raw(Box::new(rc.to.to_string_sugar(rc.to.root, false)));
+                let msg1 = Box::into_raw(Box::new(rc.msg_before.to_string()));
+                let msg2 = Box::into_raw(Box::new(rc.msg_after.to_string()));
+                vec![RawRC {
+                    from_str,
+                    to_str,
+                    msg1,
+                    msg2,
+                    redex: Box::into_raw(Box::new(rc)),
+                }]
+            } else {
+                vec![]
+            },
+        ))
+    }
+
+    /// Watches the states of a reduction, one per step, for one that repeats or grows too big
+    #[wasm_bindgen]
+    pub struct RawTerminationChecker {
+        checker: TerminationChecker,
+    }
+
+    // Loops should only be looked for if the strategy picks the same redex whenever it sees the
+    // same term, as strategy_depends_only_on_expression says
+    #[wasm_bindgen]
+    pub fn new_termination_checker(
+        size_budget: usize,
+        detect_loops: bool,
+    ) -> RawTerminationChecker {
+        RawTerminationChecker {
+            checker: TerminationChecker::new(size_budget, detect_loops),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn default_size_budget() -> usize {
+        DEFAULT_SIZE_BUDGET
+    }
+
+    // The steps to stop a reduction after when the user hasn't picked a limit
+    #[wasm_bindgen]
+    pub fn default_step_limit() -> usize {
+        DEFAULT_STEP_LIMIT
+    }
+
+    // Check main after the next step, starting with before the first. Gives why the reduction
+    // doesn't terminate, such as "this reduction loops (state at step 12 = step 4)", if it
+    // doesn't
+    #[wasm_bindgen]
+    pub unsafe fn check_termination(
+        checker: &mut RawTerminationChecker,
+        info: &RawASTInfo,
+    ) -> Option<String> {
+        let ast = &*info.ast;
+        let main_expr = ast.get_assign_exp(ast.get_main(ast.root)?);
+        checker
+            .checker
+            .check(ast, main_expr)
+            .err()
+            .map(|e| e.to_string())
+    }
+
     #[wasm_bindgen]
     pub fn parse(str: &str, parse_options: ParseOptions) -> Result<RawASTInfo, String> {
         let pr = match Parser::from_string(str.to_string()).parse_module(parse_options.prelude) {
@@ -226,7 +511,7 @@ mod wasm_only {
         let module = ast.root;
 
         if parse_options.typed {
-            match typecheck(&mut ast, module, &mut lt, &tm) {
+            match typecheck_with(parse_options.checker, &mut ast, module, &mut lt, &tm) {
                 Ok(_) => {}
                 Err(e) => return Err(format!("{:?}", e)),
             };
@@ -238,11 +523,7 @@ mod wasm_only {
             panic!("no main, should have been caught by parser")
         });
 
-        Ok(RawASTInfo {
-            ast: Box::into_raw(Box::new(ast)),
-            lt: Box::into_raw(Box::new(lt)),
-            main_expr,
-        })
+        Ok(RawASTInfo::new(ast, lt, main_expr))
     }
 
     #[wasm_bindgen]
@@ -285,7 +566,325 @@ mod wasm_only {
 
     #[wasm_bindgen]
     pub unsafe fn main_to_string(info: &RawASTInfo) -> String {
-        let info = info;
+        (*info.ast).to_string_sugar(info.main_expr, true)
+    }
+
+    // main with each thunk bound once by a let, for strategies that share arguments
+    #[wasm_bindgen]
+    pub unsafe fn main_to_string_shared(info: &RawASTInfo) -> String {
+        (*info.ast).to_string_shared(info.main_expr)
+    }
+
+    /// A reduction of main, run until it reached normal form or had to stop
+    #[wasm_bindgen]
+    pub struct RawReduction {
+        reduction: Reduction,
+        ast: AST,
+        lt: KnownTypeLabelTable,
+    }
+
+    // Reduce main with strategy until it is in normal form, or it has taken max_steps steps,
+    // grown past max_nodes nodes or loops. cancel, if given, is called with the number of steps
+    // taken before each step and stops the reduction if it returns true or throws, so a long
+    // reduction can be cut off at a deadline rather than hang the tab
+    #[wasm_bindgen]
+    pub unsafe fn reduce_main_within_budget(
+        info: &RawASTInfo,
+        strategy: &mut RawStrategy,
+        max_steps: usize,
+        max_nodes: usize,
+        cancel: Option<js_sys::Function>,
+    ) -> RawReduction {
+        let mut ast = (*info.ast).clone();
+        let lt = (*info.lt).clone();
+        let config = EvaluationConfig {
+            max_steps,
+            max_nodes,
+            cancel: cancel.map(|cancel| -> Box<dyn FnMut(usize) -> bool> {
+                Box::new(move |steps| {
+                    cancel
+                        .call1(&JsValue::NULL, &JsValue::from(steps as u32))
+                        .map_or(true, |cancelled| cancelled.is_truthy())
+                })
+            }),
+            rules: strategy.rules,
+            ..Default::default()
+        };
+        let reduction = reduce_within_budget(&mut ast, &lt, &mut *strategy.strategy, config);
+        RawReduction { reduction, ast, lt }
+    }
+
+    // Why the reduction stopped, such as "budget exceeded: stopped after 1000 steps"
+    #[wasm_bindgen]
+    pub fn reduction_outcome(reduction: &RawReduction) -> String {
+        reduction.reduction.outcome.to_string()
+    }
+
+    // "steps" or "nodes" if the reduction stopped at that limit
+    #[wasm_bindgen]
+    pub fn reduction_budget_exceeded(reduction: &RawReduction) -> Option<String> {
+        match reduction.reduction.outcome {
+            Outcome::BudgetExceeded {
+                budget: Budget::Steps,
+                ..
+            } => Some("steps".to_string()),
+            Outcome::BudgetExceeded {
+                budget: Budget::Nodes,
+                ..
+            } => Some("nodes".to_string()),
+            _ => None,
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn reduction_cancelled(reduction: &RawReduction) -> bool {
+        reduction.reduction.outcome == Outcome::Cancelled
+    }
+
+    #[wasm_bindgen]
+    pub fn reduction_start(reduction: &RawReduction) -> String {
+        reduction.reduction.start.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn reduction_len(reduction: &RawReduction) -> usize {
+        reduction.reduction.steps.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn reduction_step_redex(reduction: &RawReduction, index: usize) -> String {
+        reduction.reduction.steps[index].redex.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn reduction_step_contractum(reduction: &RawReduction, index: usize) -> String {
+        reduction.reduction.steps[index].contractum.clone()
+    }
+
+    // The name of the rule the step used, such as "function" or "match"
+    #[wasm_bindgen]
+    pub fn reduction_step_kind(reduction: &RawReduction, index: usize) -> String {
+        reduction.reduction.steps[index].kind.name().to_string()
+    }
+
+    #[wasm_bindgen]
+    pub fn reduction_step_msg_before(reduction: &RawReduction, index: usize) -> String {
+        reduction.reduction.steps[index].msg_before.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn reduction_step_msg_after(reduction: &RawReduction, index: usize) -> String {
+        reduction.reduction.steps[index].msg_after.clone()
+    }
+
+    // The same as reduction_step_msg_after, which it was called before steps had both messages
+    #[wasm_bindgen]
+    pub fn reduction_step_message(reduction: &RawReduction, index: usize) -> String {
+        reduction.reduction.steps[index].msg_after.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn reduction_step_term(reduction: &RawReduction, index: usize) -> String {
+        reduction.reduction.steps[index].term.clone()
+    }
+
+    // The whole reduction as a JSON trace, in the format sfl --trace json prints
+    #[wasm_bindgen]
+    pub fn reduction_to_json(reduction: &RawReduction) -> String {
+        reduction.reduction.to_json()
+    }
+
+    // Where the reduction stopped, to carry on stepping from
+    #[wasm_bindgen]
+    pub fn reduction_info(reduction: &RawReduction) -> RawASTInfo {
+        let ast = reduction.ast.clone();
+        let main_expr = ast.get_assign_exp(
+            ast.get_main(ast.root)
+                .expect("no main, should have been caught by parser"),
+        );
+        RawASTInfo::new(ast, reduction.lt.clone(), main_expr)
+    }
+
+    /// A reduction explored by hand, keeping every state reached so it can be undone, redone,
+    /// jumped back to and forked
+    #[wasm_bindgen]
+    pub struct RawSession {
+        session: ReductionSession,
+    }
+
+    #[wasm_bindgen]
+    pub unsafe fn new_session(info: &RawASTInfo, shares: bool) -> Result<RawSession, String> {
+        Ok(RawSession {
+            session: ReductionSession::new((*info.ast).clone(), (*info.lt).clone(), shares)?,
+        })
+    }
+
+    // The redexes in the current state, like get_all_redexes, to pick one with session_pick
+    #[wasm_bindgen]
+    pub fn session_redexes(session: &RawSession) -> *mut Vec<RawRC> {
+        let ast = session.session.ast();
+        let rcs = session
+            .session
+            .redexes()
+            .into_iter()
+            .map(|rc| RawRC {
+                from_str: Box::into_raw(Box::new(ast.to_string_sugar(rc.from, false))),
+                to_str: Box::into_raw(Box::new(rc.to.to_string_sugar(rc.to.root, false))),
+                msg1: Box::into_raw(Box::new(rc.msg_before.to_string())),
+                msg2: Box::into_raw(Box::new(rc.msg_after.to_string())),
+                redex: Box::into_raw(Box::new(rc)),
+            })
+            .collect();
+        Box::into_raw(Box::new(rcs))
+    }
+
+    // Whether the session's redexes, and the steps strategies take in it, include ones under
+    // binders and eta-redexes from now on
+    #[wasm_bindgen]
+    pub fn session_set_rules(session: &mut RawSession, under_lambdas: bool, eta: bool) {
+        session
+            .session
+            .set_rules(ReductionRules { under_lambdas, eta });
+    }
+
+    // Contract one of the redexes from session_redexes and free them. Gives the state moved to
+    #[wasm_bindgen]
+    pub unsafe fn session_pick(
+        session: &mut RawSession,
+        rcs: *mut Vec<RawRC>,
+        to_subst: usize,
+    ) -> usize {
+        let rcs = Box::from_raw(rcs);
+        let state = session.session.step(&*rcs[to_subst].redex);
+        for rc in rcs.iter() {
+            rc.free();
+        }
+        state
+    }
+
+    // Take the step strategy picks, if main isn't in normal form. Gives the state moved to
+    #[wasm_bindgen]
+    pub fn session_step_with(
+        session: &mut RawSession,
+        strategy: &mut RawStrategy,
+    ) -> Option<usize> {
+        session.session.step_with(&mut *strategy.strategy)
+    }
+
+    #[wasm_bindgen]
+    pub fn session_undo(session: &mut RawSession) -> bool {
+        session.session.undo()
+    }
+
+    #[wasm_bindgen]
+    pub fn session_redo(session: &mut RawSession) -> bool {
+        session.session.redo()
+    }
+
+    #[wasm_bindgen]
+    pub fn session_jump(session: &mut RawSession, state: usize) -> Result<(), String> {
+        session.session.jump(state)
+    }
+
+    #[wasm_bindgen]
+    pub fn session_current(session: &RawSession) -> usize {
+        session.session.current()
+    }
+
+    #[wasm_bindgen]
+    pub fn session_state_count(session: &RawSession) -> usize {
+        session.session.state_count()
+    }
+
+    #[wasm_bindgen]
+    pub fn session_parent(session: &RawSession, state: usize) -> Option<usize> {
+        session.session.parent(state)
+    }
+
+    #[wasm_bindgen]
+    pub fn session_children_len(session: &RawSession, state: usize) -> usize {
+        session.session.children(state).len()
+    }
+
+    #[wasm_bindgen]
+    pub fn session_child(session: &RawSession, state: usize, index: usize) -> usize {
+        session.session.children(state)[index]
+    }
+
+    #[wasm_bindgen]
+    pub fn session_term_at(session: &RawSession, state: usize) -> String {
+        session.session.term_at(state)
+    }
+
+    // What the step that reached state did, such as "Applied inbuilt + to 1 and 2"
+    #[wasm_bindgen]
+    pub fn session_step_message(session: &RawSession, state: usize) -> Option<String> {
+        session
+            .session
+            .step_to(state)
+            .map(|step| step.msg_after.clone())
+    }
+
+    // The program in the current state, for the functions that take a RawASTInfo
+    #[wasm_bindgen]
+    pub fn session_info(session: &RawSession) -> RawASTInfo {
+        let ast = session.session.current_ast();
+        let main_expr = ast.get_assign_exp(
+            ast.get_main(ast.root)
+                .expect("no main, should have been caught by parser"),
+        );
+        RawASTInfo::new(ast, session.session.lt().clone(), main_expr)
+    }
+
+    // The graph of every term main can reduce to, up to max_terms of them, as "dot" or "json"
+    #[wasm_bindgen]
+    pub unsafe fn reduction_graph(
+        info: &RawASTInfo,
+        max_terms: usize,
+        format: &str,
+    ) -> Result<String, String> {
+        reduction_graph_with_rules(info, max_terms, format, false, false)
+    }
+
+    // Like reduction_graph, also taking steps under binders and eta steps if asked
+    #[wasm_bindgen]
+    pub unsafe fn reduction_graph_with_rules(
+        info: &RawASTInfo,
+        max_terms: usize,
+        format: &str,
+        under_lambdas: bool,
+        eta: bool,
+    ) -> Result<String, String> {
+        let rules = ReductionRules { under_lambdas, eta };
+        let graph = explore_reductions_with(&*info.ast, &*info.lt, max_terms, rules);
+        match format {
+            "dot" => Ok(graph.to_dot()),
+            "json" => Ok(graph.to_json()),
+            _ => Err(format!(
+                "Unknown graph format {}, expected dot or json",
+                format
+            )),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn default_graph_bound() -> usize {
+        DEFAULT_GRAPH_BOUND
+    }
+
+    #[wasm_bindgen]
+    pub fn strategy_shares_arguments(strategy: &RawStrategy) -> bool {
+        strategy.strategy.shares_arguments()
+    }
+
+    #[wasm_bindgen]
+    pub fn strategy_depends_only_on_expression(strategy: &RawStrategy) -> bool {
+        strategy.strategy.depends_only_on_expression()
+    }
+
+    // The value of main from the interpreter, without the steps to it
+    #[wasm_bindgen]
+    pub unsafe fn evaluate_main(info: &RawASTInfo) -> String {
         let ast = &*info.ast;
 
         let main_assign = if let Some(main) = ast.get_main(ast.root) {
@@ -294,8 +893,25 @@ mod wasm_only {
             return String::new();
         };
 
-        let main_expr = ast.get_assign_exp(main_assign);
-        ast.to_string_sugar(main_expr, true)
+        let value = evaluate(ast, ast.root, ast.get_assign_exp(main_assign), &*info.lt);
+        value.to_string_sugar(value.root, false)
+    }
+
+    // Make calls to label evaluate to a value in one step, as an OPAQUE pragma does. Sessions
+    // and reductions started after this see it
+    #[wasm_bindgen]
+    pub unsafe fn set_opaque(info: &RawASTInfo, label: &str) -> Result<(), String> {
+        (*info.lt).set_opaque(label)
+    }
+
+    // Make every label the prelude defines opaque
+    #[wasm_bindgen]
+    pub unsafe fn set_prelude_opaque(info: &RawASTInfo) -> Result<(), String> {
+        let ast = &*info.ast;
+        for label in ast.get_prelude_labels(ast.root) {
+            (*info.lt).set_opaque(&label)?;
+        }
+        Ok(())
     }
 
     #[wasm_bindgen]
@@ -315,14 +931,13 @@ mod wasm_only {
 
     #[wasm_bindgen]
     pub unsafe fn diff(ast1: &RawASTInfo, ast2: &RawASTInfo) -> RawDiff {
-        let ast1 = &*ast1.ast;
-        let ast2 = &*ast2.ast;
-
-        let ast1_main = ast1.get_assign_exp(ast1.get_main(ast1.root).unwrap());
-        let ast2_main = ast2.get_assign_exp(ast2.get_main(ast2.root).unwrap());
-
         RawDiff {
-            diff: Box::into_raw(Box::new(AST::diff(ast1, ast2, ast1_main, ast2_main))),
+            diff: Box::into_raw(Box::new(AST::diff(
+                &*ast1.ast,
+                &*ast2.ast,
+                ast1.main_expr,
+                ast2.main_expr,
+            ))),
         }
     }
 
@@ -352,6 +967,256 @@ mod wasm_only {
         }
     }
 
+    struct DerivationRow {
+        label: String,
+        depth: usize,
+        rule: String,
+        judgement: String,
+        input: String,
+        output: Option<String>,
+    }
+
+    /// The typing derivations of every label in a program, flattened in pre-order
+    #[wasm_bindgen]
+    pub struct RawDerivations {
+        rows: Vec<DerivationRow>,
+        error: Option<String>,
+    }
+
+    fn context_to_string(items: &[String]) -> String {
+        format!("[{}]", items.join(", "))
+    }
+
+    #[wasm_bindgen]
+    pub fn get_derivations(
+        str: &str,
+        parse_options: ParseOptions,
+    ) -> Result<RawDerivations, String> {
+        let pr = match Parser::from_string(str.to_string()).parse_module(parse_options.prelude) {
+            Ok(ast) => ast,
+            Err(e) => return Err(format!("{:?}", e)),
+        };
+        let mut ast = pr.ast;
+        let mut lt = pr.lt;
+        let module = ast.root;
+
+        let (result, derivations) = typecheck_with_derivations(&mut ast, module, &mut lt, &pr.tm);
+
+        let mut rows = vec![];
+        for (label, derivation) in &derivations {
+            for (depth, d) in derivation.flatten() {
+                rows.push(DerivationRow {
+                    label: label.clone(),
+                    depth,
+                    rule: d.rule.clone(),
+                    judgement: d.judgement.clone(),
+                    input: context_to_string(&d.input),
+                    output: d.output.as_deref().map(context_to_string),
+                });
+            }
+        }
+
+        Ok(RawDerivations {
+            rows,
+            error: result.err().map(|e| format!("{:?}", e)),
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn derivations_error(derivations: &RawDerivations) -> Option<String> {
+        derivations.error.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn derivations_len(derivations: &RawDerivations) -> usize {
+        derivations.rows.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn derivation_label(derivations: &RawDerivations, index: usize) -> String {
+        derivations.rows[index].label.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn derivation_depth(derivations: &RawDerivations, index: usize) -> usize {
+        derivations.rows[index].depth
+    }
+
+    #[wasm_bindgen]
+    pub fn derivation_rule(derivations: &RawDerivations, index: usize) -> String {
+        derivations.rows[index].rule.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn derivation_judgement(derivations: &RawDerivations, index: usize) -> String {
+        derivations.rows[index].judgement.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn derivation_input(derivations: &RawDerivations, index: usize) -> String {
+        derivations.rows[index].input.clone()
+    }
+
+    /// The output context, or None if the rule failed
+    #[wasm_bindgen]
+    pub fn derivation_output(derivations: &RawDerivations, index: usize) -> Option<String> {
+        derivations.rows[index].output.clone()
+    }
+
+    struct UnificationRow {
+        label: String,
+        unification: String,
+    }
+
+    /// The unifications made by Hindley–Milner inference on a program, in order
+    #[wasm_bindgen]
+    pub struct RawUnifications {
+        rows: Vec<UnificationRow>,
+        error: Option<String>,
+    }
+
+    /// Typecheck a program with Algorithm W or J ("w" or "j"), recording its unifications. Those
+    /// for the prelude are left out when it is included
+    #[wasm_bindgen]
+    pub fn get_unifications(
+        str: &str,
+        parse_options: ParseOptions,
+        algorithm: &str,
+    ) -> Result<RawUnifications, String> {
+        let algorithm: Algorithm = algorithm.parse()?;
+        let pr = match Parser::from_string(str.to_string()).parse_module(parse_options.prelude) {
+            Ok(ast) => ast,
+            Err(e) => return Err(format!("{:?}", e)),
+        };
+        let mut ast = pr.ast;
+        let mut lt = pr.lt;
+        let module = ast.root;
+
+        let (result, traces) = typecheck_hm(&mut ast, module, &mut lt, &pr.tm, algorithm);
+
+        let mut rows = vec![];
+        for (label, trace) in &traces {
+            if trace.iter().all(|u| ast.is_from_prelude(u.node)) {
+                continue;
+            }
+            for unification in trace {
+                rows.push(UnificationRow {
+                    label: label.clone(),
+                    unification: unification.to_string(),
+                });
+            }
+        }
+
+        Ok(RawUnifications {
+            rows,
+            error: result.err().map(|e| format!("{:?}", e)),
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn unifications_error(unifications: &RawUnifications) -> Option<String> {
+        unifications.error.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn unifications_len(unifications: &RawUnifications) -> usize {
+        unifications.rows.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn unification_label(unifications: &RawUnifications, index: usize) -> String {
+        unifications.rows[index].label.clone()
+    }
+
+    /// The unification as "A ~ B  [bindings]", or with why it failed
+    #[wasm_bindgen]
+    pub fn unification_string(unifications: &RawUnifications, index: usize) -> String {
+        unifications.rows[index].unification.clone()
+    }
+
+    /// Informational notes on the type assignments of a program: those less general than the
+    /// types of their definitions
+    #[wasm_bindgen]
+    pub struct RawTypeLints {
+        lints: Vec<TypeLint>,
+    }
+
+    /// Lint the type assignments of a program. A program that doesn't typecheck has no lints
+    #[wasm_bindgen]
+    pub fn get_type_lints(str: &str, parse_options: ParseOptions) -> Result<RawTypeLints, String> {
+        let pr = match Parser::from_string(str.to_string()).parse_module(parse_options.prelude) {
+            Ok(ast) => ast,
+            Err(e) => return Err(format!("{:?}", e)),
+        };
+        let module = pr.ast.root;
+
+        Ok(RawTypeLints {
+            lints: lint_declared_types(&pr.ast, module, &pr.lt, &pr.tm),
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn type_lints_len(lints: &RawTypeLints) -> usize {
+        lints.lints.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn type_lint_message(lints: &RawTypeLints, index: usize) -> String {
+        lints.lints[index].to_string()
+    }
+
+    /// Where the label the lint is about is defined, with line and col starting from 0
+    #[wasm_bindgen]
+    pub fn type_lint_line(lints: &RawTypeLints, index: usize) -> usize {
+        lints.lints[index].line
+    }
+
+    #[wasm_bindgen]
+    pub fn type_lint_col(lints: &RawTypeLints, index: usize) -> usize {
+        lints.lints[index].col
+    }
+
+    /// The types found for the nodes of a program, for looking up the type at a position
+    #[wasm_bindgen]
+    pub struct RawTypeTable {
+        ast: AST,
+        types: TypeTable,
+        error: Option<String>,
+    }
+
+    #[wasm_bindgen]
+    pub fn get_type_table(str: &str, parse_options: ParseOptions) -> Result<RawTypeTable, String> {
+        let pr = match Parser::from_string(str.to_string()).parse_module(parse_options.prelude) {
+            Ok(ast) => ast,
+            Err(e) => return Err(format!("{:?}", e)),
+        };
+        let mut ast = pr.ast;
+        let mut lt = pr.lt;
+        let module = ast.root;
+
+        let (result, types) = typecheck_with_types(&mut ast, module, &mut lt, &pr.tm);
+
+        Ok(RawTypeTable {
+            ast,
+            types,
+            error: result.err().map(|e| format!("{:?}", e)),
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn type_table_error(table: &RawTypeTable) -> Option<String> {
+        table.error.clone()
+    }
+
+    /// The type of the innermost expression at a position, with line and col starting from 0
+    #[wasm_bindgen]
+    pub fn type_at(table: &RawTypeTable, line: usize, col: usize) -> Option<String> {
+        table
+            .types
+            .type_at(&table.ast, line, col)
+            .map(|(_, t)| t.to_string())
+    }
+
     #[wasm_bindgen]
     pub struct StringPair {
         str1: String,